@@ -1,10 +1,12 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { BankFormat } from "./BankFormat";
+import type { CustomFormat } from "./CustomFormat";
 import type { ExtendedLedger } from "./ExtendedLedger";
+import type { Money } from "./Money";
 import type { Owner } from "./Owner";
 
 export type Account = { id: string, 
 /**
  * The OIDC owner
  */
-owner: Owner, name: string, format: BankFormat, ledgers: Array<ExtendedLedger>, initial_balance: number | null, initial_date: number, spending: boolean, };
\ No newline at end of file
+owner: Owner, name: string, format: BankFormat, custom_format: CustomFormat | null, ledgers: Array<ExtendedLedger>, initial_balance: Money | null, initial_date: number, spending: boolean, };
\ No newline at end of file
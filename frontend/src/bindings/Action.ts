@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What kind of transaction a record is.
+ */
+export type Action = "Update" | "Interest" | "Fee" | "Dividend" | "Tax";
\ No newline at end of file
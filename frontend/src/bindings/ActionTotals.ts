@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Currency } from "./Currency";
+import type { Money } from "./Money";
+
+/**
+ * The dividends, interest, taxes and fees booked in one currency during a year.
+ */
+export type ActionTotals = { year: number, currency: Currency, dividends: Money, interest: Money, 
+/**
+ * Negative for taxes paid.
+ */
+taxes: Money, 
+/**
+ * Negative for fees paid.
+ */
+fees: Money, };
\ No newline at end of file
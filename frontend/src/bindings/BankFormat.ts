@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type BankFormat = "ubs" | "neon" | "revolut" | "ibkr" | "wise";
\ No newline at end of file
+export type BankFormat = "ubs" | "neon" | "revolut" | "ibkr" | "wise" | "camt" | "ofx" | "custom";
\ No newline at end of file
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ConfirmFileRequest = { filename: string, 
+/**
+ * The token returned by the preview.
+ */
+token: string, };
\ No newline at end of file
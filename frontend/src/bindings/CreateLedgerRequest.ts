@@ -1,4 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { BankFormat } from "./BankFormat";
+import type { CustomFormat } from "./CustomFormat";
+import type { Money } from "./Money";
 
-export type CreateLedgerRequest = { format: BankFormat, initialBalance: number | null, initialDate: number, name: string, spending: boolean, };
\ No newline at end of file
+export type CreateLedgerRequest = { format: BankFormat, customFormat?: CustomFormat, initialBalance: Money | null, initialDate: number, name: string, spending: boolean, };
\ No newline at end of file
@@ -0,0 +1,52 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Column } from "./Column";
+import type { Currency } from "./Currency";
+import type { RowFilter } from "./RowFilter";
+
+export type CustomFormat = { delimiter: string, 
+/**
+ * Zero based line of the header. The lines before it are skipped. Without a header row
+ * the columns have to be given by their index.
+ */
+header_row: number | null, 
+/**
+ * The first column if not given.
+ */
+date: Column, 
+/**
+ * A chrono format string like `%Y-%m-%d`.
+ */
+date_format: string, 
+/**
+ * A single signed amount column. Alternatively `debit` and `credit` can be given.
+ */
+amount: Column | null, 
+/**
+ * Outgoing amounts, which are booked as negative independent of their sign in the file.
+ */
+debit: Column | null, 
+/**
+ * Incoming amounts, which are booked as positive independent of their sign in the file.
+ * It is used if the debit is empty or zero.
+ */
+credit: Column | null, thousands_separator: string | null, decimal_separator: string, 
+/**
+ * The description columns, non-empty values are joined with `, `.
+ */
+description: Array<Column>, category: Column | null, 
+/**
+ * Unique transaction id of the bank, used to detect duplicates across files.
+ */
+id: Column | null, 
+/**
+ * Column containing the currency of each row. Rows are split into a ledger per currency.
+ */
+currency_column: Column | null, 
+/**
+ * Currency of all rows if there is no currency column.
+ */
+currency: Currency, 
+/**
+ * Only rows matching all filters are imported.
+ */
+filters: Array<RowFilter>, };
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Currency } from "./Currency";
+
+export type Derivation = { "kind": "fetched" } | { "kind": "inverse" } | { "kind": "cross", via: Currency, };
\ No newline at end of file
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BankFormat } from "./BankFormat";
+import type { Currency } from "./Currency";
+import type { Detection } from "./Detection";
+
+export type DetectResponse = { 
+/**
+ * The format the file clearly is in, if any.
+ */
+format: BankFormat | null, 
+/**
+ * The currencies of the cash ledgers in the file.
+ */
+currencies: Array<Currency>, 
+/**
+ * All formats the file might be in, best match first.
+ */
+candidates: Array<Detection>, };
\ No newline at end of file
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BankFormat } from "./BankFormat";
+
+export type Detection = { format: BankFormat, 
+/**
+ * Between 0 (certainly not this format) and 1 (certainly this format).
+ */
+score: number, };
\ No newline at end of file
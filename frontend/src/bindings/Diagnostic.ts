@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Severity } from "./Severity";
+
+/**
+ * A problem found in a file, located as precisely as the format allows.
+ */
+export type Diagnostic = { 
+/**
+ * The name of the file, which is only known once the parser is done.
+ */
+file: string, 
+/**
+ * The one based line in the file.
+ */
+line: number | null, 
+/**
+ * The name of the column or field.
+ */
+column: string | null, severity: Severity, message: string, };
\ No newline at end of file
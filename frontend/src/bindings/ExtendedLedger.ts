@@ -1,6 +1,17 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Currency } from "./Currency";
 import type { ExtendedLedgerRecord } from "./ExtendedLedgerRecord";
 import type { LedgerKind } from "./LedgerKind";
+import type { Split } from "./Split";
+import type { Statement } from "./Statement";
 import type { Symbol } from "./Symbol";
 
-export type ExtendedLedger = { name: string, symbol: Symbol, records: Array<ExtendedLedgerRecord>, kind: LedgerKind, };
\ No newline at end of file
+export type ExtendedLedger = { name: string, symbol: Symbol, records: Array<ExtendedLedgerRecord>, kind: LedgerKind, statements: Array<Statement>, 
+/**
+ * The currency a stock is traded in, which its price has to be converted from.
+ */
+quote_currency: Currency | null, 
+/**
+ * The splits the quantities of the records were adjusted for.
+ */
+splits: Array<Split>, };
\ No newline at end of file
@@ -1,3 +1,17 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Action } from "./Action";
+import type { Money } from "./Money";
 
-export type ExtendedLedgerRecord = { date: number, amount: number, description: string, original_description: string, category: string, original_category: string, comments: string, checked: boolean, };
\ No newline at end of file
+export type ExtendedLedgerRecord = { 
+/**
+ * A stable identity of the record that survives reloading the ledger files.
+ */
+id: string, date: number, amount: Money, description: string, original_description: string, category: string, original_category: string, comments: string, checked: boolean, 
+/**
+ * The id of the account on the other side if this record is an internal transfer.
+ */
+transfer: string | null, action: Action, 
+/**
+ * The price of a single share for trades of a stock, in its quote currency.
+ */
+price: number | null, };
\ No newline at end of file
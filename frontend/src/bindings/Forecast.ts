@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RecurringFlowData } from "./RecurringFlowData";
+
+/**
+ * The simulated total at the end of each month after the series.
+ */
+export type Forecast = { timestamps: number[], median: Array<number>, 
+/**
+ * The 10th percentile.
+ */
+low: Array<number>, 
+/**
+ * The 90th percentile.
+ */
+high: Array<number>, 
+/**
+ * The expected net income per month, including the irregular flows.
+ */
+monthly_flow: number, recurring: Array<RecurringFlowData>, };
\ No newline at end of file
@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Currency } from "./Currency";
+
+/**
+ * A stock held in an account.
+ *
+ * Prices and cost bases are in the quote currency of the stock, values and gains in the base
+ * currency of the portfolio.
+ */
+export type Holding = { account: string, symbol: string, quote_currency: Currency, quantity: number, average_cost_basis: number, fifo_cost_basis: number, 
+/**
+ * The latest price of a single share.
+ */
+price: number, value: number, 
+/**
+ * The gain of the held shares against their FIFO cost basis at today's exchange rate.
+ */
+unrealized_gain: number, 
+/**
+ * The gains of the sells by year, at the exchange rate of each sell.
+ */
+realized_gains: { [key: number]: number }, };
\ No newline at end of file
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Currency } from "./Currency";
+import type { Holding } from "./Holding";
+
+export type HoldingsResponse = { base_currency: Currency, holdings: Array<Holding>, 
+/**
+ * Some prices or rates could not be updated, so the values might be off.
+ */
+stale: boolean, };
\ No newline at end of file
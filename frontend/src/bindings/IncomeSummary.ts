@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ActionTotals } from "./ActionTotals";
+
+export type IncomeSummary = { totals: Array<ActionTotals>, };
\ No newline at end of file
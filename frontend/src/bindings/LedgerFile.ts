@@ -1,3 +1,17 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BankFormat } from "./BankFormat";
+import type { Diagnostic } from "./Diagnostic";
 
-export type LedgerFile = { filename: string, number_of_entries: number | null, error: string | null, };
\ No newline at end of file
+export type LedgerFile = { filename: string, number_of_entries: number | null, 
+/**
+ * The number of entries dropped because an earlier file already contained them.
+ */
+duplicates: number | null, 
+/**
+ * The format the file clearly is in, if any.
+ */
+detected_format: BankFormat | null, error: string | null, 
+/**
+ * The problems found in the file, including the error if it could not be parsed.
+ */
+diagnostics: Array<Diagnostic>, };
\ No newline at end of file
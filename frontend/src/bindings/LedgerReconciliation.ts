@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Reconciliation } from "./Reconciliation";
+import type { Symbol } from "./Symbol";
+
+export type LedgerReconciliation = { symbol: Symbol, balances: Array<Reconciliation>, };
\ No newline at end of file
@@ -1,4 +1,8 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { SpendingSummary } from "./SpendingSummary";
 
-export type LedgerSummary = { spending: { [key: string]: SpendingSummary }, };
\ No newline at end of file
+export type LedgerSummary = { spending: { [key: string]: SpendingSummary }, 
+/**
+ * Why some records could not be matched as transfers between the accounts.
+ */
+unmatched: Array<string>, };
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RatePair } from "./RatePair";
+
+export type ListRatesResponse = { pairs: Array<RatePair>, };
\ No newline at end of file
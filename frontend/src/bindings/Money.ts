@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * An exact decimal amount of money or a quantity of shares.
+ *
+ * Amounts are only rounded when they are converted to another currency. They are serialized as
+ * plain JSON numbers.
+ */
+export type Money = number;
\ No newline at end of file
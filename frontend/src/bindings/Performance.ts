@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The returns of an account over a period, in the base currency.
+ */
+export type Performance = { id: string, name: string, 
+/**
+ * The value at the end of the day before the period.
+ */
+start_value: number, end_value: number, 
+/**
+ * The deposits minus the withdrawals during the period.
+ */
+net_flows: number, 
+/**
+ * The growth over the whole period, regardless of the deposits and withdrawals.
+ */
+time_weighted_return: number, 
+/**
+ * The annual rate of return of the money invested (XIRR).
+ */
+money_weighted_return: number | null, };
\ No newline at end of file
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Currency } from "./Currency";
+import type { Performance } from "./Performance";
+import type { Period } from "./Period";
+
+export type PerformanceResponse = { base_currency: Currency, period: Period, from: number, to: number, accounts: Array<Performance>, total: Performance | null, 
+/**
+ * Some prices or rates could not be updated, so the latest values might be off.
+ */
+stale: boolean, 
+/**
+ * Why some records could not be matched as transfers between the accounts.
+ */
+unmatched: Array<string>, };
\ No newline at end of file
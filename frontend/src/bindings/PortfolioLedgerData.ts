@@ -1,3 +1,11 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type PortfolioLedgerData = { id: string, name: string, series: Array<number>, };
\ No newline at end of file
+export type PortfolioLedgerData = { id: string, name: string, 
+/**
+ * The balance at the end of each period.
+ */
+series: Array<number>, 
+/**
+ * The average daily balance over each period.
+ */
+average: Array<number>, };
\ No newline at end of file
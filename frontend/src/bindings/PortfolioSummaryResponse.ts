@@ -1,7 +1,17 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Currency } from "./Currency";
+import type { Forecast } from "./Forecast";
 import type { PortfolioLedgerData } from "./PortfolioLedgerData";
 import type { PortfolioLedgersData } from "./PortfolioLedgersData";
+import type { Resolution } from "./Resolution";
 import type { SpendPerMonth } from "./SpendPerMonth";
 
-export type PortfolioSummaryResponse = { total_balance: PortfolioLedgersData, total_prediction: PortfolioLedgerData, spend_per_month: SpendPerMonth, base_currency: Currency, };
\ No newline at end of file
+export type PortfolioSummaryResponse = { total_balance: PortfolioLedgersData, total_prediction: PortfolioLedgerData, prediction_timestamps: number[], forecast: Forecast, spend_per_month: SpendPerMonth, base_currency: Currency, resolution: Resolution, 
+/**
+ * Some rates could not be updated, so the latest values might be off.
+ */
+stale: boolean, 
+/**
+ * Why some records could not be matched as transfers between the accounts.
+ */
+unmatched: Array<string>, };
\ No newline at end of file
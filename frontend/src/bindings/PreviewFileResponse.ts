@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Diagnostic } from "./Diagnostic";
+import type { PreviewLedger } from "./PreviewLedger";
+
+export type PreviewFileResponse = { 
+/**
+ * Has to be passed to the confirm call to store the file.
+ */
+token: string, filename: string, ledgers: Array<PreviewLedger>, from: number, to: number, 
+/**
+ * The number of records which are already contained in other files of the account.
+ */
+duplicates: number, 
+/**
+ * The rows which would be skipped and other problems with the upload.
+ */
+diagnostics: Array<Diagnostic>, };
\ No newline at end of file
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LedgerKind } from "./LedgerKind";
+import type { Money } from "./Money";
+import type { PreviewRecord } from "./PreviewRecord";
+
+export type PreviewLedger = { symbol: string, kind: LedgerKind, 
+/**
+ * The sum of all records, including the duplicates.
+ */
+total: Money, records: Array<PreviewRecord>, };
\ No newline at end of file
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Money } from "./Money";
+
+export type PreviewRecord = { id: string | null, date: number, amount: Money, description: string, category: string, 
+/**
+ * The record is already contained in another file and will be skipped.
+ */
+duplicate: boolean, };
\ No newline at end of file
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Derivation } from "./Derivation";
+import type { Symbol } from "./Symbol";
+
+export type RatePair = { from: Symbol, to: Symbol, derivation: Derivation, first: number, last: number, stale: boolean, 
+/**
+ * Why the rates could not be updated, if they are stale.
+ */
+error: string | null, };
\ No newline at end of file
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BalanceKind } from "./BalanceKind";
+import type { Money } from "./Money";
+
+/**
+ * A balance reported by a bank statement next to the balance computed from the records.
+ */
+export type Reconciliation = { iban: string | null, date: number, kind: BalanceKind, reported: Money, computed: Money, difference: Money, };
\ No newline at end of file
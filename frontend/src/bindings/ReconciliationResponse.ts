@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LedgerReconciliation } from "./LedgerReconciliation";
+
+export type ReconciliationResponse = { id: string, ledgers: Array<LedgerReconciliation>, };
\ No newline at end of file
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RecurringFlowData = { name: string, 
+/**
+ * The average amount per month, negative for expenses.
+ */
+monthly: number, };
\ No newline at end of file
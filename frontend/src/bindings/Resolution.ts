@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type Resolution = "day" | "week" | "month" | "quarter";
\ No newline at end of file
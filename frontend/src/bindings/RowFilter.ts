@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Column } from "./Column";
+
+export type RowFilter = { column: Column, 
+/**
+ * Regex the value of the column has to match.
+ */
+pattern: string, 
+/**
+ * Skip the matching rows instead of keeping them.
+ */
+exclude: boolean, };
\ No newline at end of file
@@ -0,0 +1,38 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Money } from "./Money";
+
+/**
+ * A categorization rule.
+ *
+ * All given matchers have to match for the rule to apply. Rules are evaluated in order and the
+ * first matching rule decides the category of a record.
+ */
+export type Rule = { 
+/**
+ * The category assigned to matching records.
+ */
+category: string, 
+/**
+ * A regex which has to match the original description.
+ */
+description: string | null, 
+/**
+ * The inclusive lower bound of the amount.
+ */
+min_amount: Money | null, 
+/**
+ * The inclusive upper bound of the amount.
+ */
+max_amount: Money | null, 
+/**
+ * The id of the account the record has to belong to.
+ */
+account: string | null, 
+/**
+ * The first day (inclusive) the rule applies to.
+ */
+from: number, 
+/**
+ * The last day (inclusive) the rule applies to.
+ */
+to: number, };
\ No newline at end of file
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ExtendedLedgerRecord } from "./ExtendedLedgerRecord";
+import type { Symbol } from "./Symbol";
+
+export type RuleMatch = { account: string, symbol: Symbol, 
+/**
+ * The record as it would look like with the rule applied.
+ */
+record: ExtendedLedgerRecord, };
\ No newline at end of file
@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Money } from "./Money";
 
-export type SpendPerMonth = { months: { [key: number]: { [key: number]: { [key: string]: number } } }, };
\ No newline at end of file
+export type SpendPerMonth = { months: { [key: number]: { [key: number]: { [key: string]: Money } } }, };
\ No newline at end of file
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A stock split which turns every share held before `date` into `ratio` shares.
+ *
+ * Reverse splits have a ratio below one.
+ */
+export type Split = { date: number, ratio: number, };
\ No newline at end of file
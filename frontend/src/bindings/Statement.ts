@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { StatementBalance } from "./StatementBalance";
+
+/**
+ * The metadata of a bank statement which is not part of the individual records.
+ *
+ * Only formats which carry this information (like camt.053) produce statements.
+ */
+export type Statement = { iban: string | null, 
+/**
+ * The balance before the first entry of the statement.
+ */
+opening_balance: StatementBalance | null, 
+/**
+ * The balance after the last entry of the statement.
+ */
+closing_balance: StatementBalance | null, };
\ No newline at end of file
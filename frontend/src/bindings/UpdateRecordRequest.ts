@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * An edit of a single record. Fields which are not given are left untouched.
+ */
+export type UpdateRecordRequest = { id: string, checked: boolean | null, comments: string | null, category: string | null, description: string | null, };
\ No newline at end of file
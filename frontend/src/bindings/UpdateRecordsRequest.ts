@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { UpdateRecordRequest } from "./UpdateRecordRequest";
+
+export type UpdateRecordsRequest = { records: Array<UpdateRecordRequest>, };
\ No newline at end of file
@@ -63,6 +63,7 @@ function Dashboard() {
       <NetWorth
         totalBalance={data.total_balance}
         totalPrediction={data.total_prediction}
+        predictionTimestamps={data.prediction_timestamps}
         baseCurrency={data.base_currency}
       />
 
@@ -16,10 +16,12 @@ interface ChartData {
 export default function NetWorth({
   totalBalance,
   totalPrediction,
+  predictionTimestamps,
   baseCurrency,
 }: {
   totalBalance: PortfolioLedgersData;
   totalPrediction: PortfolioLedgerData;
+  predictionTimestamps: number[];
   baseCurrency: Currency;
 }) {
   // State to track which accounts are visible
@@ -40,15 +42,12 @@ export default function NetWorth({
       }
     }
 
-    const lastDate = totalBalance.timestamps[totalBalance.timestamps.length - 1];
     const isPredictionVisible = !hiddenAccounts.has(totalPrediction.name);
     
     // Always add prediction data, but set to 0 if hidden
     for (let i = 0; i < totalPrediction.series.length; i++) {
-      const date = new Date(lastDate * 1000);
-      date.setDate(date.getDate() + i);
       processedData.push({
-        date,
+        date: new Date((predictionTimestamps[i] ?? 0) * 1000),
       });
       processedData[processedData.length - 1][totalPrediction.name] =
         isPredictionVisible ? (totalPrediction.series[i] ?? 0) : 0;
@@ -62,7 +61,7 @@ export default function NetWorth({
     processedCategories.push({ name: totalPrediction.name, stack: "prediction" });
 
     return { data: processedData, categories: processedCategories };
-  }, [totalBalance, totalPrediction, hiddenAccounts]);
+  }, [totalBalance, totalPrediction, predictionTimestamps, hiddenAccounts]);
 
   // Memoize current balance calculation
   const currentBalance = useMemo(() => {
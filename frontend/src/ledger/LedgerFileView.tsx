@@ -9,6 +9,7 @@ import {
 import {
   CheckIcon,
   CloudArrowUpIcon,
+  ExclamationTriangleIcon,
   TrashIcon,
   XMarkIcon,
 } from "@heroicons/react/24/solid";
@@ -139,9 +140,29 @@ export function LedgerFileView() {
                     </TableCell>
                     <TableCell className="flex justify-end">
                       {item.error ? (
-                        <Tooltip side="top" content={item.error}>
+                        <Tooltip
+                          side="top"
+                          content={
+                            item.detected_format
+                              ? `${item.error}\nThis looks like a ${item.detected_format} file.`
+                              : item.error
+                          }
+                        >
                           <XMarkIcon className="py-1 mr-2 h-6 text-red-700" />
                         </Tooltip>
+                      ) : item.diagnostics.length > 0 ? (
+                        <Tooltip
+                          side="top"
+                          content={item.diagnostics
+                            .map((d) =>
+                              d.line
+                                ? `Line ${d.line}${d.column ? ` (${d.column})` : ""}: ${d.message}`
+                                : d.message,
+                            )
+                            .join("\n")}
+                        >
+                          <ExclamationTriangleIcon className="py-1 mr-2 h-6 text-yellow-600" />
+                        </Tooltip>
                       ) : (
                         <>
                           <Tooltip
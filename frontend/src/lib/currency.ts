@@ -2,41 +2,207 @@ import {
   CircleStackIcon,
   CurrencyDollarIcon,
   CurrencyEuroIcon,
+  CurrencyPoundIcon,
   CurrencyYenIcon,
+  DocumentTextIcon,
 } from "@heroicons/react/24/solid";
 import SvgNeon from "../assets/neon.svg";
 import SvgUbs from "../assets/ubs.svg";
 import SvgRevolut from "../assets/revolut.svg";
 import SvgIbkr from "../assets/ibkr.svg";
 import SvgWise from "../assets/wise.svg";
+import type { Currency } from "../bindings/Currency";
+
+// Lists every currency of the bindings, so a currency missing here fails to compile.
+const CODES: Record<Currency, null> = {
+  AED: null,
+  AFN: null,
+  ALL: null,
+  AMD: null,
+  ANG: null,
+  AOA: null,
+  ARS: null,
+  AUD: null,
+  AWG: null,
+  AZN: null,
+  BAM: null,
+  BBD: null,
+  BDT: null,
+  BGN: null,
+  BHD: null,
+  BIF: null,
+  BMD: null,
+  BND: null,
+  BOB: null,
+  BOV: null,
+  BRL: null,
+  BSD: null,
+  BTN: null,
+  BWP: null,
+  BYN: null,
+  BZD: null,
+  CAD: null,
+  CDF: null,
+  CHE: null,
+  CHF: null,
+  CHW: null,
+  CLF: null,
+  CLP: null,
+  CNY: null,
+  COP: null,
+  COU: null,
+  CRC: null,
+  CUP: null,
+  CVE: null,
+  CZK: null,
+  DJF: null,
+  DKK: null,
+  DOP: null,
+  DZD: null,
+  EGP: null,
+  ERN: null,
+  ETB: null,
+  EUR: null,
+  FJD: null,
+  FKP: null,
+  GBP: null,
+  GEL: null,
+  GHS: null,
+  GIP: null,
+  GMD: null,
+  GNF: null,
+  GTQ: null,
+  GYD: null,
+  HKD: null,
+  HNL: null,
+  HTG: null,
+  HUF: null,
+  IDR: null,
+  ILS: null,
+  INR: null,
+  IQD: null,
+  IRR: null,
+  ISK: null,
+  JMD: null,
+  JOD: null,
+  JPY: null,
+  KES: null,
+  KGS: null,
+  KHR: null,
+  KMF: null,
+  KPW: null,
+  KRW: null,
+  KWD: null,
+  KYD: null,
+  KZT: null,
+  LAK: null,
+  LBP: null,
+  LKR: null,
+  LRD: null,
+  LSL: null,
+  LYD: null,
+  MAD: null,
+  MDL: null,
+  MGA: null,
+  MKD: null,
+  MMK: null,
+  MNT: null,
+  MOP: null,
+  MRU: null,
+  MUR: null,
+  MVR: null,
+  MWK: null,
+  MXN: null,
+  MXV: null,
+  MYR: null,
+  MZN: null,
+  NAD: null,
+  NGN: null,
+  NIO: null,
+  NOK: null,
+  NPR: null,
+  NZD: null,
+  OMR: null,
+  PAB: null,
+  PEN: null,
+  PGK: null,
+  PHP: null,
+  PKR: null,
+  PLN: null,
+  PYG: null,
+  QAR: null,
+  RON: null,
+  RSD: null,
+  RUB: null,
+  RWF: null,
+  SAR: null,
+  SBD: null,
+  SCR: null,
+  SDG: null,
+  SEK: null,
+  SGD: null,
+  SHP: null,
+  SLE: null,
+  SOS: null,
+  SRD: null,
+  SSP: null,
+  STN: null,
+  SVC: null,
+  SYP: null,
+  SZL: null,
+  THB: null,
+  TJS: null,
+  TMT: null,
+  TND: null,
+  TOP: null,
+  TRY: null,
+  TTD: null,
+  TWD: null,
+  TZS: null,
+  UAH: null,
+  UGX: null,
+  USD: null,
+  USN: null,
+  UYI: null,
+  UYU: null,
+  UYW: null,
+  UZS: null,
+  VED: null,
+  VES: null,
+  VND: null,
+  VUV: null,
+  WST: null,
+  XAF: null,
+  XCD: null,
+  XCG: null,
+  XOF: null,
+  XPF: null,
+  YER: null,
+  ZAR: null,
+  ZMW: null,
+  ZWG: null,
+};
+
+const NAMES = new Intl.DisplayNames(["en"], { type: "currency" });
+
+const ICONS: Partial<Record<Currency, typeof CircleStackIcon>> = {
+  USD: CurrencyDollarIcon,
+  EUR: CurrencyEuroIcon,
+  JPY: CurrencyYenIcon,
+  GBP: CurrencyPoundIcon,
+};
+
+// The most common currencies come first.
+const COMMON: Currency[] = ["CHF", "USD", "EUR"];
 
 export const CURRENCIES = [
-  {
-    name: "Swiss Franc",
-    symbol: "CHF",
-    icon: CircleStackIcon,
-  },
-  {
-    name: "United States Dollar",
-    symbol: "USD",
-    icon: CurrencyDollarIcon,
-  },
-  {
-    name: "Euro",
-    symbol: "EUR",
-    icon: CurrencyEuroIcon,
-  },
-  {
-    name: "Polish złoty",
-    symbol: "PLN",
-    icon: CircleStackIcon,
-  },
-  {
-    name: "Japanese Yen",
-    symbol: "JPY",
-    icon: CurrencyYenIcon,
-  },
-];
+  ...COMMON,
+  ...(Object.keys(CODES) as Currency[]).filter((c) => !COMMON.includes(c)),
+].map((symbol) => ({
+  name: NAMES.of(symbol) ?? symbol,
+  symbol,
+  icon: ICONS[symbol] ?? CircleStackIcon,
+}));
 
 export const BANKS = [
   {
@@ -64,4 +230,19 @@ export const BANKS = [
     name: "Ubs",
     icon: SvgUbs,
   },
+  {
+    id: "camt",
+    name: "ISO 20022 (camt.053/054)",
+    icon: DocumentTextIcon,
+  },
+  {
+    id: "ofx",
+    name: "OFX / QFX",
+    icon: DocumentTextIcon,
+  },
+  {
+    id: "custom",
+    name: "Custom CSV",
+    icon: DocumentTextIcon,
+  },
 ];
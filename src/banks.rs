@@ -1,45 +1,312 @@
+mod camt;
+mod custom;
 mod ibkr;
 mod neon;
+mod ofx;
 mod revolut;
 mod ubs;
 mod wise;
+mod xml;
 
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+    path::Path,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::NaiveDate;
-use serde::{Deserialize, Serialize};
+use csv::ReaderBuilder;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use ts_rs::TS;
 
-use crate::{cli::BankFormat, fx::Symbol};
+pub use custom::CustomFormat;
 
-pub fn load(name: &str, path: impl AsRef<Path>, format: BankFormat) -> anyhow::Result<Vec<Ledger>> {
-    match format {
-        BankFormat::Neon => load_inner::<neon::Neon>(name, path),
-        BankFormat::Ubs => load_inner::<ubs::Ubs>(name, path),
-        BankFormat::Ibkr => load_inner::<ibkr::Ibkr>(name, path),
-        BankFormat::Revolut => load_inner::<revolut::Revolut>(name, path),
-        BankFormat::Wise => load_inner::<wise::Wise>(name, path),
+use crate::{
+    cli::BankFormat,
+    fx::{Currency, Symbol},
+    money::Money,
+    realms::portfolio::state::Action,
+};
+
+pub fn load(
+    name: &str,
+    path: impl AsRef<Path>,
+    format: BankFormat,
+    custom_format: Option<&CustomFormat>,
+) -> anyhow::Result<ParsedAccount> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("could not read ledger CSV {}", path.display()))?;
+    let file = path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    parse(name, &file, content, format, custom_format)
+}
+
+/// Parses the content of `file`. The diagnostics of the result refer to `file`.
+pub fn parse(
+    name: &str,
+    file: &str,
+    content: String,
+    format: BankFormat,
+    custom_format: Option<&CustomFormat>,
+) -> anyhow::Result<ParsedAccount> {
+    let mut parsed = match format {
+        BankFormat::Neon => neon::Neon::parse(name, content),
+        BankFormat::Ubs => ubs::Ubs::parse(name, content),
+        BankFormat::Ibkr => ibkr::Ibkr::parse(name, content),
+        BankFormat::Revolut => revolut::Revolut::parse(name, content),
+        BankFormat::Wise => wise::Wise::parse(name, content),
+        BankFormat::Camt => camt::Camt::parse(name, content),
+        BankFormat::Ofx => ofx::Ofx::parse(name, content),
+        BankFormat::Custom => custom_format
+            .context("The account has no custom format mapping")
+            .and_then(|custom_format| custom_format.parse(name, content)),
+    }?;
+    for diagnostic in &mut parsed.diagnostics {
+        diagnostic.file = file.to_string();
+    }
+    parsed.diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    Ok(parsed)
+}
+
+/// A file is considered to clearly be in a format from this score on.
+pub const DETECTION_THRESHOLD: f64 = 0.8;
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Detection {
+    pub format: BankFormat,
+    /// Between 0 (certainly not this format) and 1 (certainly this format).
+    pub score: f64,
+}
+
+/// Scores the content against all built-in formats, best match first. Formats which certainly
+/// do not match are left out. Custom formats are never detected.
+pub fn detect(content: &str) -> Vec<Detection> {
+    let mut detections = [
+        (BankFormat::Ubs, ubs::Ubs::detect(content)),
+        (BankFormat::Neon, neon::Neon::detect(content)),
+        (BankFormat::Revolut, revolut::Revolut::detect(content)),
+        (BankFormat::Ibkr, ibkr::Ibkr::detect(content)),
+        (BankFormat::Wise, wise::Wise::detect(content)),
+        (BankFormat::Camt, camt::Camt::detect(content)),
+        (BankFormat::Ofx, ofx::Ofx::detect(content)),
+    ]
+    .into_iter()
+    .filter(|(_, score)| *score > 0.0)
+    .map(|(format, score)| Detection { format, score })
+    .collect::<Vec<_>>();
+    detections.sort_by(|a, b| b.score.total_cmp(&a.score));
+    detections
+}
+
+/// Returns the format a file clearly belongs to if it is not the expected one.
+pub fn mismatch(format: BankFormat, content: &str) -> Option<Detection> {
+    // Custom mappings may well describe an export of a bank with a built-in parser.
+    if matches!(format, BankFormat::Custom) {
+        return None;
     }
+    let detections = detect(content);
+    let expected = detections
+        .iter()
+        .find(|d| d.format == format)
+        .map_or(0.0, |d| d.score);
+    detections
+        .into_iter()
+        .next()
+        .filter(|best| best.format != format)
+        .filter(|best| best.score >= DETECTION_THRESHOLD && expected < DETECTION_THRESHOLD)
 }
 
-fn load_inner<T: Parser>(name: &str, path: impl AsRef<Path>) -> anyhow::Result<Vec<Ledger>> {
-    let loaded = T::parse(
-        name,
-        std::fs::read_to_string(&path)
-            .with_context(|| format!("could not read ledger CSV {}", path.as_ref().display()))?,
-    )?
-    .ledgers;
-    Ok(loaded)
+/// The share of the expected columns found in the first line of a CSV file.
+fn header_score(content: &str, delimiter: u8, expected: &[&str]) -> f64 {
+    let header = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(Cursor::new(content.trim_start_matches('\u{feff}')))
+        .into_records()
+        .next()
+        .and_then(Result::ok)
+        .unwrap_or_default();
+    let found = expected
+        .iter()
+        .filter(|column| header.iter().any(|h| h.trim() == **column))
+        .count();
+    found as f64 / expected.len() as f64
+}
+
+/// Merges the ledgers loaded from one file into the ledgers already loaded for an account.
+///
+/// Records which were already loaded from an earlier file (e.g. from an overlapping statement)
+/// are dropped. Returns the number of dropped records.
+pub fn merge(ledgers: &mut Vec<Ledger>, loaded: Vec<Ledger>) -> usize {
+    let mut dropped = 0;
+    for ledger in loaded {
+        let Some(found) = ledgers.iter_mut().find(|l| l.symbol == ledger.symbol) else {
+            ledgers.push(ledger);
+            continue;
+        };
+
+        if found.quote_currency.is_none() {
+            found.quote_currency = ledger.quote_currency;
+        }
+        for statement in ledger.statements {
+            if !found.statements.contains(&statement) {
+                found.statements.push(statement);
+            }
+        }
+        for split in ledger.splits {
+            if !found.splits.iter().any(|s| s.date == split.date) {
+                found.splits.push(split);
+            }
+        }
+
+        let duplicates = duplicates(&found.records, &ledger.records);
+        for (record, duplicate) in ledger.records.into_iter().zip(duplicates) {
+            if duplicate {
+                dropped += 1;
+            } else {
+                found.records.push(record);
+            }
+        }
+    }
+    dropped
+}
+
+/// Flags the records which are already contained in the existing records.
+pub fn duplicates(existing: &[LedgerRecord], records: &[LedgerRecord]) -> Vec<bool> {
+    let mut counts = HashMap::<String, usize>::new();
+    for record in existing {
+        *counts.entry(record.fingerprint()).or_default() += 1;
+    }
+
+    // Identical records within the same file are legit (e.g. two equal payments on the same
+    // day), so only as many occurrences are flagged as were already loaded before.
+    let mut occurrences = HashMap::<String, usize>::new();
+    records
+        .iter()
+        .map(|record| {
+            let fingerprint = record.fingerprint();
+            let occurrence = occurrences.entry(fingerprint.clone()).or_default();
+            *occurrence += 1;
+            *occurrence <= counts.get(&fingerprint).copied().unwrap_or_default()
+        })
+        .collect()
 }
 
 pub trait Parser {
     fn parse(name: &str, content: String) -> Result<ParsedAccount>;
+    /// Scores how likely the content is in this format by its headers and structure, between 0
+    /// and 1.
+    fn detect(content: &str) -> f64;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ParsedAccount {
-    ledgers: Vec<Ledger>,
+    pub ledgers: Vec<Ledger>,
+    /// The problems which did not prevent the file from being parsed, like skipped rows.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A problem found in a file, located as precisely as the format allows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Diagnostic {
+    /// The name of the file, which is only known once the parser is done.
+    pub file: String,
+    /// The one based line in the file.
+    pub line: Option<usize>,
+    /// The name of the column or field.
+    pub column: Option<String>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "kebab-case")]
+#[ts(export)]
+pub enum Severity {
+    /// The row was skipped, the rest of the file was imported.
+    Warning,
+    /// The file could not be imported at all.
+    Error,
+}
+
+impl Diagnostic {
+    pub fn warning(line: Option<usize>, column: Option<&str>, message: impl ToString) -> Self {
+        Self {
+            file: String::new(),
+            line,
+            column: column.map(ToString::to_string),
+            severity: Severity::Warning,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn error(file: &str, message: impl ToString) -> Self {
+        Self {
+            file: file.to_string(),
+            line: None,
+            column: None,
+            severity: Severity::Error,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Deserializes the rows of a CSV file together with their line number.
+///
+/// Rows which cannot be read are skipped with a warning. If not a single row can be read, the
+/// file is most likely in another format and the first problem is returned as error instead.
+fn csv_rows<T: DeserializeOwned, R: Read>(
+    mut reader: csv::Reader<R>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<(usize, T)>> {
+    let headers = reader.headers()?.clone();
+    let mut rows = vec![];
+    let mut problems = vec![];
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(error) => {
+                let line = error.position().map(|p| p.line() as usize);
+                problems.push(Diagnostic::warning(line, None, error));
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line() as usize);
+        match record.deserialize::<T>(Some(&headers)) {
+            Ok(row) => rows.push((line, row)),
+            Err(error) => {
+                let column = match error.kind() {
+                    csv::ErrorKind::Deserialize { err, .. } => {
+                        err.field().and_then(|field| headers.get(field as usize))
+                    }
+                    _ => None,
+                };
+                let message = match error.kind() {
+                    csv::ErrorKind::Deserialize { err, .. } => {
+                        format!("The row could not be read: {}", err.kind())
+                    }
+                    _ => error.to_string(),
+                };
+                problems.push(Diagnostic::warning(Some(line), column, message));
+            }
+        }
+    }
+
+    if rows.is_empty() {
+        if let Some(problem) = problems.first() {
+            bail!("{}", problem.message);
+        }
+    }
+    diagnostics.extend(problems);
+    Ok(rows)
 }
 
 #[derive(Debug)]
@@ -48,6 +315,11 @@ pub struct Ledger {
     pub symbol: Symbol,
     pub records: Vec<LedgerRecord>,
     pub kind: LedgerKind,
+    pub statements: Vec<Statement>,
+    /// The currency a stock is traded in, which its price has to be converted from.
+    pub quote_currency: Option<Currency>,
+    /// The splits of a stock reported by the bank.
+    pub splits: Vec<Split>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -57,6 +329,53 @@ pub struct ExtendedLedger {
     pub symbol: Symbol,
     pub records: Vec<ExtendedLedgerRecord>,
     pub kind: LedgerKind,
+    #[serde(default)]
+    pub statements: Vec<Statement>,
+    /// The currency a stock is traded in, which its price has to be converted from.
+    #[serde(default)]
+    pub quote_currency: Option<Currency>,
+    /// The splits the quantities of the records were adjusted for.
+    #[serde(default)]
+    pub splits: Vec<Split>,
+}
+
+/// The metadata of a bank statement which is not part of the individual records.
+///
+/// Only formats which carry this information (like camt.053) produce statements.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Statement {
+    pub iban: Option<String>,
+    /// The balance before the first entry of the statement.
+    pub opening_balance: Option<StatementBalance>,
+    /// The balance after the last entry of the statement.
+    pub closing_balance: Option<StatementBalance>,
+}
+
+/// A stock split which turns every share held before `date` into `ratio` shares.
+///
+/// Reverse splits have a ratio below one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Split {
+    #[ts(type = "number")]
+    pub date: NaiveDate,
+    pub ratio: f64,
+}
+
+impl Split {
+    /// Whether the ratio turns the shares into a finite, positive number of shares.
+    pub fn is_valid(&self) -> bool {
+        self.ratio.is_finite() && self.ratio > 0.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct StatementBalance {
+    #[ts(type = "number")]
+    pub date: NaiveDate,
+    pub amount: Money,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
@@ -68,36 +387,69 @@ pub enum LedgerKind {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct LedgerRecord {
+    /// The transaction id assigned by the bank, if the export contains one.
+    pub id: Option<String>,
     pub date: NaiveDate,
-    pub amount: f64,
+    pub amount: Money,
     pub description: String,
     pub category: String,
+    pub action: Action,
+    /// The price of a single share for trades of a stock, in its quote currency.
+    pub price: Option<f64>,
+}
+
+impl LedgerRecord {
+    /// A deterministic identity of the record.
+    ///
+    /// This is the bank's transaction id where one exists and a hash of the date, amount and
+    /// description otherwise.
+    pub fn fingerprint(&self) -> String {
+        if let Some(id) = &self.id {
+            return id.clone();
+        }
+
+        let hash = Sha256::digest(format!(
+            "{}|{}|{}",
+            self.date,
+            self.amount.normalize(),
+            self.description
+        ));
+        hash[..8].iter().map(|b| format!("{b:02x}")).collect()
+    }
 }
 
 impl From<StockLedgerRecord> for LedgerRecord {
     fn from(
         StockLedgerRecord {
+            id,
             date,
             amount,
+            price,
             description,
             category,
             ..
         }: StockLedgerRecord,
     ) -> Self {
         Self {
+            id,
             date,
             amount,
             description,
             category,
+            action: Action::Update,
+            price: Some(price),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct StockLedgerRecord {
+    pub id: Option<String>,
     pub date: NaiveDate,
-    pub amount: f64,
+    pub amount: Money,
     pub price: f64,
+    /// The commission paid for the trade, which is booked separately from its price.
+    pub commission: Money,
     pub description: String,
     pub category: String,
 }
@@ -105,15 +457,116 @@ pub struct StockLedgerRecord {
 #[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, TS)]
 #[ts(export)]
 pub struct ExtendedLedgerRecord {
+    /// A stable identity of the record that survives reloading the ledger files.
+    pub id: String,
     #[ts(type = "number")]
     pub date: NaiveDate,
-    pub amount: f64,
+    pub amount: Money,
     pub description: String,
     pub original_description: String,
     pub category: String,
     pub original_category: String,
     pub comments: String,
     pub checked: bool,
+    /// The id of the account on the other side if this record is an internal transfer.
+    #[serde(default)]
+    pub transfer: Option<String>,
+    #[serde(default)]
+    pub action: Action,
+    /// The price of a single share for trades of a stock, in its quote currency.
+    #[serde(default)]
+    pub price: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::{
+        cli::BankFormat,
+        fx::{Currency, Symbol},
+        money::Money,
+    };
+
+    use super::{detect, merge, mismatch, Action, Ledger, LedgerKind, LedgerRecord};
+
+    fn ledger(records: &[(Option<&str>, &str, f64)]) -> Ledger {
+        Ledger {
+            name: "Test".to_string(),
+            symbol: Symbol::Currency(Currency::CHF),
+            records: records
+                .iter()
+                .map(|(id, description, amount)| LedgerRecord {
+                    id: id.map(ToString::to_string),
+                    date: NaiveDate::from_ymd_opt(2021, 1, 22).unwrap(),
+                    amount: Money::from_f64(*amount).unwrap(),
+                    description: description.to_string(),
+                    category: "".to_string(),
+                    action: Action::Update,
+                    price: None,
+                })
+                .collect(),
+            kind: LedgerKind::Bank,
+            statements: vec![],
+            quote_currency: None,
+            splits: vec![],
+        }
+    }
+
+    #[test]
+    fn merge_overlapping_files() {
+        let mut ledgers = vec![];
+        let yearly = ledger(&[
+            (None, "neon Switzerland AG", 30.0),
+            (None, "neon Switzerland AG", 30.0),
+            (Some("9999022ZC8003633"), "STRIPE", 9.59),
+        ]);
+        assert_eq!(merge(&mut ledgers, vec![yearly]), 0);
+
+        let monthly = ledger(&[
+            (None, "neon Switzerland AG", 30.0),
+            (None, "neon Switzerland AG", 30.0),
+            (None, "neon Switzerland AG", 30.0),
+            (Some("9999022ZC8003633"), "STRIPE PAYMENTS UK LTD", 9.59),
+            (Some("9999022ZC7962684"), "TECHNOKRAT GMBH", 3688.0),
+        ]);
+        assert_eq!(merge(&mut ledgers, vec![monthly]), 3);
+
+        assert_eq!(ledgers.len(), 1);
+        assert_eq!(ledgers[0].records.len(), 5);
+    }
+
+    #[test]
+    fn detect_test_data() {
+        let files = [
+            ("ibkr/2024.csv", BankFormat::Ibkr),
+            ("neon/2024_account_statements.csv", BankFormat::Neon),
+            ("revolut/2024.csv", BankFormat::Revolut),
+            ("ubs-private/UBS_test.csv", BankFormat::Ubs),
+            (
+                "wise/statement_12345_CHF_2024-01-01_2024-12-31.csv",
+                BankFormat::Wise,
+            ),
+        ];
+        for (file, format) in files {
+            let content =
+                std::fs::read_to_string(format!("portfolio-test/ledgers/123456789/{file}"))
+                    .unwrap();
+            let best = &detect(&content)[0];
+            assert_eq!(best.format, format, "{file}");
+            assert_eq!(best.score, 1.0, "{file}");
+            assert!(mismatch(format, &content).is_none(), "{file}");
+        }
+
+        let revolut = std::fs::read_to_string("portfolio-test/ledgers/123456789/revolut/2024.csv")
+            .unwrap();
+        assert_eq!(
+            mismatch(BankFormat::Neon, &revolut).map(|d| d.format),
+            Some(BankFormat::Revolut)
+        );
+        assert!(mismatch(BankFormat::Custom, &revolut).is_none());
+        assert!(detect("Datum;Betrag;Text\n").is_empty());
+    }
 }
 
 #[cfg(test)]
@@ -122,7 +575,7 @@ pub mod test_utils {
     use crate::handler::auth::user::User;
     use crate::realms::portfolio::state::Owner;
     use crate::state::{CacheState, PortfolioAdapter};
-    use axum::extract::State;
+    use axum::extract::{Query, State};
     use std::{path::PathBuf, sync::Arc};
 
     pub async fn test_account_balance_api(account_id: &str, portfolio_path: &str, owner_id: &str) -> f64 {
@@ -142,6 +595,7 @@ pub mod test_utils {
         let result = handler(
             State(portfolio_adapter),
             State(cache_state),
+            Query(Default::default()),
             user,
         ).await;
         
@@ -152,7 +606,7 @@ pub mod test_utils {
                 // Find the account in the response
                 let account_balance = data.total_balance.balances.iter()
                     .find(|b| b.id == account_id)
-                    .expect(&format!("{} account not found in response", account_id));
+                    .unwrap_or_else(|| panic!("{} account not found in response", account_id));
                 
                 // Get the current balance (last value in series)
                 let current_balance = account_balance.series.last()
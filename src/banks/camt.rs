@@ -0,0 +1,378 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use chrono::NaiveDate;
+use roxmltree::{Document, Node};
+
+use crate::{
+    fx::{Currency, Symbol},
+    money::Money,
+    realms::portfolio::state::Action,
+};
+
+use super::{
+    xml::{child, child_text, children, line},
+    Diagnostic, Ledger, LedgerKind, LedgerRecord, ParsedAccount, Parser, Statement,
+    StatementBalance,
+};
+
+/// ISO 20022 bank statements (camt.053) and debit/credit notifications (camt.054).
+pub struct Camt {}
+
+impl Parser for Camt {
+    fn parse(name: &str, content: String) -> anyhow::Result<ParsedAccount> {
+        let document = Document::parse(&content).context("The data is not valid XML")?;
+        let root = document.root_element();
+
+        // camt.053 contains statements while camt.054 contains notifications.
+        // Both carry the account and the entries in the same structure.
+        let reports = root
+            .descendants()
+            .filter(|n| n.has_tag_name("Stmt") || n.has_tag_name("Ntfctn"))
+            .collect::<Vec<_>>();
+        if reports.is_empty() {
+            bail!(
+                "The data seems to not be in camt.053/camt.054 format as no statements were found"
+            )
+        }
+
+        let mut ledgers: Vec<Ledger> = vec![];
+        let mut diagnostics = vec![];
+        for report in reports {
+            let account = child(report, "Acct");
+            let iban = account
+                .and_then(|a| child(a, "Id"))
+                .and_then(|id| child_text(id, "IBAN"))
+                .map(ToString::to_string);
+            let currency = account.and_then(|a| child_text(a, "Ccy")).or_else(|| {
+                children(report, "Ntry")
+                    .find_map(|e| child(e, "Amt").and_then(|a| a.attribute("Ccy")))
+            });
+            // Booking the entries in a guessed currency would distort every balance.
+            let Some(currency) = currency else {
+                diagnostics.push(Diagnostic::warning(
+                    Some(line(report)),
+                    Some("Ccy"),
+                    "The statement has no currency and is skipped",
+                ));
+                continue;
+            };
+            let Ok(currency) = Currency::from_str(currency) else {
+                diagnostics.push(Diagnostic::warning(
+                    Some(line(report)),
+                    Some("Ccy"),
+                    format!("{currency} is not a known currency and the statement is skipped"),
+                ));
+                continue;
+            };
+
+            let mut records = vec![];
+            for entry in children(report, "Ntry").filter(is_booked) {
+                match parse_entry(entry) {
+                    Ok(record) => records.push(record),
+                    // A broken entry is skipped, the rest of the statement is still imported.
+                    Err(error) => diagnostics.push(Diagnostic::warning(
+                        Some(line(entry)),
+                        Some("Ntry"),
+                        format!("{error:#}"),
+                    )),
+                }
+            }
+
+            let statement = Statement {
+                iban,
+                opening_balance: balance(report, &["OPBD", "PRCD"])?,
+                closing_balance: balance(report, &["CLBD"])?,
+            };
+
+            let symbol = Symbol::Currency(currency);
+            if let Some(ledger) = ledgers.iter_mut().find(|l| l.symbol == symbol) {
+                ledger.records.extend(records);
+                ledger.statements.push(statement);
+            } else {
+                ledgers.push(Ledger {
+                    name: name.to_string(),
+                    symbol,
+                    records,
+                    kind: LedgerKind::Bank,
+                    statements: vec![statement],
+                    quote_currency: None,
+                    splits: vec![],
+                });
+            }
+        }
+
+        Ok(ParsedAccount {
+            ledgers,
+            diagnostics,
+        })
+    }
+
+    fn detect(content: &str) -> f64 {
+        // The elements are matched by their local name, as some banks use a namespace prefix.
+        let report = |document: Document| {
+            document
+                .descendants()
+                .any(|n| n.has_tag_name("BkToCstmrStmt") || n.has_tag_name("BkToCstmrDbtCdtNtfctn"))
+        };
+        if Document::parse(content).is_ok_and(report) {
+            1.0
+        } else if content.contains(":camt.05") {
+            0.8
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Parses a single `Ntry` into a record.
+fn parse_entry(entry: Node) -> anyhow::Result<LedgerRecord> {
+    let amount = signed_amount(entry)?;
+    let date = child(entry, "BookgDt")
+        .or_else(|| child(entry, "ValDt"))
+        .context("An entry has no booking date")
+        .and_then(date)?;
+
+    let details = child(entry, "NtryDtls").and_then(|d| child(d, "TxDtls"));
+    // The other party is the creditor for outgoing and the debtor for incoming payments.
+    let party = details.and_then(|d| child(d, "RltdPties")).and_then(|p| {
+        let role = if amount.is_sign_negative() {
+            "Cdtr"
+        } else {
+            "Dbtr"
+        };
+        // Newer versions wrap the party in an additional `Pty` element.
+        child(p, role)
+            .and_then(|p| child(p, "Pty").or(Some(p)))
+            .and_then(|p| child_text(p, "Nm"))
+    });
+    let remittance = details
+        .and_then(|d| child(d, "RmtInf"))
+        .map(|r| {
+            children(r, "Ustrd")
+                .filter_map(|u| u.text())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let additional = child_text(entry, "AddtlNtryInf");
+
+    let description = party
+        .or(additional)
+        .into_iter()
+        .chain(remittance)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let id = child_text(entry, "AcctSvcrRef")
+        .or_else(|| child_text(entry, "NtryRef"))
+        .or_else(|| {
+            details
+                .and_then(|d| child(d, "Refs"))
+                .and_then(|r| child_text(r, "AcctSvcrRef"))
+        })
+        .map(ToString::to_string);
+
+    Ok(LedgerRecord {
+        id,
+        date,
+        amount,
+        description,
+        category: "".to_string(),
+        action: Action::Update,
+        price: None,
+    })
+}
+
+/// Only booked entries are part of the balance. Pending and informational ones are skipped.
+fn is_booked(entry: &Node) -> bool {
+    // Up to version 04 the status is a plain code, from version 08 on it is wrapped in `Cd`.
+    child(*entry, "Sts")
+        .and_then(|s| child_text(s, "Cd").or(s.text()))
+        .is_none_or(|s| s.trim() == "BOOK")
+}
+
+/// Finds the first balance of the given types (e.g. `OPBD` for the opening booked balance).
+fn balance(report: Node, codes: &[&str]) -> anyhow::Result<Option<StatementBalance>> {
+    for balance in children(report, "Bal") {
+        let code = child(balance, "Tp")
+            .and_then(|t| child(t, "CdOrPrtry"))
+            .and_then(|c| child_text(c, "Cd"));
+        if !code.is_some_and(|c| codes.contains(&c)) {
+            continue;
+        }
+
+        let date = child(balance, "Dt")
+            .context("A balance has no date")
+            .and_then(date)?;
+        return Ok(Some(StatementBalance {
+            date,
+            amount: signed_amount(balance)?,
+        }));
+    }
+    Ok(None)
+}
+
+/// Reads the `Amt` of an entry or balance and applies its `CdtDbtInd`.
+fn signed_amount(node: Node) -> anyhow::Result<Money> {
+    let amount = child_text(node, "Amt")
+        .context("An amount is missing")?
+        .trim()
+        .parse::<Money>()
+        .context("An amount is not a number")?;
+    Ok(match child_text(node, "CdtDbtInd") {
+        Some("DBIT") => -amount,
+        _ => amount,
+    })
+}
+
+/// Reads a date group, which contains either a `Dt` or a `DtTm`.
+fn date(node: Node) -> anyhow::Result<NaiveDate> {
+    let text = child_text(node, "Dt")
+        .or_else(|| child_text(node, "DtTm"))
+        .context("A date is missing")?;
+    NaiveDate::parse_from_str(text.get(..10).unwrap_or(text), "%Y-%m-%d")
+        .with_context(|| format!("{text} is not a valid date"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::banks::Parser;
+
+    use super::Camt;
+
+    const STATEMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.04">
+  <BkToCstmrStmt>
+    <GrpHdr>
+      <MsgId>20210131375204000000001</MsgId>
+      <CreDtTm>2021-01-31T20:00:00</CreDtTm>
+    </GrpHdr>
+    <Stmt>
+      <Id>20210131375204000000001</Id>
+      <Acct>
+        <Id>
+          <IBAN>CH5800230230123456789</IBAN>
+        </Id>
+        <Ccy>CHF</Ccy>
+      </Acct>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>OPBD</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="CHF">18991.81</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><Dt>2021-01-21</Dt></Dt>
+      </Bal>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>CLBD</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="CHF">22113.15</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><Dt>2021-01-31</Dt></Dt>
+      </Bal>
+      <Ntry>
+        <Amt Ccy="CHF">3688.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2021-01-22</Dt></BookgDt>
+        <ValDt><Dt>2021-01-22</Dt></ValDt>
+        <AcctSvcrRef>9999022ZC7962684</AcctSvcrRef>
+        <NtryDtls>
+          <TxDtls>
+            <RltdPties>
+              <Dbtr><Nm>TECHNOKRAT GMBH</Nm></Dbtr>
+            </RltdPties>
+            <RmtInf><Ustrd>LOHN</Ustrd></RmtInf>
+          </TxDtls>
+        </NtryDtls>
+        <AddtlNtryInf>credit</AddtlNtryInf>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="CHF">9.59</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2021-01-22</Dt></BookgDt>
+        <AcctSvcrRef>9999022ZC8003633</AcctSvcrRef>
+        <AddtlNtryInf>STRIPE PAYMENTS UK LTD</AddtlNtryInf>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="CHF">503.50</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><DtTm>2021-01-28T10:12:00</DtTm></BookgDt>
+        <AcctSvcrRef>9906527KH9626550</AcctSvcrRef>
+        <NtryDtls>
+          <TxDtls>
+            <RltdPties>
+              <Cdtr><Nm>UBS Switzerland AG</Nm></Cdtr>
+            </RltdPties>
+          </TxDtls>
+        </NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="CHF">62.75</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts><Cd>BOOK</Cd></Sts>
+        <BookgDt><Dt>2021-01-27</Dt></BookgDt>
+        <AcctSvcrRef>9906026TO5927425</AcctSvcrRef>
+        <AddtlNtryInf>CAISSE DES MEDECINS,1211 GENEVE</AddtlNtryInf>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="CHF">482.80</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>PDNG</Sts>
+        <BookgDt><Dt>2021-02-01</Dt></BookgDt>
+        <AddtlNtryInf>CSS KRANKEN-VERSICHERUNG</AddtlNtryInf>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="CHF">10.00</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2021-01-29</Dt></BookgDt>
+        <NtryRef>BL01529HJ0125142</NtryRef>
+        <AddtlNtryInf>Balance closing of service prices</AddtlNtryInf>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>
+"#;
+
+    #[test]
+    fn parse_statement() {
+        insta::assert_debug_snapshot!(Camt::parse("UBS", STATEMENT.into()).unwrap());
+    }
+
+    #[test]
+    fn detect_prefixed() {
+        let prefixed = STATEMENT
+            .replace("<Document xmlns=", "<camt:Document xmlns:camt=")
+            .replace("</Document>", "</camt:Document>")
+            .replace("BkToCstmrStmt>", "camt:BkToCstmrStmt>");
+        assert_eq!(Camt::detect(&prefixed), 1.0);
+        assert_eq!(Camt::detect(STATEMENT), 1.0);
+        assert_eq!(Camt::detect("Date;Amount"), 0.0);
+    }
+
+    #[test]
+    fn skip_missing_currency() {
+        let statement = STATEMENT
+            .replace("<Ccy>CHF</Ccy>", "")
+            .replace(" Ccy=\"CHF\"", "");
+        let parsed = Camt::parse("UBS", statement).unwrap();
+        assert!(parsed.ledgers.is_empty());
+        assert_eq!(parsed.diagnostics[0].column.as_deref(), Some("Ccy"));
+    }
+
+    #[test]
+    fn skip_unknown_currency() {
+        let statement = STATEMENT.replace("<Ccy>CHF</Ccy>", "<Ccy>XYZ</Ccy>");
+        let parsed = Camt::parse("UBS", statement).unwrap();
+        assert!(parsed.ledgers.is_empty());
+        assert!(parsed.diagnostics[0].message.contains("XYZ"));
+    }
+
+    #[test]
+    #[should_panic(expected = "no statements were found")]
+    fn parse_fail() {
+        Camt::parse("UBS", "<Document></Document>".into()).unwrap();
+    }
+}
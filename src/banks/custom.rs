@@ -0,0 +1,401 @@
+use std::{collections::HashMap, io::Cursor, str::FromStr};
+
+use anyhow::{bail, Context};
+use chrono::NaiveDate;
+use csv::{ReaderBuilder, StringRecord};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    fx::{Currency, Symbol},
+    money::Money,
+    realms::portfolio::state::Action,
+};
+
+use super::{Diagnostic, Ledger, LedgerKind, LedgerRecord, ParsedAccount};
+
+/// Column mapping of a CSV export for banks without a dedicated parser.
+///
+/// It is stored with the account in the `portfolio.yaml`:
+///
+/// ```yaml
+/// format: custom
+/// custom_format:
+///   delimiter: ;
+///   header_row: 3
+///   date: Booking date
+///   date_format: "%d.%m.%Y"
+///   debit: Debit
+///   credit: Credit
+///   thousands_separator: "'"
+///   description: [Text, Reference]
+///   currency: CHF
+///   filters:
+///     - column: Status
+///       pattern: ^Pending$
+///       exclude: true
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(default)]
+pub struct CustomFormat {
+    pub delimiter: char,
+    /// Zero based line of the header. The lines before it are skipped. Without a header row
+    /// the columns have to be given by their index.
+    pub header_row: Option<usize>,
+    /// The first column if not given.
+    pub date: Column,
+    /// A chrono format string like `%Y-%m-%d`.
+    pub date_format: String,
+    /// A single signed amount column. Alternatively `debit` and `credit` can be given.
+    pub amount: Option<Column>,
+    /// Outgoing amounts, which are booked as negative independent of their sign in the file.
+    pub debit: Option<Column>,
+    /// Incoming amounts, which are booked as positive independent of their sign in the file.
+    /// It is used if the debit is empty or zero.
+    pub credit: Option<Column>,
+    pub thousands_separator: Option<char>,
+    pub decimal_separator: char,
+    /// The description columns, non-empty values are joined with `, `.
+    pub description: Vec<Column>,
+    pub category: Option<Column>,
+    /// Unique transaction id of the bank, used to detect duplicates across files.
+    pub id: Option<Column>,
+    /// Column containing the currency of each row. Rows are split into a ledger per currency.
+    pub currency_column: Option<Column>,
+    /// Currency of all rows if there is no currency column.
+    pub currency: Currency,
+    /// Only rows matching all filters are imported.
+    pub filters: Vec<RowFilter>,
+}
+
+/// A column referenced by its header name or by its zero based index.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(untagged)]
+#[ts(export)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct RowFilter {
+    pub column: Column,
+    /// Regex the value of the column has to match.
+    pub pattern: String,
+    /// Skip the matching rows instead of keeping them.
+    #[serde(default)]
+    pub exclude: bool,
+}
+
+impl Default for CustomFormat {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            header_row: Some(0),
+            date: Column::Index(0),
+            date_format: "%Y-%m-%d".to_string(),
+            amount: None,
+            debit: None,
+            credit: None,
+            thousands_separator: None,
+            decimal_separator: '.',
+            description: vec![],
+            category: None,
+            id: None,
+            currency_column: None,
+            currency: Currency::default(),
+            filters: vec![],
+        }
+    }
+}
+
+impl CustomFormat {
+    pub fn parse(&self, name: &str, content: String) -> anyhow::Result<ParsedAccount> {
+        if !self.delimiter.is_ascii() {
+            bail!("The delimiter {} is not a single byte", self.delimiter);
+        }
+        if self.amount.is_none() && self.debit.is_none() && self.credit.is_none() {
+            bail!("The custom format needs an amount or a debit and credit column");
+        }
+
+        let mut rows = ReaderBuilder::new()
+            .delimiter(self.delimiter as u8)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(Cursor::new(&content))
+            .into_records()
+            .enumerate()
+            .skip(self.header_row.unwrap_or_default());
+
+        let headers = match self.header_row {
+            Some(_) => rows
+                .next()
+                .map(|(_, header)| header)
+                .transpose()?
+                .context("The header row is missing")?,
+            None => StringRecord::new(),
+        };
+        let columns = Columns::new(self, &headers)?;
+
+        let mut ledgers = HashMap::<Currency, Vec<LedgerRecord>>::new();
+        let mut diagnostics = vec![];
+        for (index, row) in rows {
+            let line = index + 1;
+            let row = match row {
+                Ok(row) => row,
+                Err(error) => {
+                    diagnostics.push(Diagnostic::warning(Some(line), None, error));
+                    continue;
+                }
+            };
+            if row.iter().all(|v| v.trim().is_empty()) || !columns.keep(&row) {
+                continue;
+            }
+            match self.parse_row(&columns, &row) {
+                Ok(Some((currency, record))) => ledgers.entry(currency).or_default().push(record),
+                Ok(None) => {}
+                Err((column, message)) => {
+                    diagnostics.push(Diagnostic::warning(Some(line), Some(column), message))
+                }
+            }
+        }
+
+        let mut ledgers = ledgers
+            .into_iter()
+            .map(|(currency, records)| Ledger {
+                name: name.to_string(),
+                symbol: Symbol::Currency(currency),
+                records,
+                kind: LedgerKind::Bank,
+                statements: vec![],
+                quote_currency: None,
+                splits: vec![],
+            })
+            .collect::<Vec<_>>();
+        ledgers.sort_by_key(|l| l.symbol.to_string());
+
+        Ok(ParsedAccount {
+            ledgers,
+            diagnostics,
+        })
+    }
+
+    /// Parses a single row. Rows without a date are skipped, problems are returned with the
+    /// name of the offending column.
+    fn parse_row(
+        &self,
+        columns: &Columns,
+        row: &StringRecord,
+    ) -> Result<Option<(Currency, LedgerRecord)>, (&'static str, String)> {
+        let date = columns.value(row, columns.date);
+        // Rows without a date are summaries or intermediate headers.
+        if date.is_empty() {
+            return Ok(None);
+        }
+        let date = NaiveDate::parse_from_str(date, &self.date_format).map_err(|_| {
+            (
+                "date",
+                format!("{date} does not match {}", self.date_format),
+            )
+        })?;
+
+        let (column, amount) = match (columns.amount, columns.debit, columns.credit) {
+            (Some(amount), _, _) => ("amount", self.number(columns.value(row, amount))),
+            (None, debit, credit) => {
+                let debit = debit.map(|c| self.number(columns.value(row, c)));
+                let credit = credit.map(|c| self.number(columns.value(row, c)));
+                match (debit, credit) {
+                    // Some banks fill the unused column with a zero.
+                    (Some(Ok(Some(debit))), Some(credit))
+                        if debit.is_zero() && !matches!(credit, Ok(None)) =>
+                    {
+                        ("credit", credit.map(|v| v.map(Money::abs)))
+                    }
+                    (Some(Ok(Some(debit))), _) if debit.is_zero() => ("debit", Ok(Some(debit))),
+                    (Some(Ok(Some(debit))), _) => ("debit", Ok(Some(-debit.abs()))),
+                    (Some(Err(error)), _) => ("debit", Err(error)),
+                    (_, Some(credit)) => ("credit", credit.map(|v| v.map(Money::abs))),
+                    (Some(Ok(None)), None) | (None, None) => ("debit", Ok(None)),
+                }
+            }
+        };
+        let amount = amount
+            .map_err(|_| (column, "The amount is not a number".to_string()))?
+            .ok_or((column, "The amount is missing".to_string()))?;
+
+        let currency = match columns.currency {
+            Some(column) => {
+                let value = columns.value(row, column);
+                Currency::from_str(value).map_err(|_| {
+                    (
+                        "currency_column",
+                        format!("{value} is not a known currency"),
+                    )
+                })?
+            }
+            None => self.currency,
+        };
+
+        let record = LedgerRecord {
+            id: columns
+                .id
+                .map(|c| columns.value(row, c))
+                .filter(|v| !v.is_empty())
+                .map(ToString::to_string),
+            date,
+            amount,
+            description: columns
+                .description
+                .iter()
+                .map(|c| columns.value(row, *c))
+                .filter(|v| !v.is_empty())
+                .collect::<Vec<_>>()
+                .join(", "),
+            category: columns
+                .category
+                .map(|c| columns.value(row, c).to_string())
+                .unwrap_or_default(),
+            action: Action::Update,
+            price: None,
+        };
+        Ok(Some((currency, record)))
+    }
+
+    /// Parses an amount with the configured separators, empty values are `None`.
+    fn number(&self, value: &str) -> anyhow::Result<Option<Money>> {
+        let mut value = value.replace(char::is_whitespace, "");
+        if let Some(separator) = self.thousands_separator {
+            value = value.replace(separator, "");
+        }
+        if self.decimal_separator != '.' {
+            value = value.replace(self.decimal_separator, ".");
+        }
+        if value.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(value.parse()?))
+    }
+}
+
+/// The columns of a [`CustomFormat`] resolved to their indices.
+struct Columns {
+    date: usize,
+    amount: Option<usize>,
+    debit: Option<usize>,
+    credit: Option<usize>,
+    description: Vec<usize>,
+    category: Option<usize>,
+    id: Option<usize>,
+    currency: Option<usize>,
+    filters: Vec<(usize, Regex, bool)>,
+}
+
+impl Columns {
+    fn new(format: &CustomFormat, headers: &StringRecord) -> anyhow::Result<Columns> {
+        let resolve = |column: &Column| match column {
+            Column::Index(index) => Ok(*index),
+            Column::Name(name) => headers
+                .iter()
+                .position(|h| h.trim() == name)
+                .with_context(|| format!("The column {name} does not exist")),
+        };
+        let resolve_optional = |column: &Option<Column>| column.as_ref().map(resolve).transpose();
+
+        Ok(Columns {
+            date: resolve(&format.date)?,
+            amount: resolve_optional(&format.amount)?,
+            debit: resolve_optional(&format.debit)?,
+            credit: resolve_optional(&format.credit)?,
+            description: format
+                .description
+                .iter()
+                .map(resolve)
+                .collect::<anyhow::Result<_>>()?,
+            category: resolve_optional(&format.category)?,
+            id: resolve_optional(&format.id)?,
+            currency: resolve_optional(&format.currency_column)?,
+            filters: format
+                .filters
+                .iter()
+                .map(|filter| {
+                    let regex = Regex::new(&filter.pattern)
+                        .with_context(|| format!("{} is not a valid regex", filter.pattern))?;
+                    Ok((resolve(&filter.column)?, regex, filter.exclude))
+                })
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    fn value<'a>(&self, row: &'a StringRecord, column: usize) -> &'a str {
+        row.get(column).unwrap_or_default().trim()
+    }
+
+    fn keep(&self, row: &StringRecord) -> bool {
+        self.filters
+            .iter()
+            .all(|(column, regex, exclude)| regex.is_match(self.value(row, *column)) != *exclude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CustomFormat;
+
+    const MAPPING: &str = r#"
+delimiter: ;
+header_row: 2
+date: Booking date
+date_format: "%d.%m.%Y"
+debit: Debit
+credit: Credit
+thousands_separator: "'"
+description: [Text, Reference]
+id: Reference
+currency_column: Currency
+filters:
+  - column: Status
+    pattern: ^Pending$
+    exclude: true
+"#;
+
+    const CSV: &str = r#"Account;CH93 0076 2011 6238 5295 7
+Exported;01.02.2024
+Booking date;Text;Reference;Debit;Credit;Currency;Status
+03.01.2024;Coop Pronto;R-1;12.35;;CHF;Booked
+15.01.2024;Rent;R-2;1'850.00;;CHF;Booked
+25.01.2024;Salary;R-3;;7'500.00;CHF;Booked
+25.01.2024;Refund;R-6;0.00;42.00;CHF;Booked
+26.01.2024;Hotel;R-4;-230.10;;EUR;Booked
+31.01.2024;Card fee;R-5;5.00;;CHF;Pending
+;Total;;;;;
+"#;
+
+    #[test]
+    fn parse_custom_format() {
+        let format: CustomFormat = serde_yaml::from_str(MAPPING).unwrap();
+        insta::assert_debug_snapshot!(format.parse("Bank", CSV.into()).unwrap());
+    }
+
+    #[test]
+    fn parse_by_index() {
+        let format: CustomFormat = serde_yaml::from_str(
+            "{header_row: null, date: 0, amount: 2, description: [1], decimal_separator: ','}",
+        )
+        .unwrap();
+        let parsed = format
+            .parse("Bank", "2024-01-03,Coffee,\"-4,50\"\n".into())
+            .unwrap();
+        assert_eq!(parsed.ledgers[0].records[0].amount.to_string(), "-4.50");
+        assert_eq!(parsed.ledgers[0].records[0].description, "Coffee");
+    }
+
+    #[test]
+    #[should_panic(expected = "The column Amount does not exist")]
+    fn parse_fail() {
+        let format: CustomFormat = serde_yaml::from_str("{date: Date, amount: Amount}").unwrap();
+        format.parse("Bank", "Date,Betrag\n".into()).unwrap();
+    }
+}
@@ -1,24 +1,32 @@
-use std::{collections::HashMap, io::Cursor};
+use std::{collections::HashMap, io::Cursor, str::FromStr};
 
 use anyhow::{bail, Error};
 use chrono::NaiveDate;
 use csv::{Reader, ReaderBuilder};
+use regex::Regex;
 
-use crate::fx::{Currency, Symbol};
+use crate::{
+    fx::{Currency, Symbol},
+    money::Money,
+    realms::portfolio::state::Action,
+};
 
-use super::{Ledger, LedgerRecord, ParsedAccount, Parser, StockLedgerRecord};
+use super::{
+    csv_rows, Diagnostic, Ledger, LedgerKind, LedgerRecord, ParsedAccount, Parser, Split,
+    StockLedgerRecord,
+};
 
 pub struct Ibkr {}
 
 impl Parser for Ibkr {
     fn parse(name: &str, content: String) -> anyhow::Result<ParsedAccount> {
         let mut ledgers: Vec<Ledger> = vec![];
-        let mut currency_ledger: Option<Ledger> = None;
+        // One cash ledger for each currency the account holds.
+        let mut currency_ledgers: Vec<Ledger> = vec![];
         let lines = content.lines().collect::<Vec<_>>();
         let mut end = lines.len();
         let mut header_found = false;
-
-        let currency = Currency::USD;
+        let mut diagnostics = vec![];
 
         // We scan the entire file from the back until we find a HEADER row that tells us what kind of
         // transactions the transactions following the header are.
@@ -30,63 +38,92 @@ impl Parser for Ibkr {
                 let reader = ReaderBuilder::new()
                     .delimiter(b',')
                     .from_reader(Cursor::new(&content));
+                let mut section = vec![];
 
                 // Extract the cash transactions.
                 if line.contains("\"CTRN\"") {
-                    let records = parse_cash_transactions(reader)?;
-                    if let Some(ledger) = &mut currency_ledger {
-                        ledger.records.extend(records);
-                    } else {
-                        currency_ledger = Some(super::Ledger {
-                            name: name.to_string(),
-                            records,
-                            symbol: Symbol::Currency(currency),
-                            kind: super::LedgerKind::Bank,
-                        });
+                    for (currency, records) in parse_cash_transactions(reader, &mut section)? {
+                        let symbol = Symbol::Currency(currency);
+                        ledger(&mut currency_ledgers, name, symbol, LedgerKind::Bank)
+                            .records
+                            .extend(records);
+                    }
+                }
+                // Extract the splits and other changes of the held shares.
+                else if line.contains("\"CORP\"") {
+                    let actions = parse_corporate_actions(reader, &mut section)?;
+                    for (symbol, currency, action) in actions {
+                        let stock = ledger(&mut ledgers, name, symbol, LedgerKind::Stock);
+                        stock.quote_currency.get_or_insert(currency);
+                        match action {
+                            CorporateAction::Split(split) => {
+                                if !stock.splits.iter().any(|s| s.date == split.date) {
+                                    stock.splits.push(split);
+                                }
+                            }
+                            CorporateAction::Shares(record) => stock.records.push(record),
+                        }
                     }
                 }
                 // Extract the trades.
                 else if line.contains("\"TRNT\"") {
-                    let data = parse_stock_transactions(reader)?;
-                    for (symbol, records) in data {
-                        // For each stock transaction we have an entry on the individual symbols ledger
-                        // but also on the main currency ledger because it does not export the stock transactions.
-                        // We need to deduce the amount of {main_currency} we paid for said stock.
-                        let currency_records = records.iter().map(|r| LedgerRecord {
-                            amount: r.amount * -1.0 * r.price,
-                            date: r.date,
-                            description: r.description.clone(),
-                            category: r.category.clone(),
-                        });
-                        if let Some(ledger) = &mut currency_ledger {
-                            ledger.records.extend(currency_records);
-                        } else {
-                            currency_ledger = Some(super::Ledger {
-                                name: name.to_string(),
-                                records: currency_records.collect(),
-                                symbol: Symbol::Currency(currency),
-                                kind: super::LedgerKind::Bank,
+                    let data = parse_stock_transactions(reader, &mut section)?;
+                    for ((symbol, currency), records) in data {
+                        // For each stock transaction we have an entry on the individual symbols
+                        // ledger but also on the ledger of the trade currency because it does not
+                        // export the stock transactions. We need to deduce the amount of {currency}
+                        // we paid for said stock. The commission is booked as a fee of its own so
+                        // it can be told apart from the price.
+                        let mut currency_records = vec![];
+                        for r in &records {
+                            currency_records.push(LedgerRecord {
+                                id: r.id.clone(),
+                                amount: (-r.amount).convert(r.price, currency)?,
+                                date: r.date,
+                                description: r.description.clone(),
+                                category: r.category.clone(),
+                                action: Action::Update,
+                                price: None,
                             });
+                            if !r.commission.is_zero() {
+                                currency_records.push(LedgerRecord {
+                                    id: r.id.as_ref().map(|id| format!("{id}:commission")),
+                                    amount: r.commission,
+                                    date: r.date,
+                                    description: format!("Commission: {}", r.description),
+                                    category: r.category.clone(),
+                                    action: Action::Fee,
+                                    price: None,
+                                });
+                            }
                         }
+                        let cash = Symbol::Currency(currency);
+                        ledger(&mut currency_ledgers, name, cash, LedgerKind::Bank)
+                            .records
+                            .extend(currency_records);
 
                         // We also need to add each record to the individual stock ledgers of course.
-                        // But here we just add the number of shares, not the price we paid in {main_currency}.
-                        let records = records.into_iter().map(From::from).collect();
-                        if let Some(ledger) = ledgers.iter_mut().find(|l| l.symbol == symbol) {
-                            ledger.records.extend(records);
-                        } else {
-                            ledgers.push(super::Ledger {
-                                name: name.to_string(),
-                                records,
-                                symbol,
-                                kind: super::LedgerKind::Stock,
-                            });
-                        }
+                        // But here we just add the number of shares, not the price we paid in {currency}.
+                        let stock = ledger(&mut ledgers, name, symbol, LedgerKind::Stock);
+                        stock.quote_currency.get_or_insert(currency);
+                        stock
+                            .records
+                            .extend(records.into_iter().map(LedgerRecord::from));
                     }
                 } else {
-                    bail!("Unknown export")
+                    diagnostics.push(Diagnostic::warning(
+                        Some(index + 1),
+                        None,
+                        "Only trades (TRNT), cash transactions (CTRN) and corporate actions (CORP) \
+                         are imported",
+                    ));
                 };
 
+                // The lines of the section are relative to its HEADER row.
+                diagnostics.extend(section.into_iter().map(|mut diagnostic| {
+                    diagnostic.line = diagnostic.line.map(|line| line + index);
+                    diagnostic
+                }));
                 end = index;
             }
         }
@@ -95,30 +132,106 @@ impl Parser for Ibkr {
             bail!("The data seems to not be in IBKR format as no HEADER lines were found")
         }
 
-        if let Some(ledeger) = currency_ledger {
-            ledgers.push(ledeger);
+        // The sections are grouped in hash maps, so the ledgers are sorted to always come in the
+        // same order: the stocks first and the cash after them.
+        ledgers.sort_by_key(|l| l.symbol.to_string());
+        currency_ledgers.sort_by_key(|l| l.symbol.to_string());
+        ledgers.extend(currency_ledgers);
+
+        Ok(ParsedAccount {
+            ledgers,
+            diagnostics,
+        })
+    }
+
+    fn detect(content: &str) -> f64 {
+        // Flex queries consist of sections, each introduced by a HEADER row.
+        let headers = content
+            .lines()
+            .filter(|line| line.starts_with("\"HEADER\""))
+            .collect::<Vec<_>>();
+        if headers
+            .iter()
+            .any(|line| line.contains("\"TRNT\"") || line.contains("\"CTRN\""))
+        {
+            1.0
+        } else if !headers.is_empty() {
+            0.5
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Gets the ledger of the given symbol, creating it if there is none yet.
+fn ledger<'a>(
+    ledgers: &'a mut Vec<Ledger>,
+    name: &str,
+    symbol: Symbol,
+    kind: LedgerKind,
+) -> &'a mut Ledger {
+    let index = match ledgers.iter().position(|l| l.symbol == symbol) {
+        Some(index) => index,
+        None => {
+            ledgers.push(Ledger {
+                name: name.to_string(),
+                records: vec![],
+                symbol,
+                kind,
+                statements: vec![],
+                quote_currency: None,
+                splits: vec![],
+            });
+            ledgers.len() - 1
         }
+    };
+    &mut ledgers[index]
+}
 
-        Ok(ParsedAccount { ledgers })
+/// Reads the `CurrencyPrimary` column of a row.
+///
+/// Flex queries without the column are assumed to be in USD.
+fn currency(
+    line: usize,
+    currency: Option<String>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<Currency> {
+    let Some(currency) = currency.filter(|c| !c.is_empty()) else {
+        return Some(Currency::USD);
+    };
+    match Currency::from_str(&currency) {
+        Ok(currency) => Some(currency),
+        Err(error) => {
+            diagnostics.push(Diagnostic::warning(
+                Some(line),
+                Some("CurrencyPrimary"),
+                error,
+            ));
+            None
+        }
     }
 }
 
-/// Gets all the stock purchases in the given reader.
+/// Gets all the stock purchases in the given reader by symbol and trade currency.
 ///
 /// Contains everything a regular transaction contains but also a stock price.
 fn parse_stock_transactions(
-    mut reader: Reader<Cursor<&&String>>,
-) -> Result<HashMap<Symbol, Vec<StockLedgerRecord>>, Error> {
+    reader: Reader<Cursor<&&String>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<HashMap<(Symbol, Currency), Vec<StockLedgerRecord>>, Error> {
     let mut records = HashMap::new();
-    let data = reader.deserialize::<StockRecord>();
-
-    for record in data.flatten() {
-        let symbol = Symbol::from(record.symbol);
-        let entry = records.entry(symbol).or_insert(vec![]);
+    for (line, record) in csv_rows::<StockRecord, _>(reader, diagnostics)? {
+        let Some(currency) = currency(line, record.currency, diagnostics) else {
+            continue;
+        };
+        let symbol = Symbol::Stock(record.symbol);
+        let entry = records.entry((symbol, currency)).or_insert(vec![]);
         entry.push(StockLedgerRecord {
+            id: record.id,
             date: record.date,
             amount: record.amount,
             price: record.price,
+            commission: record.commission.unwrap_or_default(),
             description: record.description,
             category: "Broker".to_string(),
         })
@@ -128,53 +241,170 @@ fn parse_stock_transactions(
 
 #[derive(Debug, serde::Deserialize)]
 struct StockRecord {
+    #[serde(rename = "TransactionID", default)]
+    id: Option<String>,
     #[serde(rename = "TradeDate")]
     date: NaiveDate,
     #[serde(rename = "Quantity")]
-    amount: f64,
+    amount: Money,
     #[serde(rename = "TradePrice")]
     price: f64,
+    /// Negative if the commission was paid, positive for a rebate.
+    #[serde(rename = "IBCommission", default)]
+    commission: Option<Money>,
     #[serde(rename = "Description")]
     description: String,
     #[serde(rename = "Symbol")]
     symbol: String,
+    #[serde(rename = "CurrencyPrimary", default)]
+    currency: Option<String>,
 }
 
-/// Parse all the cash transactions in the given reader.
+/// Parse all the cash transactions in the given reader by currency.
 fn parse_cash_transactions(
-    mut reader: Reader<Cursor<&&String>>,
-) -> Result<Vec<LedgerRecord>, Error> {
-    let records = reader
-        .deserialize::<CashRecord>()
-        .map(|v| {
-            v.map(|v| LedgerRecord {
+    reader: Reader<Cursor<&&String>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<HashMap<Currency, Vec<LedgerRecord>>, Error> {
+    let mut records = HashMap::<_, Vec<_>>::new();
+    for (line, v) in csv_rows::<CashRecord, _>(reader, diagnostics)? {
+        let Some(currency) = currency(line, v.currency, diagnostics) else {
+            continue;
+        };
+        records.entry(currency).or_default().push(LedgerRecord {
+            id: v.id,
+            date: v.date,
+            amount: v.amount,
+            description: v.description,
+            category: "Broker".to_string(),
+            action: action(v.kind.as_deref().unwrap_or_default()),
+            price: None,
+        });
+    }
+
+    Ok(records)
+}
+
+enum CorporateAction {
+    Split(Split),
+    /// Shares which were added or removed by e.g. a spin-off or a merger.
+    Shares(LedgerRecord),
+}
+
+/// Parse all the corporate actions in the given reader by symbol and currency.
+///
+/// Forward and reverse splits are not booked as shares, the quantities before them are adjusted
+/// instead to match the split-adjusted price history.
+fn parse_corporate_actions(
+    reader: Reader<Cursor<&&String>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<(Symbol, Currency, CorporateAction)>, Error> {
+    let ratio = Regex::new(r"SPLIT (\d+(?:\.\d+)?) FOR (\d+(?:\.\d+)?)")?;
+    let mut actions = vec![];
+    for (line, v) in csv_rows::<CorporateActionRecord, _>(reader, diagnostics)? {
+        let Some(currency) = currency(line, v.currency, diagnostics) else {
+            continue;
+        };
+        let symbol = Symbol::Stock(v.symbol);
+        let action = match v.kind.as_deref() {
+            Some("FS" | "RS") => {
+                let Some(captures) = ratio.captures(&v.description) else {
+                    diagnostics.push(Diagnostic::warning(
+                        Some(line),
+                        Some("Description"),
+                        "The ratio of the split could not be found",
+                    ));
+                    continue;
+                };
+                let new = captures[1].parse::<f64>()?;
+                let old = captures[2].parse::<f64>()?;
+                let split = Split {
+                    date: v.date,
+                    ratio: new / old,
+                };
+                if !split.is_valid() {
+                    diagnostics.push(Diagnostic::warning(
+                        Some(line),
+                        Some("Description"),
+                        format!("{new} for {old} is not a valid ratio of a split"),
+                    ));
+                    continue;
+                }
+                CorporateAction::Split(split)
+            }
+            _ if v.amount.is_zero() => continue,
+            _ => CorporateAction::Shares(LedgerRecord {
+                id: v.id,
                 date: v.date,
                 amount: v.amount,
                 description: v.description,
                 category: "Broker".to_string(),
-            })
-        })
-        .collect::<Result<_, _>>()?;
+                action: Action::Update,
+                price: None,
+            }),
+        };
+        actions.push((symbol, currency, action));
+    }
 
-    Ok(records)
+    Ok(actions)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct CorporateActionRecord {
+    #[serde(rename = "TransactionID", default)]
+    id: Option<String>,
+    #[serde(rename = "ReportDate")]
+    date: NaiveDate,
+    #[serde(rename = "Quantity")]
+    amount: Money,
+    #[serde(rename = "Description")]
+    description: String,
+    #[serde(rename = "Symbol")]
+    symbol: String,
+    #[serde(rename = "CurrencyPrimary", default)]
+    currency: Option<String>,
+    #[serde(rename = "Type", default)]
+    kind: Option<String>,
+}
+
+/// Classifies a cash transaction by its `Type` column.
+fn action(kind: &str) -> Action {
+    // Withholding tax on dividends mentions the dividend as well, so taxes go first.
+    match kind {
+        _ if kind.contains("Tax") => Action::Tax,
+        _ if kind.contains("Dividend") => Action::Dividend,
+        _ if kind.contains("Interest") => Action::Interest,
+        _ if kind.contains("Fee") || kind.contains("Commission") => Action::Fee,
+        _ => Action::Update,
+    }
 }
 
 #[derive(Debug, serde::Deserialize)]
 struct CashRecord {
+    #[serde(rename = "TransactionID", default)]
+    id: Option<String>,
     #[serde(rename = "SettleDate")]
     date: NaiveDate,
     #[serde(rename = "Amount")]
-    amount: f64,
+    amount: Money,
     #[serde(rename = "Description")]
     description: String,
     #[serde(rename = "Symbol")]
     #[allow(unused)]
     symbol: String,
+    #[serde(rename = "CurrencyPrimary", default)]
+    currency: Option<String>,
+    #[serde(rename = "Type", default)]
+    kind: Option<String>,
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::banks::Parser;
+    use crate::{
+        banks::Parser,
+        fx::{Currency, Symbol},
+        money::Money,
+        realms::portfolio::state::Action,
+    };
 
     const TRANSACTIONS_BAD: &str = r#"Trade date;Trade time;Booking date;Value date;Currency;Debit;Credit;Individual amount;Balance;Transaction no.;Description1;Description2;Description3;Footnotes;"#;
     const TRANSACTIONS: &str = r#""HEADER","TRNT","Symbol","Description","ISIN","CurrencyPrimary","Quantity","TradePrice","TradeDate"
@@ -193,6 +423,119 @@ mod tests {
         insta::assert_debug_snapshot!(super::Ibkr::parse("IBKR", TRANSACTIONS.into()).unwrap());
     }
 
+    #[test]
+    fn parse_multiple_currencies() {
+        const TRANSACTIONS: &str = r#""HEADER","TRNT","Symbol","Description","ISIN","CurrencyPrimary","Quantity","TradePrice","TradeDate"
+"DATA","TRNT","VT","VANGUARD TOT WORLD STK ETF","US9220427424","USD","10","113","2024-06-24"
+"DATA","TRNT","VWRL","VANGUARD FTSE ALL-WORLD","IE00B3RBWM25","EUR","5","110","2024-06-25"
+"DATA","TRNT","NESN","NESTLE SA-REG","CH0038863350","CHF","2","90","2024-06-26"
+"DATA","TRNT","BAD","BAD CURRENCY","","XXX","1","1","2024-06-26"
+"HEADER","CTRN","Symbol","Description","ISIN","CurrencyPrimary","Amount","Type","SettleDate"
+"DATA","CTRN","","CASH RECEIPTS / ELECTRONIC FUND TRANSFERS","","USD","2000","Deposits/Withdrawals","2024-06-20"
+"DATA","CTRN","","CASH RECEIPTS / ELECTRONIC FUND TRANSFERS","","EUR","1000","Deposits/Withdrawals","2024-06-20"
+"#;
+        let parsed = super::Ibkr::parse("IBKR", TRANSACTIONS.into()).unwrap();
+
+        let cash = |currency| {
+            parsed
+                .ledgers
+                .iter()
+                .find(|l| l.symbol == Symbol::Currency(currency))
+                .map(|l| l.records.iter().map(|r| r.amount.to_f64()).sum::<f64>())
+        };
+        assert_eq!(cash(Currency::USD), Some(2000.0 - 1130.0));
+        assert_eq!(cash(Currency::EUR), Some(1000.0 - 550.0));
+        assert_eq!(cash(Currency::CHF), Some(-180.0));
+
+        let quote = |symbol: &str| {
+            parsed
+                .ledgers
+                .iter()
+                .find(|l| l.symbol == Symbol::from(symbol))
+                .and_then(|l| l.quote_currency)
+        };
+        assert_eq!(quote("VT"), Some(Currency::USD));
+        assert_eq!(quote("VWRL"), Some(Currency::EUR));
+        assert_eq!(quote("NESN"), Some(Currency::CHF));
+        assert_eq!(quote("BAD"), None);
+
+        assert_eq!(parsed.diagnostics.len(), 1);
+        assert_eq!(parsed.diagnostics[0].line, Some(5));
+        assert_eq!(
+            parsed.diagnostics[0].column.as_deref(),
+            Some("CurrencyPrimary")
+        );
+    }
+
+    #[test]
+    fn parse_actions() {
+        const TRANSACTIONS: &str = r#""HEADER","TRNT","TransactionID","Symbol","Description","CurrencyPrimary","Quantity","TradePrice","IBCommission","TradeDate"
+"DATA","TRNT","T-1","VT","VANGUARD TOT WORLD STK ETF","USD","10","113","-1.05","2024-06-24"
+"DATA","TRNT","T-2","VT","VANGUARD TOT WORLD STK ETF","USD","-5","115","","2024-07-01"
+"HEADER","CTRN","TransactionID","Symbol","Description","CurrencyPrimary","Amount","Type","SettleDate"
+"DATA","CTRN","C-1","VT","VT(US9220427424) CASH DIVIDEND USD 0.5779 PER SHARE (Ordinary Dividend)","USD","5.78","Dividends","2024-06-25"
+"DATA","CTRN","C-2","VT","VT(US9220427424) CASH DIVIDEND USD 0.5779 PER SHARE - US TAX","USD","-0.87","Withholding Tax","2024-06-25"
+"DATA","CTRN","C-3","","USD CREDIT INT FOR JUN-2024","USD","3.12","Broker Interest Received","2024-07-03"
+"DATA","CTRN","C-4","","BALANCE OF MONTHLY MINIMUM FEE FOR JUN 2024","USD","-10","Other Fees","2024-07-03"
+"DATA","CTRN","C-5","","CASH RECEIPTS / ELECTRONIC FUND TRANSFERS","USD","2000","Deposits/Withdrawals","2024-06-20"
+"#;
+        let parsed = super::Ibkr::parse("IBKR", TRANSACTIONS.into()).unwrap();
+        let cash = parsed
+            .ledgers
+            .iter()
+            .find(|l| l.symbol == Symbol::Currency(Currency::USD))
+            .unwrap();
+        let action = |id: &str| {
+            cash.records
+                .iter()
+                .find(|r| r.id.as_deref() == Some(id))
+                .map(|r| (r.action, r.amount.to_f64()))
+        };
+
+        assert_eq!(action("T-1"), Some((Action::Update, -1130.0)));
+        assert_eq!(action("T-1:commission"), Some((Action::Fee, -1.05)));
+        assert_eq!(action("T-2"), Some((Action::Update, 575.0)));
+        assert_eq!(action("T-2:commission"), None);
+        assert_eq!(action("C-1"), Some((Action::Dividend, 5.78)));
+        assert_eq!(action("C-2"), Some((Action::Tax, -0.87)));
+        assert_eq!(action("C-3"), Some((Action::Interest, 3.12)));
+        assert_eq!(action("C-4"), Some((Action::Fee, -10.0)));
+        assert_eq!(action("C-5"), Some((Action::Update, 2000.0)));
+    }
+
+    #[test]
+    fn parse_corporate_actions() {
+        const TRANSACTIONS: &str = r#""HEADER","TRNT","Symbol","Description","CurrencyPrimary","Quantity","TradePrice","TradeDate"
+"DATA","TRNT","NVDA","NVIDIA CORP","USD","10","1200","2024-05-02"
+"HEADER","CORP","TransactionID","Symbol","Description","CurrencyPrimary","Quantity","Type","ReportDate"
+"DATA","CORP","A-1","NVDA","NVDA(US67066G1040) SPLIT 10 FOR 1 (NVDA, NVIDIA CORP, US67066G1040)","USD","90","FS","2024-06-10"
+"DATA","CORP","A-2","GE","GE(US3696043013) SPLIT 1 FOR 8 (GE, GENERAL ELECTRIC CO, US3696043013)","USD","-70","RS","2021-08-02"
+"DATA","CORP","A-3","GEHC","GE(US3696043013) SPINOFF 1 FOR 3 (GEHC, GE HEALTHCARE, US36266G1076)","USD","3","SO","2023-01-04"
+"DATA","CORP","A-4","XYZ","XYZ(US0000000000) SPLIT (XYZ, UNKNOWN, US0000000000)","USD","5","FS","2023-01-04"
+"DATA","CORP","A-5","XYZ","XYZ(US0000000000) SPLIT 1 FOR 0 (XYZ, UNKNOWN, US0000000000)","USD","5","FS","2023-02-01"
+"DATA","CORP","A-6","XYZ","XYZ(US0000000000) SPLIT 0 FOR 1 (XYZ, UNKNOWN, US0000000000)","USD","-5","RS","2023-03-01"
+"#;
+        let parsed = super::Ibkr::parse("IBKR", TRANSACTIONS.into()).unwrap();
+        let stock = |symbol: &str| {
+            parsed
+                .ledgers
+                .iter()
+                .find(|l| l.symbol == Symbol::from(symbol))
+                .unwrap()
+        };
+
+        let nvda = stock("NVDA");
+        assert_eq!(nvda.records.len(), 1);
+        assert_eq!(nvda.splits.len(), 1);
+        assert_eq!(nvda.splits[0].ratio, 10.0);
+        assert_eq!(stock("GE").splits[0].ratio, 0.125);
+        assert_eq!(stock("GEHC").records[0].amount, Money::from(3));
+        assert_eq!(stock("GEHC").quote_currency, Some(Currency::USD));
+
+        let lines = parsed.diagnostics.iter().map(|d| d.line);
+        assert_eq!(lines.collect::<Vec<_>>(), vec![Some(7), Some(8), Some(9)]);
+    }
+
     #[test]
     #[should_panic(
         expected = "The data seems to not be in IBKR format as no HEADER lines were found"
@@ -3,26 +3,31 @@ use std::io::Cursor;
 use chrono::NaiveDate;
 use csv::ReaderBuilder;
 
-use super::{Ledger, LedgerKind, LedgerRecord, ParsedAccount, Parser};
+use crate::{money::Money, realms::portfolio::state::Action};
+
+use super::{csv_rows, Ledger, LedgerKind, LedgerRecord, ParsedAccount, Parser};
 
 pub struct Neon {}
 
 impl Parser for Neon {
     fn parse(name: &str, content: String) -> anyhow::Result<ParsedAccount> {
-        let records = ReaderBuilder::new()
+        let mut diagnostics = vec![];
+        let reader = ReaderBuilder::new()
             .delimiter(b';')
-            .from_reader(Cursor::new(&content))
-            .deserialize::<Record>()
-            .filter(|v| (v.as_ref()).map_or("", |v| &v.spaces) == "no")
-            .map(|v| {
-                v.map(|v| LedgerRecord {
-                    date: v.date,
-                    amount: v.amount,
-                    description: v.description,
-                    category: v.category,
-                })
+            .from_reader(Cursor::new(&content));
+        let records = csv_rows::<Record, _>(reader, &mut diagnostics)?
+            .into_iter()
+            .filter(|(_, v)| v.spaces == "no")
+            .map(|(_, v)| LedgerRecord {
+                id: None,
+                date: v.date,
+                amount: v.amount,
+                description: v.description,
+                category: v.category,
+                action: Action::Update,
+                price: None,
             })
-            .collect::<Result<_, _>>()?;
+            .collect();
 
         Ok(ParsedAccount {
             ledgers: vec![Ledger {
@@ -30,9 +35,30 @@ impl Parser for Neon {
                 records,
                 symbol: crate::fx::Symbol::Currency(crate::fx::Currency::CHF),
                 kind: LedgerKind::Bank,
+                statements: vec![],
+                quote_currency: None,
+                splits: vec![],
             }],
+            diagnostics,
         })
     }
+
+    fn detect(content: &str) -> f64 {
+        super::header_score(
+            content,
+            b';',
+            &[
+                "Date",
+                "Amount",
+                "Original amount",
+                "Original currency",
+                "Exchange rate",
+                "Description",
+                "Subject",
+                "Category",
+            ],
+        )
+    }
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -40,7 +66,7 @@ struct Record {
     #[serde(rename = "Date")]
     date: NaiveDate,
     #[serde(rename = "Amount")]
-    amount: f64,
+    amount: Money,
     #[serde(rename = "Original amount")]
     _original_amount: Option<f64>,
     #[serde(rename = "Original currency")]
@@ -0,0 +1,594 @@
+use std::{collections::HashMap, str::FromStr};
+
+use anyhow::{bail, Context};
+use chrono::NaiveDate;
+use roxmltree::{Document, Node};
+
+use crate::{
+    fx::{Currency, Symbol},
+    money::Money,
+    realms::portfolio::state::Action,
+};
+
+use super::{
+    xml::{child, child_text, children, line},
+    Diagnostic, Ledger, LedgerKind, LedgerRecord, ParsedAccount, Parser, Statement,
+    StatementBalance,
+};
+
+/// OFX/QFX downloads, both the SGML based OFX 1.x and the XML based OFX 2.x.
+pub struct Ofx {}
+
+impl Parser for Ofx {
+    fn parse(name: &str, content: String) -> anyhow::Result<ParsedAccount> {
+        let xml = to_xml(&content)?;
+        let document = Document::parse(&xml).context("The OFX data could not be read")?;
+        let root = document.root_element();
+
+        let mut ledgers: Vec<Ledger> = vec![];
+        let mut diagnostics = vec![];
+        let mut found = false;
+
+        // Bank and credit card statements.
+        for statement in root
+            .descendants()
+            .filter(|n| n.has_tag_name("STMTRS") || n.has_tag_name("CCSTMTRS"))
+        {
+            found = true;
+            let currency = currency(statement)?;
+            let mut records = vec![];
+            for transaction in child(statement, "BANKTRANLIST")
+                .into_iter()
+                .flat_map(|list| children(list, "STMTTRN"))
+            {
+                match parse_transaction(transaction) {
+                    Ok(record) => records.push(record),
+                    Err(error) => diagnostics.push(warning(transaction, error)),
+                }
+            }
+            let closing_balance = child(statement, "LEDGERBAL")
+                .map(|balance| {
+                    anyhow::Ok(StatementBalance {
+                        date: date(balance, "DTASOF")?,
+                        amount: amount(balance, "BALAMT")?,
+                    })
+                })
+                .transpose()?;
+
+            add_records(
+                &mut ledgers,
+                name,
+                Symbol::Currency(currency),
+                LedgerKind::Bank,
+                records,
+                closing_balance.map(|closing_balance| Statement {
+                    iban: None,
+                    opening_balance: None,
+                    closing_balance: Some(closing_balance),
+                }),
+            );
+        }
+
+        // Investment statements.
+        for statement in root.descendants().filter(|n| n.has_tag_name("INVSTMTRS")) {
+            found = true;
+            let currency = currency(statement)?;
+            let tickers = tickers(root);
+            let mut cash = vec![];
+            let mut stocks = HashMap::<Symbol, Vec<LedgerRecord>>::new();
+
+            for transaction in child(statement, "INVTRANLIST")
+                .into_iter()
+                .flat_map(|list| list.children().filter(|n| n.is_element()))
+            {
+                if let Err(error) = parse_investment(transaction, &tickers, &mut cash, &mut stocks)
+                {
+                    diagnostics.push(warning(transaction, error));
+                }
+            }
+
+            // The stocks are added in the order of their symbols, the same on every parse.
+            let mut stocks = stocks.into_iter().collect::<Vec<_>>();
+            stocks.sort_by_key(|(symbol, _)| symbol.to_string());
+            for (symbol, records) in stocks {
+                // The securities are priced in the default currency of the statement.
+                add_records(&mut ledgers, name, symbol, LedgerKind::Stock, records, None)
+                    .quote_currency = Some(currency);
+            }
+            add_records(
+                &mut ledgers,
+                name,
+                Symbol::Currency(currency),
+                LedgerKind::Bank,
+                cash,
+                None,
+            );
+        }
+
+        if !found {
+            bail!("The data seems to not be in OFX format as no statements were found")
+        }
+
+        Ok(ParsedAccount {
+            ledgers,
+            diagnostics,
+        })
+    }
+
+    fn detect(content: &str) -> f64 {
+        if content.contains("<OFX>") {
+            1.0
+        } else if content.contains("OFXHEADER") {
+            0.8
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Parses an entry of the `INVTRANLIST` into the cash and stock records.
+fn parse_investment(
+    transaction: Node,
+    tickers: &HashMap<String, String>,
+    cash: &mut Vec<LedgerRecord>,
+    stocks: &mut HashMap<Symbol, Vec<LedgerRecord>>,
+) -> anyhow::Result<()> {
+    match transaction.tag_name().name() {
+        "INVBANKTRAN" => {
+            if let Some(transaction) = child(transaction, "STMTTRN") {
+                cash.push(parse_transaction(transaction)?);
+            }
+        }
+        "BUYSTOCK" | "SELLSTOCK" | "BUYMF" | "SELLMF" | "BUYOTHER" | "SELLOTHER" => {
+            let trade = child(transaction, "INVBUY")
+                .or_else(|| child(transaction, "INVSELL"))
+                .context("A trade has neither INVBUY nor INVSELL")?;
+            let (id, date, description) = investment_transaction(trade)?;
+            let symbol = security(trade, tickers)?;
+            // The units are negative for sells, the total is negative for buys.
+            let units = amount(trade, "UNITS")?;
+            let total = amount(trade, "TOTAL")?;
+            let price = child(trade, "UNITPRICE")
+                .map(|_| amount(trade, "UNITPRICE").map(Money::to_f64))
+                .transpose()?;
+
+            stocks.entry(symbol).or_default().push(LedgerRecord {
+                id: id.clone(),
+                date,
+                amount: units,
+                description: description.clone(),
+                category: "Broker".to_string(),
+                action: Action::Update,
+                price,
+            });
+            cash.push(LedgerRecord {
+                id,
+                date,
+                amount: total,
+                description,
+                category: "Broker".to_string(),
+                action: Action::Update,
+                price: None,
+            });
+        }
+        "INCOME" => {
+            let (id, date, description) = investment_transaction(transaction)?;
+            cash.push(LedgerRecord {
+                id,
+                date,
+                amount: amount(transaction, "TOTAL")?,
+                description,
+                category: "Broker".to_string(),
+                action: income(transaction),
+                price: None,
+            });
+        }
+        "REINVEST" => {
+            // The income is paid out to the cash and used to buy units right away. The purchase
+            // shares the id of the units, like the cash leg of a trade.
+            let (id, date, description) = investment_transaction(transaction)?;
+            let symbol = security(transaction, tickers)?;
+            let total = amount(transaction, "TOTAL")?.abs();
+            let price = child(transaction, "UNITPRICE")
+                .map(|_| amount(transaction, "UNITPRICE").map(Money::to_f64))
+                .transpose()?;
+
+            stocks.entry(symbol).or_default().push(LedgerRecord {
+                id: id.clone(),
+                date,
+                amount: amount(transaction, "UNITS")?,
+                description: description.clone(),
+                category: "Broker".to_string(),
+                action: Action::Update,
+                price,
+            });
+            cash.push(LedgerRecord {
+                id: id.as_ref().map(|id| format!("{id}:income")),
+                date,
+                amount: total,
+                description: description.clone(),
+                category: "Broker".to_string(),
+                action: income(transaction),
+                price: None,
+            });
+            cash.push(LedgerRecord {
+                id,
+                date,
+                amount: -total,
+                description,
+                category: "Broker".to_string(),
+                action: Action::Update,
+                price: None,
+            });
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The action of an `INCOME` or `REINVEST` by its `INCOMETYPE`.
+fn income(transaction: Node) -> Action {
+    match child_text(transaction, "INCOMETYPE").map(str::trim) {
+        Some("DIV") => Action::Dividend,
+        Some("INTEREST") => Action::Interest,
+        _ => Action::Update,
+    }
+}
+
+/// A transaction which could not be read is skipped with a warning.
+fn warning(node: Node, error: anyhow::Error) -> Diagnostic {
+    Diagnostic::warning(
+        Some(line(node)),
+        Some(node.tag_name().name()),
+        format!("{error:#}"),
+    )
+}
+
+fn add_records<'a>(
+    ledgers: &'a mut Vec<Ledger>,
+    name: &str,
+    symbol: Symbol,
+    kind: LedgerKind,
+    records: Vec<LedgerRecord>,
+    statement: Option<Statement>,
+) -> &'a mut Ledger {
+    let index = match ledgers.iter().position(|l| l.symbol == symbol) {
+        Some(index) => index,
+        None => {
+            ledgers.push(Ledger {
+                name: name.to_string(),
+                symbol,
+                records: vec![],
+                kind,
+                statements: vec![],
+                quote_currency: None,
+                splits: vec![],
+            });
+            ledgers.len() - 1
+        }
+    };
+    let ledger = &mut ledgers[index];
+    ledger.records.extend(records);
+    ledger.statements.extend(statement);
+    ledger
+}
+
+/// Parses a single `STMTTRN` into a record.
+fn parse_transaction(transaction: Node) -> anyhow::Result<LedgerRecord> {
+    let description = ["NAME", "MEMO"]
+        .into_iter()
+        .filter_map(|tag| child_text(transaction, tag))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let kind = child_text(transaction, "TRNTYPE").unwrap_or_default();
+    Ok(LedgerRecord {
+        id: child_text(transaction, "FITID").map(ToString::to_string),
+        date: date(transaction, "DTPOSTED")?,
+        amount: amount(transaction, "TRNAMT")?,
+        description,
+        category: kind.to_string(),
+        action: match kind.trim() {
+            "INT" => Action::Interest,
+            "DIV" => Action::Dividend,
+            "FEE" | "SRVCHG" => Action::Fee,
+            _ => Action::Update,
+        },
+        price: None,
+    })
+}
+
+/// Reads the `INVTRAN` aggregate shared by all investment transactions.
+fn investment_transaction(node: Node) -> anyhow::Result<(Option<String>, NaiveDate, String)> {
+    let transaction = child(node, "INVTRAN").context("An investment transaction has no INVTRAN")?;
+    Ok((
+        child_text(transaction, "FITID").map(ToString::to_string),
+        date(transaction, "DTTRADE")?,
+        child_text(transaction, "MEMO")
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    ))
+}
+
+/// Resolves the `SECID` of a trade to the ticker from the security list, falling back to the
+/// unique id (usually the CUSIP or ISIN).
+fn security(node: Node, tickers: &HashMap<String, String>) -> anyhow::Result<Symbol> {
+    let id = child(node, "SECID")
+        .and_then(|s| child_text(s, "UNIQUEID"))
+        .context("A trade has no security id")?;
+    Ok(Symbol::Stock(
+        tickers.get(id).cloned().unwrap_or(id.to_string()),
+    ))
+}
+
+/// Maps the unique ids of the `SECLIST` to their tickers.
+fn tickers(root: Node) -> HashMap<String, String> {
+    root.descendants()
+        .filter(|n| n.has_tag_name("SECINFO"))
+        .filter_map(|info| {
+            let id = child(info, "SECID").and_then(|s| child_text(s, "UNIQUEID"))?;
+            let ticker = child_text(info, "TICKER")?;
+            Some((id.to_string(), ticker.to_string()))
+        })
+        .collect()
+}
+
+/// The default currency of a statement, which all of its amounts are in.
+fn currency(statement: Node) -> anyhow::Result<Currency> {
+    let text = child_text(statement, "CURDEF")
+        .with_context(|| format!("The statement on line {} has no CURDEF", line(statement)))?;
+    Currency::from_str(text.trim())
+}
+
+fn amount(node: Node, tag: &'static str) -> anyhow::Result<Money> {
+    let text = child_text(node, tag).with_context(|| format!("{tag} is missing"))?;
+    number(text.trim())
+        .parse()
+        .with_context(|| format!("{tag} {text} is not a number"))
+}
+
+/// Normalizes a number to a decimal point.
+///
+/// Some european banks use a decimal comma, and some add thousands separators. If both separators
+/// occur the last one is the decimal separator, and a separator occurring more than once can only
+/// separate the thousands.
+fn number(text: &str) -> String {
+    let decimal = match (text.rfind('.'), text.rfind(',')) {
+        (Some(point), Some(comma)) => Some(point.max(comma)),
+        (Some(point), None) if text.matches('.').count() == 1 => Some(point),
+        (None, Some(comma)) if text.matches(',').count() == 1 => Some(comma),
+        _ => None,
+    };
+    text.char_indices()
+        .filter_map(|(i, c)| match c {
+            '.' | ',' if Some(i) == decimal => Some('.'),
+            '.' | ',' => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// Reads an OFX datetime like `20240115120000.000[-5:EST]`, of which only the date is used.
+fn date(node: Node, tag: &'static str) -> anyhow::Result<NaiveDate> {
+    let text = child_text(node, tag).with_context(|| format!("{tag} is missing"))?;
+    NaiveDate::parse_from_str(text.trim().get(..8).unwrap_or(text), "%Y%m%d")
+        .with_context(|| format!("{tag} {text} is not a valid date"))
+}
+
+/// Turns OFX into well formed XML.
+///
+/// OFX 1.x is SGML where the elements holding a value are not closed. Those elements are closed
+/// here. OFX 2.x already is XML and passes through unchanged apart from the header.
+fn to_xml(content: &str) -> anyhow::Result<String> {
+    let start = content
+        .find("<OFX>")
+        .context("The data seems to not be in OFX format as no <OFX> element was found")?;
+
+    // The line breaks are kept, so the lines of the XML match the lines of the original content.
+    let mut xml = "\n".repeat(content[..start].matches('\n').count());
+    let mut tokens = content[start..].split('<').skip(1).peekable();
+    while let Some(token) = tokens.next() {
+        let mut newlines = token.matches('\n').count();
+        let (tag, text) = token
+            .split_once('>')
+            .with_context(|| format!("<{token} is not a valid tag"))?;
+        let tag = tag.trim();
+        let text = text.trim();
+
+        if tag.starts_with('/') || text.is_empty() {
+            xml.push_str(&format!("<{tag}>"));
+            xml.push_str(&"\n".repeat(newlines));
+            continue;
+        }
+
+        xml.push_str(&format!("<{tag}>{}</{tag}>", escape(text)));
+        // The closing tag of a value is optional in SGML, so skip it if present.
+        if tokens
+            .peek()
+            .and_then(|t| t.split_once('>'))
+            .is_some_and(|(next, _)| next.trim() == format!("/{tag}"))
+        {
+            newlines += tokens.next().unwrap_or_default().matches('\n').count();
+        }
+        xml.push_str(&"\n".repeat(newlines));
+    }
+    Ok(xml)
+}
+
+fn escape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::banks::Parser;
+
+    use super::{number, Ofx};
+
+    const BANK_STATEMENT_SGML: &str = r#"OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+SECURITY:NONE
+ENCODING:USASCII
+CHARSET:1252
+COMPRESSION:NONE
+OLDFILEUID:NONE
+NEWFILEUID:NONE
+
+<OFX>
+<SIGNONMSGSRSV1>
+<SONRS>
+<STATUS><CODE>0<SEVERITY>INFO</STATUS>
+<DTSERVER>20240131120000[0:GMT]
+<LANGUAGE>ENG
+</SONRS>
+</SIGNONMSGSRSV1>
+<BANKMSGSRSV1>
+<STMTTRNRS>
+<TRNUID>1
+<STATUS><CODE>0<SEVERITY>INFO</STATUS>
+<STMTRS>
+<CURDEF>EUR
+<BANKACCTFROM><BANKID>12345678<ACCTID>DE89370400440532013000<ACCTTYPE>CHECKING</BANKACCTFROM>
+<BANKTRANLIST>
+<DTSTART>20240101
+<DTEND>20240131
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20240103120000.000[-5:EST]
+<TRNAMT>-42.50
+<FITID>202401030001
+<NAME>Tom &amp; Jerry's Deli
+<MEMO>Card payment
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20240125
+<TRNAMT>2500.00
+<FITID>202401250001</FITID>
+<NAME>ACME GmbH</NAME>
+<MEMO>Salary January</MEMO>
+</STMTTRN>
+</BANKTRANLIST>
+<LEDGERBAL><BALAMT>3457.50<DTASOF>20240131</LEDGERBAL>
+</STMTRS>
+</STMTTRNRS>
+</BANKMSGSRSV1>
+</OFX>
+"#;
+
+    const INVESTMENT_STATEMENT_XML: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<?OFX OFXHEADER="200" VERSION="220" SECURITY="NONE" OLDFILEUID="NONE" NEWFILEUID="NONE"?>
+<OFX>
+  <INVSTMTMSGSRSV1>
+    <INVSTMTTRNRS>
+      <TRNUID>1</TRNUID>
+      <INVSTMTRS>
+        <DTASOF>20240630</DTASOF>
+        <CURDEF>USD</CURDEF>
+        <INVACCTFROM><BROKERID>broker.example.com</BROKERID><ACCTID>U1234567</ACCTID></INVACCTFROM>
+        <INVTRANLIST>
+          <DTSTART>20240101</DTSTART>
+          <DTEND>20240630</DTEND>
+          <INVBANKTRAN>
+            <STMTTRN>
+              <TRNTYPE>CREDIT</TRNTYPE>
+              <DTPOSTED>20240620</DTPOSTED>
+              <TRNAMT>69980.00</TRNAMT>
+              <FITID>D-1</FITID>
+              <NAME>Deposit</NAME>
+            </STMTTRN>
+            <SUBACCTFUND>CASH</SUBACCTFUND>
+          </INVBANKTRAN>
+          <BUYSTOCK>
+            <INVBUY>
+              <INVTRAN><FITID>T-1</FITID><DTTRADE>20240624</DTTRADE><MEMO>VANGUARD TOT WORLD STK ETF</MEMO></INVTRAN>
+              <SECID><UNIQUEID>922042742</UNIQUEID><UNIQUEIDTYPE>CUSIP</UNIQUEIDTYPE></SECID>
+              <UNITS>100</UNITS>
+              <UNITPRICE>113</UNITPRICE>
+              <COMMISSION>1</COMMISSION>
+              <TOTAL>-11301</TOTAL>
+              <SUBACCTSEC>CASH</SUBACCTSEC>
+              <SUBACCTFUND>CASH</SUBACCTFUND>
+            </INVBUY>
+            <BUYTYPE>BUY</BUYTYPE>
+          </BUYSTOCK>
+          <REINVEST>
+            <INVTRAN><FITID>R-1</FITID><DTTRADE>20240626</DTTRADE><MEMO>VT DIVIDEND REINVESTMENT</MEMO></INVTRAN>
+            <SECID><UNIQUEID>922042742</UNIQUEID><UNIQUEIDTYPE>CUSIP</UNIQUEIDTYPE></SECID>
+            <INCOMETYPE>DIV</INCOMETYPE>
+            <TOTAL>-22.60</TOTAL>
+            <SUBACCTSEC>CASH</SUBACCTSEC>
+            <UNITS>0.2</UNITS>
+            <UNITPRICE>113</UNITPRICE>
+          </REINVEST>
+          <INCOME>
+            <INVTRAN><FITID>I-1</FITID><DTTRADE>20240625</DTTRADE><MEMO>VT CASH DIVIDEND</MEMO></INVTRAN>
+            <SECID><UNIQUEID>922042742</UNIQUEID><UNIQUEIDTYPE>CUSIP</UNIQUEIDTYPE></SECID>
+            <INCOMETYPE>DIV</INCOMETYPE>
+            <TOTAL>57.79</TOTAL>
+            <SUBACCTSEC>CASH</SUBACCTSEC>
+            <SUBACCTFUND>CASH</SUBACCTFUND>
+          </INCOME>
+        </INVTRANLIST>
+      </INVSTMTRS>
+    </INVSTMTTRNRS>
+  </INVSTMTMSGSRSV1>
+  <SECLISTMSGSRSV1>
+    <SECLIST>
+      <STOCKINFO>
+        <SECINFO>
+          <SECID><UNIQUEID>922042742</UNIQUEID><UNIQUEIDTYPE>CUSIP</UNIQUEIDTYPE></SECID>
+          <SECNAME>VANGUARD TOT WORLD STK ETF</SECNAME>
+          <TICKER>VT</TICKER>
+        </SECINFO>
+      </STOCKINFO>
+    </SECLIST>
+  </SECLISTMSGSRSV1>
+</OFX>
+"#;
+
+    #[test]
+    fn parse_bank_statement_sgml() {
+        insta::assert_debug_snapshot!(Ofx::parse("Bank", BANK_STATEMENT_SGML.into()).unwrap());
+    }
+
+    #[test]
+    fn parse_investment_statement_xml() {
+        insta::assert_debug_snapshot!(
+            Ofx::parse("Broker", INVESTMENT_STATEMENT_XML.into()).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_number() {
+        assert_eq!(number("-42.50"), "-42.50");
+        assert_eq!(number("-42,50"), "-42.50");
+        assert_eq!(number("1,234.56"), "1234.56");
+        assert_eq!(number("1.234,56"), "1234.56");
+        assert_eq!(number("1'234.56"), "1'234.56");
+        assert_eq!(number("1,234,567"), "1234567");
+        assert_eq!(number("1.234.567"), "1234567");
+    }
+
+    #[test]
+    #[should_panic(expected = "has no CURDEF")]
+    fn parse_missing_currency() {
+        let content = BANK_STATEMENT_SGML.replace("<CURDEF>EUR\n", "");
+        Ofx::parse("Bank", content).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "no <OFX> element was found")]
+    fn parse_fail() {
+        Ofx::parse("Bank", "Date;Amount;Description".into()).unwrap();
+    }
+}
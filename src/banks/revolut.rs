@@ -7,33 +7,43 @@ use chrono::{
 use csv::ReaderBuilder;
 use serde::{de::Visitor, Deserializer};
 
-use crate::fx::Currency;
+use crate::{fx::Currency, money::Money, realms::portfolio::state::Action};
 
-use super::{Ledger, LedgerKind, LedgerRecord, ParsedAccount, Parser};
+use super::{csv_rows, Diagnostic, Ledger, LedgerKind, LedgerRecord, ParsedAccount, Parser};
 
 pub struct Revolut {}
 
 impl Parser for Revolut {
     fn parse(name: &str, content: String) -> anyhow::Result<ParsedAccount> {
         let mut currency = Currency::CHF;
-        let records = ReaderBuilder::new()
+        let mut diagnostics = vec![];
+        let reader = ReaderBuilder::new()
             .delimiter(b',')
-            .from_reader(Cursor::new(&content))
-            .deserialize::<Record>()
-            .filter(|v| (v.as_ref()).map_or("", |v| &v.state) == "COMPLETED")
-            .filter_map(|v| {
-                v.map(|v| {
-                    currency = Currency::from_str(&v.currency).unwrap();
-                    v.date.map(|date| LedgerRecord {
-                        date,
-                        amount: v.amount,
-                        description: v.description,
-                        category: v.category,
-                    })
-                })
-                .transpose()
-            })
-            .collect::<Result<_, _>>()?;
+            .from_reader(Cursor::new(&content));
+        let mut records = vec![];
+        for (line, v) in csv_rows::<Record, _>(reader, &mut diagnostics)? {
+            if v.state != "COMPLETED" {
+                continue;
+            }
+            currency = match Currency::from_str(&v.currency) {
+                Ok(currency) => currency,
+                Err(error) => {
+                    diagnostics.push(Diagnostic::warning(Some(line), Some("Currency"), error));
+                    continue;
+                }
+            };
+            if let Some(date) = v.date {
+                records.push(LedgerRecord {
+                    id: None,
+                    date,
+                    amount: v.amount,
+                    description: v.description,
+                    category: v.category,
+                    action: Action::Update,
+                    price: None,
+                });
+            }
+        }
 
         Ok(ParsedAccount {
             ledgers: vec![Ledger {
@@ -41,9 +51,31 @@ impl Parser for Revolut {
                 records,
                 symbol: crate::fx::Symbol::Currency(currency),
                 kind: LedgerKind::Bank,
+                statements: vec![],
+                quote_currency: None,
+                splits: vec![],
             }],
+            diagnostics,
         })
     }
+
+    fn detect(content: &str) -> f64 {
+        super::header_score(
+            content,
+            b',',
+            &[
+                "Type",
+                "Product",
+                "Started Date",
+                "Completed Date",
+                "Description",
+                "Amount",
+                "Fee",
+                "Currency",
+                "State",
+            ],
+        )
+    }
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -51,7 +83,7 @@ struct Record {
     #[serde(rename = "Completed Date", deserialize_with = "parse_date_with_time")]
     date: Option<NaiveDate>,
     #[serde(rename = "Amount")]
-    amount: f64,
+    amount: Money,
     #[serde(rename = "Description")]
     description: String,
     #[serde(rename = "Type")]
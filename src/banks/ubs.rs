@@ -3,38 +3,65 @@ use std::{io::Cursor, str::FromStr};
 use chrono::{NaiveDate, NaiveTime};
 use csv::ReaderBuilder;
 
-use crate::fx::{Currency, Symbol};
+use crate::{
+    fx::{Currency, Symbol},
+    realms::portfolio::state::Action,
+};
 
-use super::{Ledger, LedgerKind, LedgerRecord, ParsedAccount, Parser};
+use super::{csv_rows, Diagnostic, Ledger, LedgerKind, LedgerRecord, ParsedAccount, Parser};
 
 pub struct Ubs {}
 
 impl Parser for Ubs {
     fn parse(name: &str, content: String) -> anyhow::Result<ParsedAccount> {
         let mut currency = Currency::CHF;
-        let records = ReaderBuilder::new()
+        let mut diagnostics = vec![];
+        let reader = ReaderBuilder::new()
             .delimiter(b';')
-            .from_reader(Cursor::new(&content))
-            .deserialize::<Record>()
-            .map(|v| {
-                v.map(|v| {
-                    currency = Currency::from_str(&v.currency).unwrap();
-                    v.booking_date.map(|date| LedgerRecord {
-                        date,
-                        amount: v
-                            .debit
-                            .or(v.credit)
-                            .unwrap()
-                            .replace("'", "")
-                            .parse()
-                            .unwrap(),
-                        description: v.description_1.unwrap_or_default(),
-                        category: "".to_string(),
-                    })
-                })
-            })
-            .filter_map(|v| v.transpose())
-            .collect::<Result<_, _>>()?;
+            .from_reader(Cursor::new(&content));
+        let mut records = vec![];
+        for (line, v) in csv_rows::<Record, _>(reader, &mut diagnostics)? {
+            // Rows without a booking date are pending or summaries.
+            let Some(date) = v.booking_date else {
+                continue;
+            };
+            currency = match Currency::from_str(&v.currency) {
+                Ok(currency) => currency,
+                Err(error) => {
+                    diagnostics.push(Diagnostic::warning(Some(line), Some("Currency"), error));
+                    continue;
+                }
+            };
+            let (column, amount) = match (v.debit, v.credit) {
+                (Some(debit), _) => ("Debit", debit),
+                (None, Some(credit)) => ("Credit", credit),
+                (None, None) => {
+                    diagnostics.push(Diagnostic::warning(
+                        Some(line),
+                        Some("Debit"),
+                        "Neither a debit nor a credit amount is given",
+                    ));
+                    continue;
+                }
+            };
+            let Ok(amount) = amount.replace('\'', "").parse() else {
+                diagnostics.push(Diagnostic::warning(
+                    Some(line),
+                    Some(column),
+                    format!("{amount} is not a number"),
+                ));
+                continue;
+            };
+            records.push(LedgerRecord {
+                id: v.transaction_no,
+                date,
+                amount,
+                description: v.description_1.unwrap_or_default(),
+                category: "".to_string(),
+                action: Action::Update,
+                price: None,
+            });
+        }
 
         Ok(ParsedAccount {
             ledgers: vec![Ledger {
@@ -42,9 +69,30 @@ impl Parser for Ubs {
                 records,
                 symbol: Symbol::Currency(currency),
                 kind: LedgerKind::Bank,
+                statements: vec![],
+                quote_currency: None,
+                splits: vec![],
             }],
+            diagnostics,
         })
     }
+
+    fn detect(content: &str) -> f64 {
+        super::header_score(
+            content,
+            b';',
+            &[
+                "Trade date",
+                "Booking date",
+                "Value date",
+                "Currency",
+                "Debit",
+                "Credit",
+                "Transaction no.",
+                "Description1",
+            ],
+        )
+    }
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -68,7 +116,7 @@ struct Record {
     #[serde(rename = "Balance")]
     _balance: Option<f64>,
     #[serde(rename = "Transaction no.")]
-    _transaction_no: Option<String>,
+    transaction_no: Option<String>,
     #[serde(rename = "Description1")]
     description_1: Option<String>,
     #[serde(rename = "Description2")]
@@ -100,6 +148,26 @@ mod tests {
         insta::assert_debug_snapshot!(super::Ubs::parse("UBS", TRANSACTIONS.into()).unwrap());
     }
 
+    #[test]
+    fn parse_malformed_rows() {
+        let content = r#"Trade date;Trade time;Booking date;Value date;Currency;Debit;Credit;Individual amount;Balance;Transaction no.;Description1;Description2;Description3;Footnotes;
+2021-01-29;;2021-01-29;2021-01-31;CHF;-10.00;;;22113.15;BL01529HJ0125142;"Balance closing of service prices";;;;
+2021-01-27;;2021-01-28;2021-01-28;XXX;-503.50;;;22123.15;9906527KH9626550;"UBS Card Center";;;;
+2021-01-27;;2021-01-27;2021-01-27;CHF;-6x.75;;;22626.65;9906026TO5927425;"CAISSE DES MEDECINS";;;;
+2021-01-22;;2021-01-2x;2021-01-22;CHF;;9.59;;22689.40;9999022ZC8003633;"STRIPE PAYMENTS UK LTD";;;;
+"#;
+        let parsed = crate::banks::parse(
+            "UBS",
+            "UBS.csv",
+            content.into(),
+            crate::cli::BankFormat::Ubs,
+            None,
+        )
+        .unwrap();
+        assert_eq!(parsed.ledgers[0].records.len(), 1);
+        insta::assert_debug_snapshot!(parsed.diagnostics);
+    }
+
     #[tokio::test]
     async fn test_ubs_private_balance_api_test_data() {
         use crate::banks::test_utils::test_account_balance_api;
@@ -7,41 +7,48 @@ use chrono::{
 use csv::ReaderBuilder;
 use serde::{de::Visitor, Deserializer};
 
-use crate::fx::Currency;
+use crate::{fx::Currency, money::Money, realms::portfolio::state::Action};
 
-use super::{Ledger, LedgerKind, LedgerRecord, ParsedAccount, Parser};
+use super::{csv_rows, Diagnostic, Ledger, LedgerKind, LedgerRecord, ParsedAccount, Parser};
 
 pub struct Wise {}
 
 impl Parser for Wise {
     fn parse(name: &str, content: String) -> anyhow::Result<ParsedAccount> {
         let mut currency = Currency::CHF;
-        let records = ReaderBuilder::new()
+        let mut diagnostics = vec![];
+        let reader = ReaderBuilder::new()
             .delimiter(b',')
-            .from_reader(Cursor::new(&content))
-            .deserialize::<Record>()
-            .filter_map(|v| {
-                v.map(|v| {
-                    // Set currency from the record
-                    currency = Currency::from_str(&v.currency).unwrap_or(Currency::CHF);
-
-                    let description = v.description
-                        .or_else(|| v.payer_name.clone())
-                        .or_else(|| v.payee_name.clone())
-                        .unwrap_or_else(|| "no description".into());
-
-                    v.date.map(|date| {
-                        LedgerRecord {
-                            date,
-                            amount: v.amount,
-                            description,
-                            category: "WISE".into(),
-                        }
-                    })
-                })
-                .transpose()
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+            .from_reader(Cursor::new(&content));
+        let mut records = vec![];
+        for (line, v) in csv_rows::<Record, _>(reader, &mut diagnostics)? {
+            currency = match Currency::from_str(&v.currency) {
+                Ok(currency) => currency,
+                Err(error) => {
+                    diagnostics.push(Diagnostic::warning(Some(line), Some("Currency"), error));
+                    continue;
+                }
+            };
+
+            let description = v
+                .description
+                .or_else(|| v.payer_name.clone())
+                .or_else(|| v.payee_name.clone())
+                .unwrap_or_else(|| "no description".into());
+
+            if let Some(date) = v.date {
+                records.push(LedgerRecord {
+                    // Wise uses the same id for both sides of a conversion.
+                    id: v.id.map(|id| format!("{id}:{}", v.currency)),
+                    date,
+                    amount: v.amount,
+                    description,
+                    category: "WISE".into(),
+                    action: Action::Update,
+                    price: None,
+                });
+            }
+        }
 
         Ok(ParsedAccount {
             ledgers: vec![Ledger {
@@ -49,17 +56,40 @@ impl Parser for Wise {
                 records,
                 symbol: crate::fx::Symbol::Currency(currency),
                 kind: LedgerKind::Bank,
+                statements: vec![],
+                quote_currency: None,
+                splits: vec![],
             }],
+            diagnostics,
         })
     }
+
+    fn detect(content: &str) -> f64 {
+        super::header_score(
+            content,
+            b',',
+            &[
+                "TransferWise ID",
+                "Date Time",
+                "Amount",
+                "Currency",
+                "Description",
+                "Running Balance",
+                "Payer Name",
+                "Payee Name",
+            ],
+        )
+    }
 }
 
 #[derive(Debug, serde::Deserialize)]
 struct Record {
+    #[serde(rename = "TransferWise ID")]
+    id: Option<String>,
     #[serde(rename = "Date Time", deserialize_with = "parse_date_with_time")]
     date: Option<NaiveDate>,
     #[serde(rename = "Amount")]
-    amount: f64,
+    amount: Money,
     #[serde(rename = "Currency")]
     currency: String,
     #[serde(rename = "Description")]
@@ -136,7 +166,10 @@ fn parse_date_with_time<'de, D: Deserializer<'de>>(de: D) -> Result<Option<Naive
 
 #[cfg(test)]
 pub mod tests {
-    use crate::banks::Parser;
+    use crate::{
+        banks::Parser,
+        fx::{Currency, Symbol},
+    };
 
     use super::Wise;
 
@@ -151,6 +184,23 @@ TRANSFER-1364555736,06-01-2025,"06-01-2025 12:36:08.427",104.54,CHF,"Received mo
         insta::assert_debug_snapshot!(Wise::parse("Wise", TRANSACTIONS.to_string()).unwrap());
     }
 
+    #[test]
+    fn parse_other_currencies() {
+        const TRANSACTIONS: &str = r#""TransferWise ID",Date,"Date Time",Amount,Currency,Description
+TRANSFER-1,29-07-2025,"29-07-2025 09:04:55.522",1200.50,SEK,"Received money"
+TRANSFER-2,28-07-2025,"28-07-2025 09:05:09.270",96.45,ABC,"Received money"
+"#;
+        let parsed = Wise::parse("Wise", TRANSACTIONS.to_string()).unwrap();
+        assert_eq!(
+            parsed.ledgers[0].symbol,
+            Symbol::Currency(Currency::SEK)
+        );
+        assert_eq!(parsed.ledgers[0].records.len(), 1);
+        assert_eq!(parsed.diagnostics.len(), 1);
+        assert_eq!(parsed.diagnostics[0].line, Some(3));
+        assert_eq!(parsed.diagnostics[0].column.as_deref(), Some("Currency"));
+    }
+
     #[test]
     pub fn test_wise_balance_calculation() {
         test_wise_balance_with_target(1036.74);
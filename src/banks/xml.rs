@@ -0,0 +1,23 @@
+//! Small helpers to navigate the XML based formats by local tag name.
+
+use roxmltree::Node;
+
+pub fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(move |n| n.has_tag_name(name))
+}
+
+pub fn child<'a, 'input>(node: Node<'a, 'input>, name: &'static str) -> Option<Node<'a, 'input>> {
+    children(node, name).next()
+}
+
+pub fn child_text<'a>(node: Node<'a, '_>, name: &'static str) -> Option<&'a str> {
+    child(node, name).and_then(|n| n.text())
+}
+
+/// The one based line the node starts at.
+pub fn line(node: Node) -> usize {
+    node.document().text_pos_at(node.range().start).row as usize
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand, ValueEnum};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
@@ -14,12 +16,24 @@ pub struct Args {
 pub enum Command {
     #[command(about = "Serve the frontend")]
     Serve(Serve),
+    #[command(about = "Import the portfolio files into a SQLite database")]
+    Migrate(Migrate),
 }
 
 #[derive(Parser, Debug)]
 pub struct Serve {}
 
-#[derive(ValueEnum, Clone, Debug, Serialize, Deserialize, TS, Copy)]
+#[derive(Parser, Debug)]
+pub struct Migrate {
+    /// The directory with the `ledgers/<owner>` trees.
+    #[arg(long, default_value = "portfolio")]
+    pub from: PathBuf,
+    /// The database to import into, which is created if it does not exist.
+    #[arg(long, default_value = "portfolio/portfolio.db")]
+    pub database: PathBuf,
+}
+
+#[derive(ValueEnum, Clone, Debug, Serialize, Deserialize, TS, Copy, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 #[ts(export)]
 pub enum BankFormat {
@@ -28,4 +42,8 @@ pub enum BankFormat {
     Revolut,
     Ibkr,
     Wise,
+    Camt,
+    Ofx,
+    /// A CSV export described by the `custom_format` of the account.
+    Custom,
 }
@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
@@ -7,9 +9,24 @@ use itertools::Itertools;
 #[derive(Debug)]
 pub struct AppError(anyhow::Error);
 
+/// An invalid request, which is answered with 400 Bad Request instead of 500.
+#[derive(Debug)]
+pub struct BadRequest(pub String);
+
+impl Display for BadRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BadRequest {}
+
 // Tell axum how to convert `AppError` into a response.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        if let Some(BadRequest(message)) = self.0.downcast_ref::<BadRequest>() {
+            return (StatusCode::BAD_REQUEST, message.clone()).into_response();
+        }
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("Something went wrong:\n{}", self.0.chain().join("\n")),
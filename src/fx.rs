@@ -1,25 +1,228 @@
+pub mod provider;
+
 use std::{
     collections::{BTreeMap, HashMap},
     fmt::Display,
+    path::{Path, PathBuf},
     str::FromStr,
+    sync::Arc,
 };
 
 use anyhow::{Context, Result};
-use chrono::{Datelike, Days, NaiveDate, NaiveTime, Utc};
-use reqwest::StatusCode;
+use chrono::{Datelike, Days, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, TS)]
-#[ts(export)]
-pub enum Currency {
+use self::provider::Providers;
+
+/// Declares the currencies with their number of minor units and their symbol.
+macro_rules! currencies {
+    ($($(#[$attr:meta])* $code:ident => ($minor_units:literal, $symbol:literal),)*) => {
+        /// A currency of ISO 4217, without the precious metals and the codes for testing.
+        ///
+        /// Serialized as its three letter code.
+        #[allow(clippy::upper_case_acronyms)]
+        #[derive(
+            Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, TS,
+        )]
+        #[ts(export)]
+        pub enum Currency {
+            $($(#[$attr])* $code,)*
+        }
+
+        impl Currency {
+            /// The three letter code, e.g. `CHF`.
+            pub fn code(self) -> &'static str {
+                match self {
+                    $(Currency::$code => stringify!($code),)*
+                }
+            }
+
+            /// The number of digits after the decimal point, e.g. 2 for cents.
+            pub fn minor_units(self) -> u32 {
+                match self {
+                    $(Currency::$code => $minor_units,)*
+                }
+            }
+
+            /// The symbol used in the currency's country, which may be ambiguous, e.g. `$`.
+            pub fn symbol(self) -> &'static str {
+                match self {
+                    $(Currency::$code => $symbol,)*
+                }
+            }
+        }
+    };
+}
+
+currencies! {
+    AED => (2, "د.إ"),
+    AFN => (2, "؋"),
+    ALL => (2, "L"),
+    AMD => (2, "֏"),
+    ANG => (2, "ƒ"),
+    AOA => (2, "Kz"),
+    ARS => (2, "$"),
+    AUD => (2, "A$"),
+    AWG => (2, "ƒ"),
+    AZN => (2, "₼"),
+    BAM => (2, "KM"),
+    BBD => (2, "$"),
+    BDT => (2, "৳"),
+    BGN => (2, "лв"),
+    BHD => (3, "BD"),
+    BIF => (0, "FBu"),
+    BMD => (2, "$"),
+    BND => (2, "$"),
+    BOB => (2, "Bs"),
+    BOV => (2, "BOV"),
+    BRL => (2, "R$"),
+    BSD => (2, "$"),
+    BTN => (2, "Nu."),
+    BWP => (2, "P"),
+    BYN => (2, "Br"),
+    BZD => (2, "$"),
+    CAD => (2, "CA$"),
+    CDF => (2, "FC"),
+    CHE => (2, "CHE"),
     #[default]
-    CHF,
-    USD,
-    EUR,
-    JPY,
-    GBP,
-    PLN,
+    CHF => (2, "CHF"),
+    CHW => (2, "CHW"),
+    CLF => (4, "UF"),
+    CLP => (0, "$"),
+    CNY => (2, "CN¥"),
+    COP => (2, "$"),
+    COU => (2, "COU"),
+    CRC => (2, "₡"),
+    CUP => (2, "$"),
+    CVE => (2, "$"),
+    CZK => (2, "Kč"),
+    DJF => (0, "Fdj"),
+    DKK => (2, "kr"),
+    DOP => (2, "$"),
+    DZD => (2, "DA"),
+    EGP => (2, "E£"),
+    ERN => (2, "Nfk"),
+    ETB => (2, "Br"),
+    EUR => (2, "€"),
+    FJD => (2, "$"),
+    FKP => (2, "£"),
+    GBP => (2, "£"),
+    GEL => (2, "₾"),
+    GHS => (2, "₵"),
+    GIP => (2, "£"),
+    GMD => (2, "D"),
+    GNF => (0, "FG"),
+    GTQ => (2, "Q"),
+    GYD => (2, "$"),
+    HKD => (2, "HK$"),
+    HNL => (2, "L"),
+    HTG => (2, "G"),
+    HUF => (2, "Ft"),
+    IDR => (2, "Rp"),
+    ILS => (2, "₪"),
+    INR => (2, "₹"),
+    IQD => (3, "ID"),
+    IRR => (2, "﷼"),
+    ISK => (0, "kr"),
+    JMD => (2, "$"),
+    JOD => (3, "JD"),
+    JPY => (0, "¥"),
+    KES => (2, "KSh"),
+    KGS => (2, "сом"),
+    KHR => (2, "៛"),
+    KMF => (0, "CF"),
+    KPW => (2, "₩"),
+    KRW => (0, "₩"),
+    KWD => (3, "KD"),
+    KYD => (2, "$"),
+    KZT => (2, "₸"),
+    LAK => (2, "₭"),
+    LBP => (2, "LL"),
+    LKR => (2, "Rs"),
+    LRD => (2, "$"),
+    LSL => (2, "L"),
+    LYD => (3, "LD"),
+    MAD => (2, "DH"),
+    MDL => (2, "L"),
+    MGA => (2, "Ar"),
+    MKD => (2, "ден"),
+    MMK => (2, "K"),
+    MNT => (2, "₮"),
+    MOP => (2, "MOP$"),
+    MRU => (2, "UM"),
+    MUR => (2, "₨"),
+    MVR => (2, "Rf"),
+    MWK => (2, "MK"),
+    MXN => (2, "MX$"),
+    MXV => (2, "MXV"),
+    MYR => (2, "RM"),
+    MZN => (2, "MT"),
+    NAD => (2, "$"),
+    NGN => (2, "₦"),
+    NIO => (2, "C$"),
+    NOK => (2, "kr"),
+    NPR => (2, "₨"),
+    NZD => (2, "NZ$"),
+    OMR => (3, "RO"),
+    PAB => (2, "B/."),
+    PEN => (2, "S/"),
+    PGK => (2, "K"),
+    PHP => (2, "₱"),
+    PKR => (2, "₨"),
+    PLN => (2, "zł"),
+    PYG => (0, "₲"),
+    QAR => (2, "QR"),
+    RON => (2, "lei"),
+    RSD => (2, "дин."),
+    RUB => (2, "₽"),
+    RWF => (0, "FRw"),
+    SAR => (2, "SR"),
+    SBD => (2, "$"),
+    SCR => (2, "₨"),
+    SDG => (2, "LS"),
+    SEK => (2, "kr"),
+    SGD => (2, "S$"),
+    SHP => (2, "£"),
+    SLE => (2, "Le"),
+    SOS => (2, "Sh"),
+    SRD => (2, "$"),
+    SSP => (2, "£"),
+    STN => (2, "Db"),
+    SVC => (2, "₡"),
+    SYP => (2, "£"),
+    SZL => (2, "L"),
+    THB => (2, "฿"),
+    TJS => (2, "SM"),
+    TMT => (2, "m"),
+    TND => (3, "DT"),
+    TOP => (2, "T$"),
+    TRY => (2, "₺"),
+    TTD => (2, "$"),
+    TWD => (2, "NT$"),
+    TZS => (2, "TSh"),
+    UAH => (2, "₴"),
+    UGX => (0, "USh"),
+    USD => (2, "$"),
+    USN => (2, "USN"),
+    UYI => (0, "UYI"),
+    UYU => (2, "$"),
+    UYW => (4, "UYW"),
+    UZS => (2, "soʻm"),
+    VED => (2, "Bs.D"),
+    VES => (2, "Bs."),
+    VND => (0, "₫"),
+    VUV => (0, "VT"),
+    WST => (2, "T"),
+    XAF => (0, "FCFA"),
+    XCD => (2, "$"),
+    XCG => (2, "Cg"),
+    XOF => (0, "CFA"),
+    XPF => (0, "₣"),
+    YER => (2, "﷼"),
+    ZAR => (2, "R"),
+    ZMW => (2, "K"),
+    ZWG => (2, "ZiG"),
 }
 
 impl FromStr for Currency {
@@ -33,17 +236,15 @@ impl FromStr for Currency {
 
 impl Display for Currency {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Currency::CHF => f.write_str("CHF"),
-            Currency::USD => f.write_str("USD"),
-            Currency::EUR => f.write_str("EUR"),
-            Currency::JPY => f.write_str("JPY"),
-            Currency::GBP => f.write_str("GBP"),
-            Currency::PLN => f.write_str("PLN"),
-        }
+        f.write_str(self.code())
     }
 }
 
+/// A currency or a stock ticker.
+///
+/// It is serialized as a plain string. Tickers which are also currency codes, e.g. `ALL`, are read
+/// back as currencies, so parsers which know that they have a stock create it as
+/// [`Symbol::Stock`], and stored data uses the explicit representation of [`tagged`].
 #[allow(clippy::derived_hash_with_manual_eq)]
 #[derive(Debug, Clone, Eq, Hash, Serialize, Deserialize, TS)]
 #[ts(export)]
@@ -96,20 +297,69 @@ impl<S: AsRef<str>> From<S> for Symbol {
     }
 }
 
+/// (De)serializes a [`Symbol`] with its variant, e.g. `{"Stock": "ALL"}`, so a ticker is never
+/// read back as a currency. Plain strings of older files are read as well.
+pub mod tagged {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Currency, Symbol};
+
+    #[derive(Serialize, Deserialize)]
+    enum Tagged {
+        Currency(Currency),
+        Stock(String),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Tagged(Tagged),
+        Plain(Symbol),
+    }
+
+    pub fn serialize<S: Serializer>(symbol: &Symbol, serializer: S) -> Result<S::Ok, S::Error> {
+        match symbol {
+            Symbol::Currency(currency) => Tagged::Currency(*currency),
+            Symbol::Stock(ticker) => Tagged::Stock(ticker.clone()),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Symbol, D::Error> {
+        Ok(match Stored::deserialize(deserializer)? {
+            Stored::Tagged(Tagged::Currency(currency)) => Symbol::Currency(currency),
+            Stored::Tagged(Tagged::Stock(ticker)) => Symbol::Stock(ticker),
+            Stored::Plain(symbol) => symbol,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct HistoryCache {
     pub fx: HashMap<(Symbol, Symbol), Pair>,
+    providers: Arc<Providers>,
+    /// Where the pairs are stored, if anywhere.
+    directory: Option<PathBuf>,
 }
 
 impl HistoryCache {
     pub fn new() -> Self {
-        Self { fx: HashMap::new() }
+        Self::with_providers(Providers::default())
+    }
+
+    pub fn with_providers(providers: Providers) -> Self {
+        Self {
+            fx: HashMap::new(),
+            providers: Arc::new(providers),
+            directory: None,
+        }
     }
 
     pub fn load() -> Result<Self> {
         let mut fx = HashMap::new();
-        std::fs::create_dir_all("portfolio/fx")?;
-        for file in std::fs::read_dir("portfolio/fx")? {
+        let directory = PathBuf::from("portfolio/fx");
+        std::fs::create_dir_all(&directory)?;
+        for file in std::fs::read_dir(&directory)? {
             let file = file?;
             let path = file.path();
             let file = std::fs::File::open(&path)?;
@@ -117,89 +367,256 @@ impl HistoryCache {
                 .with_context(|| format!("{} could not be read", path.display()))?;
             fx.insert((pair.from.clone(), pair.to.clone()), pair);
         }
-        Ok(Self { fx })
+        let providers = Providers::load(Path::new("portfolio/providers.yaml"))?;
+        Ok(Self {
+            fx,
+            providers: Arc::new(providers),
+            directory: Some(directory),
+        })
     }
 
-    pub async fn get<'a: 'b, 'b>(&'a mut self, from: &Symbol, to: &Symbol) -> Result<&'b Pair> {
-        let start = NaiveDate::from_ymd_opt(2016, 1, 1).unwrap();
+    /// A copy of the cached pairs which is not stored anywhere, to fetch rates on without
+    /// blocking the cache. The fetched rates are taken over with [`HistoryCache::merge`].
+    pub fn snapshot(&self) -> Self {
+        Self {
+            fx: self.fx.clone(),
+            providers: self.providers.clone(),
+            directory: None,
+        }
+    }
+
+    /// Takes over the pairs of a snapshot which cover at least the days of the cached ones and
+    /// stores them.
+    pub fn merge(&mut self, snapshot: Self) -> Result<()> {
+        for (key, pair) in snapshot.fx {
+            let covers = self.fx.get(&key).is_none_or(|cached| {
+                pair.since() <= cached.since() && pair.last() >= cached.last()
+            });
+            if covers {
+                self.fx.insert(key, pair);
+            }
+        }
+        self.save()
+    }
+
+    /// Gets the rates of a pair from `since` until the last weekday.
+    ///
+    /// Currency pairs which are not cached are derived from the opposite pair or crossed through
+    /// another currency if possible, and fetched if none of these works. Only the days which are
+    /// not cached yet are fetched. If that fails, the cached rates are returned and marked as stale.
+    pub async fn get<'a: 'b, 'b>(
+        &'a mut self,
+        from: &Symbol,
+        to: &Symbol,
+        since: NaiveDate,
+    ) -> Result<&'b Pair> {
         let mut end = Utc::now().naive_local().date();
         end = match end.weekday() {
             chrono::Weekday::Sat => end.checked_sub_days(Days::new(1)).unwrap(),
             chrono::Weekday::Sun => end.checked_sub_days(Days::new(2)).unwrap(),
             _ => end,
         };
+        self.refresh(from, to, since, end).await?;
+        self.save()?;
+        Ok(self.fx.get(&(from.clone(), to.clone())).unwrap())
+    }
 
-        if self.fx.contains_key(&(from.clone(), to.clone())) {
-            let rate = self.fx.get(&(from.clone(), to.clone())).unwrap();
-            let x = rate.rates.last_key_value().map_or(NaiveDate::MIN, |r| *r.0);
-            if end <= (x) {
-                Ok(self.fx.get(&(from.clone(), to.clone())).unwrap())
-            } else {
-                self.update_rates(start, end, from.clone(), to.clone())
-                    .await?;
-                Ok(self.fx.get(&(from.clone(), to.clone())).unwrap())
+    async fn refresh(
+        &mut self,
+        from: &Symbol,
+        to: &Symbol,
+        since: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<()> {
+        let fetched = self
+            .fx
+            .get(&(from.clone(), to.clone()))
+            .is_some_and(|p| p.derivation == Derivation::Fetched);
+        if !fetched {
+            for via in self.paths(from, to) {
+                if self.derive(from, to, via, since, end).await {
+                    return Ok(());
+                }
             }
-        } else {
-            self.update_rates(start, end, from.clone(), to.clone())
-                .await?;
-            Ok(self.fx.get(&(from.clone(), to.clone())).unwrap())
         }
+        // Without a way to derive it, the pair is fetched itself.
+        self.update(from, to, since, end).await
+    }
+
+    /// Finds the ways to derive the rates of a currency pair from the fetched pairs, the most
+    /// likely to succeed first.
+    fn paths(&self, from: &Symbol, to: &Symbol) -> Vec<DerivedVia> {
+        let (Symbol::Currency(from), Symbol::Currency(to)) = (from, to) else {
+            return vec![];
+        };
+        let fetched = |from: &Currency, to: &Currency| {
+            self.fx
+                .get(&(Symbol::Currency(*from), Symbol::Currency(*to)))
+                .is_some_and(|p| p.derivation == Derivation::Fetched)
+        };
+        let leg = |from: &Currency, to: &Currency| fetched(from, to) || fetched(to, from);
+
+        if fetched(to, from) {
+            return vec![DerivedVia::Inverse];
+        }
+        // The euro and the dollar are the most likely to be quoted against everything else.
+        let mut pivots = self
+            .fx
+            .keys()
+            .filter_map(|(from, _)| match from {
+                Symbol::Currency(c) => Some(*c),
+                Symbol::Stock(_) => None,
+            })
+            .collect::<Vec<_>>();
+        pivots.sort_by_key(|c| (*c != Currency::EUR, *c != Currency::USD, c.to_string()));
+        pivots.dedup();
+        pivots
+            .into_iter()
+            .filter(|via| via != from && via != to)
+            .filter(|via| leg(from, via) && leg(via, to))
+            .map(DerivedVia::Cross)
+            .collect()
     }
 
-    async fn update_rates(
+    /// Derives the rates of a pair `via` the fetched pairs. Returns false if a leg could not be
+    /// updated or they have no rates in common, so that the next way can be tried.
+    async fn derive(
         &mut self,
-        start: NaiveDate,
+        from: &Symbol,
+        to: &Symbol,
+        via: DerivedVia,
+        since: NaiveDate,
         end: NaiveDate,
-        from: Symbol,
-        to: Symbol,
-    ) -> Result<(), anyhow::Error> {
-        let rate = fetch_rates(start, end, from.clone(), to.clone()).await?;
-        let quotes = rate
-            .chart
-            .result
-            .first()
-            .unwrap()
-            .indicators
-            .quote
-            .first()
-            .unwrap()
-            .close
-            .iter()
-            .copied();
-        let dates = rate
-            .chart
-            .result
-            .first()
-            .unwrap()
-            .timestamp
+    ) -> bool {
+        let derived = match via {
+            DerivedVia::Inverse => self.leg(from, to, since, end).await,
+            DerivedVia::Cross(pivot) => {
+                let pivot = Symbol::Currency(pivot);
+                match self.leg(from, &pivot, since, end).await {
+                    Ok(first) => {
+                        let second = self.leg(&pivot, to, since, end).await;
+                        second.map(|second| cross(first, second))
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+        };
+        let (rates, error) = match derived {
+            Ok((rates, error)) if !rates.is_empty() => (rates, error),
+            _ => return false,
+        };
+        self.fx.insert(
+            (from.clone(), to.clone()),
+            Pair {
+                from: from.clone(),
+                to: to.clone(),
+                since: Some(since),
+                dirty: false,
+                stale: error.is_some(),
+                error,
+                derivation: via.into(),
+                rates,
+            },
+        );
+        true
+    }
+
+    /// Updates the fetched pair or its opposite and gets its rates from `from` to `to`, along with
+    /// the error if they could not be updated.
+    async fn leg(
+        &mut self,
+        from: &Symbol,
+        to: &Symbol,
+        since: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<(BTreeMap<NaiveDate, f64>, Option<String>)> {
+        let key = (from.clone(), to.clone());
+        if self
+            .fx
+            .get(&key)
+            .is_some_and(|p| p.derivation == Derivation::Fetched)
+        {
+            self.update(from, to, since, end).await?;
+            let pair = &self.fx[&key];
+            return Ok((pair.rates.clone(), pair.error.clone()));
+        }
+        self.update(to, from, since, end).await?;
+        let pair = &self.fx[&(to.clone(), from.clone())];
+        let rates = pair
+            .rates
             .iter()
-            .copied()
-            .map(|v| {
-                NaiveDate::default()
-                    .checked_add_days(Days::new(v / 3600 / 24))
-                    .unwrap()
-            });
-        let pair = Pair {
-            from: from.clone(),
-            to: to.clone(),
-            dirty: true,
-            rates: dates
-                .zip(quotes)
-                .filter_map(|(d, q)| q.map(|q| (d, q)))
-                .collect(),
+            .map(|(date, rate)| (*date, 1.0 / rate))
+            .collect();
+        Ok((rates, pair.error.clone()))
+    }
+
+    async fn update(
+        &mut self,
+        from: &Symbol,
+        to: &Symbol,
+        since: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<()> {
+        let key = (from.clone(), to.clone());
+        let ranges = match self.fx.get(&key) {
+            Some(pair) => {
+                let mut ranges = vec![];
+                if let Some(first) = pair.since().filter(|first| since < *first) {
+                    ranges.push((since, first));
+                }
+                if let Some(last) = pair.last().filter(|last| *last < end) {
+                    // The last day is fetched again, as its rate might not have been final.
+                    ranges.push((last, end));
+                }
+                ranges
+            }
+            None => vec![(since, end)],
         };
-        self.fx.insert((from, to), pair);
-        self.save()?;
+
+        // The pair is only up to date if none of the ranges failed.
+        let mut error = None;
+        for (start, end) in ranges {
+            let rates = self.providers.rates(from, to, start, end).await;
+            let pair = match (rates, self.fx.get_mut(&key)) {
+                (Ok(rates), Some(pair)) => {
+                    pair.rates.extend(rates);
+                    pair
+                }
+                (Ok(rates), None) => self.fx.entry(key.clone()).or_insert(Pair {
+                    from: from.clone(),
+                    to: to.clone(),
+                    since: None,
+                    dirty: false,
+                    stale: false,
+                    error: None,
+                    derivation: Derivation::Fetched,
+                    rates,
+                }),
+                (Err(e), Some(pair)) if !pair.rates.is_empty() => {
+                    error = Some(format!("{e:#}"));
+                    continue;
+                }
+                (Err(e), _) => return Err(e),
+            };
+            pair.since = pair.since().map(|first| first.min(start)).or(Some(start));
+            pair.dirty = true;
+        }
+        if let Some(pair) = self.fx.get_mut(&key) {
+            pair.stale = error.is_some();
+            pair.error = error;
+        }
         Ok(())
     }
 
     pub fn save(&mut self) -> Result<()> {
+        let Some(directory) = &self.directory else {
+            return Ok(());
+        };
         for entry in self.fx.values_mut() {
             if entry.dirty {
-                let mut file = std::fs::File::create(format!(
-                    "portfolio/fx/{}:{}.json",
-                    entry.from, entry.to
-                ))?;
+                let mut file = std::fs::File::create(
+                    directory.join(format!("{}:{}.json", entry.from, entry.to)),
+                )?;
                 serde_json::to_writer(&mut file, &entry)?;
                 entry.dirty = false;
             }
@@ -208,84 +625,114 @@ impl HistoryCache {
     }
 }
 
-async fn fetch_rates(
-    start: NaiveDate,
-    end: NaiveDate,
-    from: Symbol,
-    to: Symbol,
-) -> Result<Rate, anyhow::Error> {
-    let interval = "1d";
-    let client = reqwest::Client::builder().user_agent("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36").build()?;
-    let ticker = match from {
-        Symbol::Currency(c) => format!("{c}{to}=X"),
-        Symbol::Stock(s) => s.to_string(),
-    };
-    let response = client
-        .get(format!(
-            "https://query1.finance.yahoo.com/v8/finance/chart/{ticker}?period1={}&period2={}&interval={interval}&events=history&includeAdjustedClose=true",
-            start.and_time(NaiveTime::default()).and_utc().timestamp(),
-            end.and_time(NaiveTime::default()).and_utc().timestamp(),
-        ))
-        .send()
-        .await?;
-    if response.status() != StatusCode::OK {
-        panic!("{ticker}: {}", response.text().await?);
-    };
-    let rate: Rate = response.json().await?;
-    if let Some(error) = rate.chart.error {
-        anyhow::bail!("{error}");
-    }
-    Ok(rate)
-}
-
 impl Default for HistoryCache {
     fn default() -> Self {
         Self::new()
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Pair {
+    #[serde(with = "tagged")]
     pub from: Symbol,
+    #[serde(with = "tagged")]
     pub to: Symbol,
+    /// The first day for which the rates were fetched. The rates may start later, e.g. if the
+    /// stock was listed later.
+    #[serde(default)]
+    pub since: Option<NaiveDate>,
     #[serde(default)]
     pub dirty: bool,
+    /// The rates could not be updated and might miss the last days.
+    #[serde(skip)]
+    pub stale: bool,
+    /// Why the rates could not be updated.
+    #[serde(skip)]
+    pub error: Option<String>,
+    /// Derived pairs are not stored, as they are derived again from the fetched pairs.
+    #[serde(skip)]
+    pub derivation: Derivation,
     pub rates: BTreeMap<NaiveDate, f64>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Rate {
-    chart: Chart,
+/// How the rates of a pair were obtained.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Derivation {
+    /// From a rate provider.
+    #[default]
+    Fetched,
+    /// The reciprocal of the rates of the opposite pair.
+    Inverse,
+    /// The rates to the `via` currency times the rates from it.
+    Cross { via: Currency },
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Chart {
-    result: Vec<Res>,
-    error: Option<String>,
+/// How a pair which is not fetched is derived, see [`Derivation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DerivedVia {
+    Inverse,
+    Cross(Currency),
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Res {
-    indicators: Indicators,
-    timestamp: Vec<u64>,
+impl From<DerivedVia> for Derivation {
+    fn from(via: DerivedVia) -> Self {
+        match via {
+            DerivedVia::Inverse => Derivation::Inverse,
+            DerivedVia::Cross(via) => Derivation::Cross { via },
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Indicators {
-    quote: Vec<Quote>,
+/// Crosses the rates of two legs on each day either of them has a rate, along with the error of
+/// a leg which could not be updated.
+fn cross(
+    (first, first_error): (BTreeMap<NaiveDate, f64>, Option<String>),
+    (second, second_error): (BTreeMap<NaiveDate, f64>, Option<String>),
+) -> (BTreeMap<NaiveDate, f64>, Option<String>) {
+    let rate = |rates: &BTreeMap<NaiveDate, f64>, date| {
+        rates.range(..=date).next_back().map(|(_, rate)| *rate)
+    };
+    let rates = first
+        .keys()
+        .chain(second.keys())
+        .filter_map(|date| Some((*date, rate(&first, *date)? * rate(&second, *date)?)))
+        .collect();
+    (rates, first_error.or(second_error))
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Quote {
-    close: Vec<Option<f64>>,
+impl Pair {
+    fn since(&self) -> Option<NaiveDate> {
+        self.since
+            .or_else(|| self.rates.first_key_value().map(|(date, _)| *date))
+    }
+
+    fn last(&self) -> Option<NaiveDate> {
+        self.rates.last_key_value().map(|(date, _)| *date)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        collections::BTreeMap,
+        str::FromStr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+    };
 
-    use crate::fx::Symbol;
+    use axum::async_trait;
+    use chrono::{Datelike, NaiveDate};
 
-    use super::Rate;
+    use crate::fx::{Currency, Symbol};
+
+    use super::{
+        provider::{yahoo::Rate, ProviderKind, Providers, RateProvider},
+        Derivation, HistoryCache, Pair,
+    };
 
     #[test]
     fn parse_symbol() {
@@ -300,6 +747,47 @@ mod tests {
         assert_eq!(Symbol::Stock("AAPL".to_string()), Symbol::from("AAPL"));
     }
 
+    #[test]
+    fn store_ticker_of_currency() {
+        let pair = Pair {
+            from: Symbol::Stock("ALL".to_string()),
+            to: Symbol::Currency(Currency::USD),
+            since: None,
+            dirty: false,
+            stale: false,
+            error: None,
+            derivation: Derivation::Fetched,
+            rates: BTreeMap::new(),
+        };
+        let stored = serde_json::to_value(&pair).unwrap();
+        assert_eq!(stored["from"], serde_json::json!({"Stock": "ALL"}));
+        let read: Pair = serde_json::from_value(stored).unwrap();
+        assert_eq!(read.from, pair.from);
+        assert_eq!(read.to, pair.to);
+
+        // Older files have plain strings.
+        let read: Pair =
+            serde_json::from_str(r#"{"from": "VT", "to": "USD", "rates": {}}"#).unwrap();
+        assert_eq!(read.from, Symbol::Stock("VT".to_string()));
+        assert_eq!(read.to, Symbol::Currency(Currency::USD));
+    }
+
+    #[test]
+    fn currency_metadata() {
+        assert_eq!(Currency::from_str("SEK").unwrap(), Currency::SEK);
+        assert!(Currency::from_str("ABC").is_err());
+        assert_eq!(Currency::HUF.to_string(), "HUF");
+        assert_eq!(Currency::JPY.minor_units(), 0);
+        assert_eq!(Currency::CHF.minor_units(), 2);
+        assert_eq!(Currency::KWD.minor_units(), 3);
+        assert_eq!(Currency::EUR.symbol(), "€");
+        assert_eq!(
+            serde_yaml::from_str::<Vec<Currency>>("[CHF, NOK, CZK]").unwrap(),
+            vec![Currency::CHF, Currency::NOK, Currency::CZK]
+        );
+        assert_eq!(serde_json::to_string(&Currency::PLN).unwrap(), "\"PLN\"");
+    }
+
     #[test]
     fn parse_fx_quotes() {
         let value = r#"{
@@ -395,4 +883,211 @@ mod tests {
     }"#;
         serde_json::from_str::<Rate>(value).unwrap();
     }
+
+    #[derive(Debug, Clone, Default)]
+    struct Recorder {
+        requests: Arc<Mutex<Vec<(NaiveDate, NaiveDate)>>>,
+        fail: Arc<AtomicBool>,
+        /// Requests starting before this day fail, as if the provider had no older rates.
+        fail_before: Arc<Mutex<Option<NaiveDate>>>,
+    }
+
+    #[async_trait]
+    impl RateProvider for Recorder {
+        fn kind(&self) -> ProviderKind {
+            ProviderKind::Manual
+        }
+
+        async fn rates(
+            &self,
+            _: &Symbol,
+            _: &Symbol,
+            start: NaiveDate,
+            end: NaiveDate,
+        ) -> anyhow::Result<BTreeMap<NaiveDate, f64>> {
+            if self.fail.load(Ordering::SeqCst) {
+                anyhow::bail!("offline");
+            }
+            if self
+                .fail_before
+                .lock()
+                .unwrap()
+                .is_some_and(|day| start < day)
+            {
+                anyhow::bail!("no rates before {start}");
+            }
+            self.requests.lock().unwrap().push((start, end));
+            Ok(start
+                .iter_days()
+                .take_while(|d| *d <= end)
+                .map(|d| (d, d.day() as f64))
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_incrementally() {
+        let recorder = Recorder::default();
+        let mut cache =
+            HistoryCache::with_providers(Providers::new(vec![Box::new(recorder.clone())]));
+        let chf = Symbol::Currency(Currency::CHF);
+        let eur = Symbol::Currency(Currency::EUR);
+        let date = |day| NaiveDate::from_ymd_opt(2024, 6, day).unwrap();
+        let requests = || std::mem::take(&mut *recorder.requests.lock().unwrap());
+        let pair = |cache: &HistoryCache| {
+            let pair = &cache.fx[&(chf.clone(), eur.clone())];
+            (pair.since, pair.rates.len(), pair.stale)
+        };
+
+        cache.update(&chf, &eur, date(10), date(14)).await.unwrap();
+        assert_eq!(requests(), vec![(date(10), date(14))]);
+        assert_eq!(pair(&cache), (Some(date(10)), 5, false));
+
+        // Only the days after the last cached one are fetched.
+        cache.update(&chf, &eur, date(10), date(18)).await.unwrap();
+        assert_eq!(requests(), vec![(date(14), date(18))]);
+        assert_eq!(pair(&cache), (Some(date(10)), 9, false));
+
+        // Earlier transactions extend the history backwards.
+        cache.update(&chf, &eur, date(5), date(18)).await.unwrap();
+        assert_eq!(requests(), vec![(date(5), date(10))]);
+        assert_eq!(pair(&cache), (Some(date(5)), 14, false));
+
+        cache.update(&chf, &eur, date(8), date(18)).await.unwrap();
+        assert_eq!(requests(), vec![]);
+
+        // The cached rates are used if they can not be updated.
+        recorder.fail.store(true, Ordering::SeqCst);
+        cache.update(&chf, &eur, date(5), date(20)).await.unwrap();
+        assert_eq!(pair(&cache), (Some(date(5)), 14, true));
+        assert!(cache.update(&eur, &chf, date(5), date(20)).await.is_err());
+
+        recorder.fail.store(false, Ordering::SeqCst);
+        cache.update(&chf, &eur, date(5), date(20)).await.unwrap();
+        assert_eq!(pair(&cache), (Some(date(5)), 16, false));
+
+        // A later range which succeeds does not hide that an earlier one failed.
+        requests();
+        *recorder.fail_before.lock().unwrap() = Some(date(3));
+        cache.update(&chf, &eur, date(1), date(24)).await.unwrap();
+        assert_eq!(requests(), vec![(date(20), date(24))]);
+        assert_eq!(pair(&cache), (Some(date(5)), 20, true));
+        let error = cache.fx[&(chf.clone(), eur.clone())].error.clone();
+        assert!(error.is_some_and(|e| e.ends_with("no rates before 2024-06-01")));
+    }
+
+    #[tokio::test]
+    async fn derive_from_cached_pairs() {
+        let recorder = Recorder::default();
+        let mut cache =
+            HistoryCache::with_providers(Providers::new(vec![Box::new(recorder.clone())]));
+        let currency = |c| Symbol::Currency(c);
+        let (chf, eur, pln) = (
+            currency(Currency::CHF),
+            currency(Currency::EUR),
+            currency(Currency::PLN),
+        );
+        let date = |day| NaiveDate::from_ymd_opt(2024, 6, day).unwrap();
+        let (since, end) = (date(10), date(14));
+
+        cache.refresh(&eur, &chf, since, end).await.unwrap();
+        cache.refresh(&eur, &pln, since, end).await.unwrap();
+        assert_eq!(recorder.requests.lock().unwrap().len(), 2);
+
+        cache.refresh(&chf, &eur, since, end).await.unwrap();
+        let pair = &cache.fx[&(chf.clone(), eur.clone())];
+        assert_eq!(pair.derivation, Derivation::Inverse);
+        assert_eq!(pair.rates[&date(12)], 1.0 / 12.0);
+
+        // PLN:EUR is the inverse of a fetched pair and EUR:CHF is fetched.
+        cache.refresh(&pln, &chf, since, end).await.unwrap();
+        let pair = &cache.fx[&(pln.clone(), chf.clone())];
+        assert_eq!(pair.derivation, Derivation::Cross { via: Currency::EUR });
+        assert_eq!(pair.rates[&date(12)], 1.0 / 12.0 * 12.0);
+        assert_eq!(pair.rates.len(), 5);
+
+        // Nothing was fetched for the derived pairs.
+        assert_eq!(recorder.requests.lock().unwrap().len(), 2);
+
+        // Pairs without a path are fetched.
+        cache
+            .refresh(&currency(Currency::USD), &chf, since, end)
+            .await
+            .unwrap();
+        assert_eq!(recorder.requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn fall_back_when_a_leg_fails() {
+        let recorder = Recorder::default();
+        let mut cache =
+            HistoryCache::with_providers(Providers::new(vec![Box::new(recorder.clone())]));
+        let currency = |c| Symbol::Currency(c);
+        let (chf, eur, pln, usd) = (
+            currency(Currency::CHF),
+            currency(Currency::EUR),
+            currency(Currency::PLN),
+            currency(Currency::USD),
+        );
+        let date = |day| NaiveDate::from_ymd_opt(2024, 6, day).unwrap();
+        let (since, end) = (date(10), date(14));
+
+        for (from, to) in [(&eur, &chf), (&usd, &chf), (&usd, &pln)] {
+            cache.refresh(from, to, since, end).await.unwrap();
+        }
+        // The provider had no rates for EUR:PLN.
+        cache.fx.insert(
+            (eur.clone(), pln.clone()),
+            Pair {
+                from: eur.clone(),
+                to: pln.clone(),
+                since: None,
+                dirty: false,
+                stale: false,
+                error: None,
+                derivation: Derivation::Fetched,
+                rates: BTreeMap::new(),
+            },
+        );
+        recorder.fail.store(true, Ordering::SeqCst);
+
+        // The euro is tried first, but only the dollar leads to rates.
+        cache.refresh(&pln, &chf, since, end).await.unwrap();
+        let pair = &cache.fx[&(pln.clone(), chf.clone())];
+        assert_eq!(pair.derivation, Derivation::Cross { via: Currency::USD });
+        assert_eq!(pair.rates.len(), 5);
+
+        // Without any way to derive them, the rates are fetched.
+        cache.fx.remove(&(usd.clone(), pln.clone()));
+        cache.fx.remove(&(pln.clone(), chf.clone()));
+        assert!(cache.refresh(&pln, &chf, since, end).await.is_err());
+        recorder.fail.store(false, Ordering::SeqCst);
+        cache.refresh(&pln, &chf, since, end).await.unwrap();
+        let pair = &cache.fx[&(pln.clone(), chf.clone())];
+        assert_eq!(pair.derivation, Derivation::Fetched);
+    }
+
+    #[tokio::test]
+    async fn merge_snapshots() {
+        let recorder = Recorder::default();
+        let mut cache =
+            HistoryCache::with_providers(Providers::new(vec![Box::new(recorder.clone())]));
+        let chf = Symbol::Currency(Currency::CHF);
+        let eur = Symbol::Currency(Currency::EUR);
+        let date = |day| NaiveDate::from_ymd_opt(2024, 6, day).unwrap();
+        let len = |cache: &HistoryCache| cache.fx[&(chf.clone(), eur.clone())].rates.len();
+
+        cache.update(&chf, &eur, date(10), date(14)).await.unwrap();
+        let mut longer = cache.snapshot();
+        let shorter = cache.snapshot();
+        longer.update(&chf, &eur, date(5), date(18)).await.unwrap();
+        assert_eq!(len(&cache), 5);
+
+        cache.merge(longer).unwrap();
+        assert_eq!(len(&cache), 14);
+
+        // A snapshot taken before does not undo the fetched days.
+        cache.merge(shorter).unwrap();
+        assert_eq!(len(&cache), 14);
+    }
 }
@@ -0,0 +1,249 @@
+pub mod ecb;
+pub mod manual;
+pub mod prices;
+pub mod yahoo;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use axum::async_trait;
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use super::Symbol;
+
+/// A source of the daily rates of a currency pair or the daily prices of a stock.
+#[async_trait]
+pub trait RateProvider: Debug + Send + Sync {
+    fn kind(&self) -> ProviderKind;
+
+    /// Gets the rates between `start` and `end`.
+    ///
+    /// Stock prices are in the quote currency of the stock, whatever `to` is. An empty map means
+    /// that the provider does not know the pair.
+    async fn rates(
+        &self,
+        from: &Symbol,
+        to: &Symbol,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<BTreeMap<NaiveDate, f64>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    Manual,
+    Prices,
+    Ecb,
+    Yahoo,
+}
+
+/// The name under which the rates of a pair are configured and stored, e.g. `EUR:CHF` for a
+/// currency pair and `VT` for a stock.
+pub fn key(from: &Symbol, to: &Symbol) -> String {
+    match from {
+        Symbol::Currency(_) => format!("{from}:{to}"),
+        Symbol::Stock(s) => s.clone(),
+    }
+}
+
+/// The configuration of the rate providers, read from `portfolio/providers.yaml`.
+///
+/// ```yaml
+/// default: [manual, prices, ecb, yahoo]
+/// symbols:
+///   VT: [prices, yahoo]
+///   EUR:PLN: [ecb]
+/// ecb: portfolio/ecb/eurofxref-hist.xml
+/// prices: portfolio/prices
+/// manual:
+///   EUR:PLN:
+///     2024-06-24: 4.31
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct ProviderConfig {
+    /// The providers which are asked in this order, unless the symbol has its own order.
+    pub default: Option<Vec<ProviderKind>>,
+    /// The order of the providers by the key of the pair, see [`key`]. Like for `manual` and the
+    /// files of `prices`, this is `FROM:TO` for a currency pair and the ticker for a stock.
+    #[serde(default)]
+    pub symbols: HashMap<String, Vec<ProviderKind>>,
+    /// Replaces the URL of the Yahoo API, e.g. with a local stand-in.
+    pub yahoo: Option<String>,
+    /// The ECB reference rate history as XML or CSV file.
+    pub ecb: Option<PathBuf>,
+    /// The directory with a `<key>.csv` price file per pair.
+    pub prices: Option<PathBuf>,
+    /// The rates by key and date.
+    #[serde(default)]
+    pub manual: HashMap<String, BTreeMap<NaiveDate, f64>>,
+}
+
+/// The configured rate providers and the order in which they are asked.
+#[derive(Debug)]
+pub struct Providers {
+    providers: Vec<Box<dyn RateProvider>>,
+    default: Vec<ProviderKind>,
+    symbols: HashMap<String, Vec<ProviderKind>>,
+}
+
+impl Providers {
+    /// Asks the providers in the order of their kinds, unless configured otherwise.
+    pub fn new(providers: Vec<Box<dyn RateProvider>>) -> Self {
+        Self {
+            providers,
+            default: vec![
+                ProviderKind::Manual,
+                ProviderKind::Prices,
+                ProviderKind::Ecb,
+                ProviderKind::Yahoo,
+            ],
+            symbols: HashMap::new(),
+        }
+    }
+
+    pub fn from_config(config: ProviderConfig) -> Self {
+        let mut providers: Vec<Box<dyn RateProvider>> = vec![];
+        if !config.manual.is_empty() {
+            providers.push(Box::new(manual::Manual::new(config.manual)));
+        }
+        if let Some(directory) = config.prices {
+            providers.push(Box::new(prices::Prices::new(directory)));
+        }
+        if let Some(file) = config.ecb {
+            providers.push(Box::new(ecb::Ecb::new(file)));
+        }
+        providers.push(Box::new(match config.yahoo {
+            Some(url) => yahoo::Yahoo::new(url),
+            None => yahoo::Yahoo::default(),
+        }));
+
+        let mut providers = Self::new(providers);
+        if let Some(default) = config.default {
+            providers.default = default;
+        }
+        providers.symbols = config.symbols;
+        providers
+    }
+
+    /// Reads the configuration if there is one.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let file = std::fs::File::open(path)?;
+        let config = serde_yaml::from_reader(file)
+            .with_context(|| format!("{} could not be read", path.display()))?;
+        Ok(Self::from_config(config))
+    }
+
+    /// Sets the order in which the providers are asked for the rates of a pair.
+    pub fn with_priority(mut self, from: &Symbol, to: &Symbol, kinds: Vec<ProviderKind>) -> Self {
+        self.symbols.insert(key(from, to), kinds);
+        self
+    }
+
+    /// Gets the rates from the first provider which knows the pair.
+    pub async fn rates(
+        &self,
+        from: &Symbol,
+        to: &Symbol,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<BTreeMap<NaiveDate, f64>> {
+        let kinds = self.symbols.get(&key(from, to)).unwrap_or(&self.default);
+        let mut error = None;
+        for kind in kinds {
+            for provider in self.providers.iter().filter(|p| p.kind() == *kind) {
+                match provider.rates(from, to, start, end).await {
+                    Ok(rates) if !rates.is_empty() => return Ok(rates),
+                    Ok(_) => {}
+                    Err(e) => error = Some(e.context(format!("{kind:?} failed for {from}:{to}"))),
+                }
+            }
+        }
+        match error {
+            Some(error) => Err(error),
+            None => anyhow::bail!("No provider has rates for {from}:{to}"),
+        }
+    }
+}
+
+impl Default for Providers {
+    fn default() -> Self {
+        Self::new(vec![Box::new(yahoo::Yahoo::default())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use chrono::NaiveDate;
+
+    use crate::fx::{Currency, Symbol};
+
+    use super::{manual::Manual, yahoo, ProviderKind, Providers};
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[tokio::test]
+    async fn priority_per_symbol() {
+        let url = yahoo::tests::stand_in().await;
+        let manual = Manual::new(HashMap::from([
+            (
+                "CHF:EUR".to_string(),
+                BTreeMap::from([(date("2024-10-31"), 1.05)]),
+            ),
+            (
+                "VT".to_string(),
+                BTreeMap::from([(date("2024-10-31"), 1.0)]),
+            ),
+        ]));
+        let providers = Providers::new(vec![Box::new(yahoo::Yahoo::new(url)), Box::new(manual)]);
+        let chf = Symbol::Currency(Currency::CHF);
+        let eur = Symbol::Currency(Currency::EUR);
+        let providers = providers
+            .with_priority(
+                &Symbol::from("VT"),
+                &eur,
+                vec![ProviderKind::Yahoo, ProviderKind::Manual],
+            )
+            .with_priority(
+                &chf,
+                &Symbol::Currency(Currency::USD),
+                vec![ProviderKind::Yahoo],
+            );
+        let (start, end) = (date("2024-10-01"), date("2024-11-01"));
+
+        // Manual entries come first by default, also for other pairs of the same currency.
+        let rates = providers.rates(&chf, &eur, start, end).await.unwrap();
+        assert_eq!(rates, BTreeMap::from([(date("2024-10-31"), 1.05)]));
+
+        let rates = providers
+            .rates(&Symbol::from("VT"), &eur, start, end)
+            .await
+            .unwrap();
+        assert_eq!(rates.len(), 2);
+        assert_eq!(rates[&date("2024-10-31")], 118.56);
+
+        // Yahoo is asked once nothing else knows the pair.
+        let rates = providers
+            .rates(&Symbol::Currency(Currency::USD), &eur, start, end)
+            .await
+            .unwrap();
+        assert_eq!(rates[&date("2024-10-31")], 0.92);
+
+        assert!(providers
+            .rates(&Symbol::from("UNKNOWN"), &eur, start, end)
+            .await
+            .is_err());
+    }
+}
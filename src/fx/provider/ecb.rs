@@ -0,0 +1,214 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use axum::async_trait;
+use chrono::NaiveDate;
+use tokio::sync::OnceCell;
+
+use crate::fx::{Currency, Symbol};
+
+use super::{ProviderKind, RateProvider};
+
+/// Reads the euro foreign exchange reference rates of the ECB from a local copy of their history,
+/// either `eurofxref-hist.xml` or `eurofxref-hist.csv`.
+///
+/// The rates between two other currencies are crossed through the euro. The file is read once,
+/// when the rates are first asked for.
+#[derive(Debug)]
+pub struct Ecb {
+    file: PathBuf,
+    history: OnceCell<History>,
+}
+
+impl Ecb {
+    pub fn new(file: PathBuf) -> Self {
+        Self {
+            file,
+            history: OnceCell::new(),
+        }
+    }
+
+    async fn history(&self) -> Result<&History> {
+        self.history
+            .get_or_try_init(|| async {
+                let file = self.file.clone();
+                // The history goes back to 1999, which takes a while to parse.
+                tokio::task::spawn_blocking(move || {
+                    let content = std::fs::read_to_string(&file)
+                        .with_context(|| format!("{} could not be read", file.display()))?;
+                    if content.trim_start().starts_with('<') {
+                        parse_xml(&content)
+                    } else {
+                        parse_csv(&content)
+                    }
+                })
+                .await?
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl RateProvider for Ecb {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Ecb
+    }
+
+    async fn rates(
+        &self,
+        from: &Symbol,
+        to: &Symbol,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<BTreeMap<NaiveDate, f64>> {
+        let (Symbol::Currency(from), Symbol::Currency(to)) = (from, to) else {
+            return Ok(BTreeMap::new());
+        };
+        let history = self.history().await?;
+        let euros = |rates: &HashMap<Currency, f64>, currency: &Currency| match currency {
+            Currency::EUR => Some(1.0),
+            _ => rates.get(currency).copied(),
+        };
+        Ok(history
+            .range(start..=end)
+            .filter_map(|(date, rates)| Some((*date, euros(rates, to)? / euros(rates, from)?)))
+            .collect())
+    }
+}
+
+/// The rates of one euro by day.
+type History = BTreeMap<NaiveDate, HashMap<Currency, f64>>;
+
+fn parse_xml(content: &str) -> Result<History> {
+    let document = roxmltree::Document::parse(content)?;
+    let mut history = History::new();
+    for day in document
+        .descendants()
+        .filter(|n| n.has_tag_name("Cube") && n.has_attribute("time"))
+    {
+        let time = day.attribute("time").unwrap_or_default();
+        let date = NaiveDate::parse_from_str(time, "%Y-%m-%d")
+            .with_context(|| format!("{time} is not a date"))?;
+        let rates = day
+            .children()
+            .filter_map(|n| {
+                // Currencies which are not supported are skipped.
+                let currency = Currency::from_str(n.attribute("currency")?).ok()?;
+                Some((currency, n.attribute("rate")?.parse().ok()?))
+            })
+            .collect();
+        history.insert(date, rates);
+    }
+    Ok(history)
+}
+
+fn parse_csv(content: &str) -> Result<History> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let currencies = reader
+        .headers()?
+        .iter()
+        .map(|h| Currency::from_str(h.trim()).ok())
+        .collect::<Vec<_>>();
+    let mut history = History::new();
+    for record in reader.records() {
+        let record = record?;
+        let time = record.get(0).unwrap_or_default().trim();
+        let date = NaiveDate::parse_from_str(time, "%Y-%m-%d")
+            .with_context(|| format!("{time} is not a date"))?;
+        // Currencies without a rate on that day are marked as N/A.
+        let rates = currencies
+            .iter()
+            .zip(&record)
+            .filter_map(|(currency, rate)| Some(((*currency)?, rate.trim().parse().ok()?)))
+            .collect();
+        history.insert(date, rates);
+    }
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::NaiveDate;
+
+    use crate::fx::{provider::RateProvider, Currency, Symbol};
+
+    use super::Ecb;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gesmes:Envelope xmlns:gesmes="http://www.gesmes.org/xml/2002-08-01" xmlns="http://www.ecb.int/vocabulary/2002-08-01/eurofxref">
+    <gesmes:subject>Reference rates</gesmes:subject>
+    <Cube>
+        <Cube time="2024-06-25">
+            <Cube currency="USD" rate="1.0709"/>
+            <Cube currency="CHF" rate="0.9555"/>
+            <Cube currency="SEK" rate="11.2725"/>
+        </Cube>
+        <Cube time="2024-06-24">
+            <Cube currency="USD" rate="1.0723"/>
+            <Cube currency="CHF" rate="0.9571"/>
+        </Cube>
+    </Cube>
+</gesmes:Envelope>"#;
+
+    const CSV: &str = "Date,USD,JPY,CYP,CHF,
+2024-06-25,1.0709,170.97,N/A,0.9555,
+2024-06-24,1.0723,171.21,N/A,0.9571,
+";
+
+    async fn rates(content: &str, from: Currency, to: Symbol) -> BTreeMap<NaiveDate, f64> {
+        let file = std::env::temp_dir().join(format!("ecb-{}-{from}-{to}", std::process::id()));
+        std::fs::write(&file, content).unwrap();
+        let rates = Ecb::new(file.clone())
+            .rates(
+                &Symbol::Currency(from),
+                &to,
+                NaiveDate::from_ymd_opt(2024, 6, 25).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 6, 30).unwrap(),
+            )
+            .await
+            .unwrap();
+        std::fs::remove_file(file).unwrap();
+        rates
+    }
+
+    #[tokio::test]
+    async fn reference_rates() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 25).unwrap();
+        for content in [XML, CSV] {
+            let eur = rates(content, Currency::EUR, Symbol::Currency(Currency::USD)).await;
+            assert_eq!(eur, BTreeMap::from([(date, 1.0709)]));
+
+            let chf = rates(content, Currency::CHF, Symbol::Currency(Currency::EUR)).await;
+            assert_eq!(chf, BTreeMap::from([(date, 1.0 / 0.9555)]));
+
+            let usd = rates(content, Currency::USD, Symbol::Currency(Currency::CHF)).await;
+            assert_eq!(usd, BTreeMap::from([(date, 0.9555 / 1.0709)]));
+
+            assert!(rates(content, Currency::EUR, Symbol::from("VT"))
+                .await
+                .is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn read_once() {
+        let file = std::env::temp_dir().join(format!("ecb-once-{}", std::process::id()));
+        std::fs::write(&file, CSV).unwrap();
+        let ecb = Ecb::new(file.clone());
+        let (eur, usd) = (
+            Symbol::Currency(Currency::EUR),
+            Symbol::Currency(Currency::USD),
+        );
+        let date = NaiveDate::from_ymd_opt(2024, 6, 25).unwrap();
+        assert_eq!(ecb.rates(&eur, &usd, date, date).await.unwrap().len(), 1);
+
+        std::fs::remove_file(file).unwrap();
+        assert_eq!(ecb.rates(&eur, &usd, date, date).await.unwrap().len(), 1);
+    }
+}
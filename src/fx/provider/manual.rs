@@ -0,0 +1,48 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use axum::async_trait;
+use chrono::NaiveDate;
+
+use crate::fx::Symbol;
+
+use super::{key, ProviderKind, RateProvider};
+
+/// Rates which were entered by hand, e.g. for a pair no other provider knows.
+#[derive(Debug)]
+pub struct Manual {
+    rates: HashMap<String, BTreeMap<NaiveDate, f64>>,
+}
+
+impl Manual {
+    /// Takes the rates by [key](super::key) and date.
+    pub fn new(rates: HashMap<String, BTreeMap<NaiveDate, f64>>) -> Self {
+        Self { rates }
+    }
+}
+
+#[async_trait]
+impl RateProvider for Manual {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Manual
+    }
+
+    async fn rates(
+        &self,
+        from: &Symbol,
+        to: &Symbol,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<BTreeMap<NaiveDate, f64>> {
+        Ok(self
+            .rates
+            .get(&key(from, to))
+            .map(|rates| {
+                rates
+                    .range(start..=end)
+                    .map(|(date, rate)| (*date, *rate))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
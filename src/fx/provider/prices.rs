@@ -0,0 +1,104 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::{Context, Result};
+use axum::async_trait;
+use chrono::NaiveDate;
+
+use crate::fx::Symbol;
+
+use super::{key, ProviderKind, RateProvider};
+
+/// Reads the rates from a `<key>.csv` file per pair, e.g. `VT.csv` or `EUR:CHF.csv`.
+///
+/// The files need a `Date` column and a `Close`, `Price` or `Rate` column, so the history
+/// downloads of most brokers and finance sites can be used as they are.
+#[derive(Debug)]
+pub struct Prices {
+    directory: PathBuf,
+}
+
+impl Prices {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+}
+
+#[async_trait]
+impl RateProvider for Prices {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Prices
+    }
+
+    async fn rates(
+        &self,
+        from: &Symbol,
+        to: &Symbol,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<BTreeMap<NaiveDate, f64>> {
+        let path = self.directory.join(format!("{}.csv", key(from, to)));
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        let rates =
+            parse(&content).with_context(|| format!("{} could not be read", path.display()))?;
+        Ok(rates.range(start..=end).map(|(d, r)| (*d, *r)).collect())
+    }
+}
+
+fn parse(content: &str) -> Result<BTreeMap<NaiveDate, f64>> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+    let column = |names: &[&str]| {
+        headers
+            .iter()
+            .position(|h| names.iter().any(|n| h.trim().eq_ignore_ascii_case(n)))
+    };
+    let date = column(&["date"]).context("The Date column is missing")?;
+    let rate = column(&["close", "price", "rate"]).context("The Close column is missing")?;
+
+    let mut rates = BTreeMap::new();
+    for record in reader.records() {
+        let record = record?;
+        let (Some(d), Some(r)) = (record.get(date), record.get(rate)) else {
+            continue;
+        };
+        // Days without trading are often left empty or marked as null.
+        let Ok(r) = r.trim().parse::<f64>() else {
+            continue;
+        };
+        let d = NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d")
+            .with_context(|| format!("{d} is not a date"))?;
+        rates.insert(d, r);
+    }
+    Ok(rates)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::parse;
+
+    #[test]
+    fn parse_price_file() {
+        let rates = parse(
+            "Date,Open,High,Low,Close,Adj Close,Volume
+2024-10-30,117.5,118.0,116.9,117.0,117.0,1000
+2024-10-31,117.0,118.9,116.8,118.56,118.56,1200
+2024-11-01,null,null,null,null,null,null
+",
+        )
+        .unwrap();
+        assert_eq!(
+            rates.into_iter().collect::<Vec<_>>(),
+            vec![
+                (NaiveDate::from_ymd_opt(2024, 10, 30).unwrap(), 117.0),
+                (NaiveDate::from_ymd_opt(2024, 10, 31).unwrap(), 118.56),
+            ]
+        );
+
+        assert!(parse("Day,Value\n2024-10-30,1.0\n").is_err());
+    }
+}
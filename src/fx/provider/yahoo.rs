@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use axum::async_trait;
+use chrono::{Days, NaiveDate, NaiveTime};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::fx::Symbol;
+
+use super::{ProviderKind, RateProvider};
+
+/// Gets the daily closes from the chart API of Yahoo Finance.
+#[derive(Debug)]
+pub struct Yahoo {
+    url: String,
+}
+
+impl Yahoo {
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+}
+
+impl Default for Yahoo {
+    fn default() -> Self {
+        Self::new("https://query1.finance.yahoo.com".to_string())
+    }
+}
+
+#[async_trait]
+impl RateProvider for Yahoo {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Yahoo
+    }
+
+    async fn rates(
+        &self,
+        from: &Symbol,
+        to: &Symbol,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<BTreeMap<NaiveDate, f64>> {
+        let rate = self.fetch(start, end, from, to).await?;
+        let result = rate
+            .chart
+            .result
+            .first()
+            .with_context(|| format!("No rates were returned for {from}:{to}"))?;
+        let quotes = result
+            .indicators
+            .quote
+            .first()
+            .with_context(|| format!("No quotes were returned for {from}:{to}"))?
+            .close
+            .iter()
+            .copied();
+        let dates = result.timestamp.iter().copied().map(|v| {
+            NaiveDate::default()
+                .checked_add_days(Days::new(v / 3600 / 24))
+                .unwrap_or_default()
+        });
+        Ok(dates
+            .zip(quotes)
+            .filter_map(|(d, q)| q.map(|q| (d, q)))
+            .collect())
+    }
+}
+
+impl Yahoo {
+    async fn fetch(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+        from: &Symbol,
+        to: &Symbol,
+    ) -> Result<Rate, anyhow::Error> {
+        let interval = "1d";
+        let client = reqwest::Client::builder().user_agent("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36").build()?;
+        let ticker = match from {
+            Symbol::Currency(c) => format!("{c}{to}=X"),
+            Symbol::Stock(s) => s.to_string(),
+        };
+        let response = client
+            .get(format!(
+                "{}/v8/finance/chart/{ticker}?period1={}&period2={}&interval={interval}&events=history&includeAdjustedClose=true",
+                self.url,
+                start.and_time(NaiveTime::default()).and_utc().timestamp(),
+                end.and_time(NaiveTime::default()).and_utc().timestamp(),
+            ))
+            .send()
+            .await?;
+        let status = response.status();
+        if status != StatusCode::OK {
+            anyhow::bail!(
+                "{ticker} could not be fetched ({status}): {}",
+                response.text().await?
+            );
+        };
+        let rate: Rate = response.json().await?;
+        if let Some(error) = rate.chart.error {
+            anyhow::bail!("{error}");
+        }
+        Ok(rate)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Rate {
+    chart: Chart,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Chart {
+    result: Vec<Res>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Res {
+    indicators: Indicators,
+    timestamp: Vec<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Indicators {
+    quote: Vec<Quote>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Quote {
+    close: Vec<Option<f64>>,
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use axum::{extract::Path, http::StatusCode, routing::get, Json, Router};
+    use chrono::NaiveDate;
+    use serde_json::{json, Value};
+
+    use crate::fx::{
+        provider::{ProviderKind, RateProvider},
+        Currency, Symbol,
+    };
+
+    use super::Yahoo;
+
+    /// Serves the closes of VT and USD:EUR on 2024-10-30 and 2024-10-31 like the chart API.
+    pub async fn stand_in() -> String {
+        async fn chart(Path(ticker): Path<String>) -> (StatusCode, Json<Value>) {
+            let close = match ticker.as_str() {
+                "VT" => json!([117.0, 118.56]),
+                "USDEUR=X" => json!([0.91, null, 0.92]),
+                _ => {
+                    return (
+                        StatusCode::NOT_FOUND,
+                        Json(json!({"chart": {"result": null, "error": "Not Found"}})),
+                    )
+                }
+            };
+            let timestamp = match ticker.as_str() {
+                "VT" => json!([1730295000, 1730381400]),
+                _ => json!([1730246400, 1730300000, 1730332800]),
+            };
+            let result =
+                json!({"timestamp": timestamp, "indicators": {"quote": [{"close": close}]}});
+            (
+                StatusCode::OK,
+                Json(json!({"chart": {"result": [result], "error": null}})),
+            )
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().route("/v8/finance/chart/:ticker", get(chart));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn fetch_from_stand_in() {
+        let yahoo = Yahoo::new(stand_in().await);
+        assert_eq!(yahoo.kind(), ProviderKind::Yahoo);
+        let (start, end) = (
+            NaiveDate::from_ymd_opt(2024, 10, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 11, 1).unwrap(),
+        );
+        let eur = Symbol::Currency(Currency::EUR);
+
+        let rates = yahoo
+            .rates(&Symbol::Currency(Currency::USD), &eur, start, end)
+            .await
+            .unwrap();
+        // Days without a close are skipped.
+        assert_eq!(
+            rates.into_iter().collect::<Vec<_>>(),
+            vec![
+                (NaiveDate::from_ymd_opt(2024, 10, 30).unwrap(), 0.91),
+                (NaiveDate::from_ymd_opt(2024, 10, 31).unwrap(), 0.92),
+            ]
+        );
+
+        let error = yahoo
+            .rates(&Symbol::from("UNKNOWN"), &eur, start, end)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("404"), "{error}");
+    }
+}
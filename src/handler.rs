@@ -1,3 +1,5 @@
 pub mod auth;
 pub mod ledger;
 pub mod portfolio;
+pub mod rates;
+pub mod rules;
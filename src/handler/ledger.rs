@@ -1,8 +1,12 @@
 pub mod create;
 pub mod delete;
+pub mod detect;
 pub mod files;
 pub mod get;
+pub mod income;
 pub mod list;
+pub mod reconciliation;
+pub mod records;
 pub mod summary;
 pub mod update;
 
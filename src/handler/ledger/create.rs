@@ -5,7 +5,7 @@ use ts_rs::TS;
 
 use crate::handler::auth::user::User;
 use crate::state::PortfolioAdapter;
-use crate::{cli::BankFormat, error::AppError};
+use crate::{banks::CustomFormat, cli::BankFormat, error::AppError, money::Money};
 
 #[debug_handler(state = crate::state::AppState)]
 pub async fn handler(
@@ -23,7 +23,10 @@ pub async fn handler(
 #[serde(rename_all = "camelCase")]
 pub struct CreateLedgerRequest {
     pub format: BankFormat,
-    pub initial_balance: Option<f64>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub custom_format: Option<CustomFormat>,
+    pub initial_balance: Option<Money>,
     #[ts(type = "number")]
     pub initial_date: Option<NaiveDate>,
     pub name: String,
@@ -0,0 +1,58 @@
+use anyhow::anyhow;
+use axum::{debug_handler, extract::Multipart, Json};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    banks::{detect, parse, Detection, DETECTION_THRESHOLD},
+    cli::BankFormat,
+    error::AppError,
+    fx::{Currency, Symbol},
+    handler::auth::user::User,
+};
+
+/// Guesses the format and the currencies of an uploaded file without storing it.
+#[debug_handler(state = crate::state::AppState)]
+pub async fn handler(
+    _user: User,
+    mut multipart: Multipart,
+) -> Result<Json<DetectResponse>, AppError> {
+    let Some(field) = multipart.next_field().await? else {
+        return Err(anyhow!("No file found in payload").into());
+    };
+    let name = field.name().unwrap_or_default().to_string();
+    let content = String::from_utf8_lossy(&field.bytes().await?).to_string();
+
+    let candidates = detect(&content);
+    let format = candidates
+        .first()
+        .filter(|detection| detection.score >= DETECTION_THRESHOLD)
+        .map(|detection| detection.format);
+    let currencies = format
+        .and_then(|format| parse(&name, &name, content, format, None).ok())
+        .unwrap_or_default()
+        .ledgers
+        .into_iter()
+        .filter_map(|ledger| match ledger.symbol {
+            Symbol::Currency(currency) => Some(currency),
+            _ => None,
+        })
+        .collect();
+
+    Ok(Json(DetectResponse {
+        format,
+        currencies,
+        candidates,
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct DetectResponse {
+    /// The format the file clearly is in, if any.
+    pub format: Option<BankFormat>,
+    /// The currencies of the cash ledgers in the file.
+    pub currencies: Vec<Currency>,
+    /// All formats the file might be in, best match first.
+    pub candidates: Vec<Detection>,
+}
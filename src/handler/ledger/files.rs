@@ -1,4 +1,6 @@
+pub mod confirm;
 pub mod delete;
 pub mod get;
 pub mod post;
+pub mod preview;
 pub mod put;
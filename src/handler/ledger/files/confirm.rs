@@ -0,0 +1,47 @@
+use anyhow::{anyhow, Context};
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    error::AppError,
+    handler::auth::user::User,
+    state::{PendingUploads, PortfolioAdapter},
+};
+
+use super::get::{ledger_files, LedgerFiles};
+
+/// Stores a file which was previewed before.
+#[debug_handler(state = crate::state::AppState)]
+pub async fn handler(
+    State(adapter): State<PortfolioAdapter>,
+    State(pending): State<PendingUploads>,
+    Path(id): Path<String>,
+    user: User,
+    Json(payload): Json<ConfirmFileRequest>,
+) -> Result<Json<LedgerFiles>, AppError> {
+    let key = (user.sub.clone(), id.clone(), payload.filename.clone());
+    let content = pending
+        .lock()
+        .await
+        .take(&key, &payload.token)
+        .with_context(|| anyhow!("{} was not previewed with this content", payload.filename))?;
+
+    adapter
+        .add_file(&user.sub, &id, &payload.filename, content)
+        .with_context(|| anyhow!("{} could not be added", payload.filename))?;
+
+    Ok(Json(ledger_files(&adapter, &user.sub, id)?))
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ConfirmFileRequest {
+    pub filename: String,
+    /// The token returned by the preview.
+    pub token: String,
+}
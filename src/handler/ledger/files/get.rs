@@ -8,7 +8,14 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-use crate::{error::AppError, handler::auth::user::User, state::PortfolioAdapter};
+use crate::{
+    banks::{detect, merge, Diagnostic, Ledger, DETECTION_THRESHOLD},
+    cli::BankFormat,
+    error::AppError,
+    handler::auth::user::User,
+    realms::portfolio::state::Owner,
+    state::PortfolioAdapter,
+};
 
 #[debug_handler(state = crate::state::AppState)]
 pub async fn handler(
@@ -16,24 +23,65 @@ pub async fn handler(
     Path(id): Path<String>,
     user: User,
 ) -> Result<Json<LedgerFiles>, AppError> {
-    let files = adapter.list_files(&user.sub)?;
+    Ok(Json(ledger_files(&adapter, &user.sub, id)?))
+}
+
+/// Loads all the files of an account in the same order as the portfolio does and reports the
+/// number of entries and dropped duplicates of each.
+pub(super) fn ledger_files(
+    adapter: &PortfolioAdapter,
+    owner: &Owner,
+    id: String,
+) -> anyhow::Result<LedgerFiles> {
+    let files = adapter.list_files(owner)?;
     let Some(paths) = files.get(&id) else {
-        return Err(anyhow!("{id} was not found"))?;
+        return Err(anyhow!("{id} was not found"));
     };
 
+    let mut ledgers = Vec::<Ledger>::new();
     let files = paths
         .iter()
         .map(|path| {
-            let entries = adapter.load_file(&user.sub, &id);
-            LedgerFile {
-                filename: path.display().to_string(),
-                number_of_entries: entries.as_ref().ok().map(|e| e.len()),
-                error: entries.err().map(|e| e.chain().join("\n")),
+            let filename = path.display().to_string();
+            let detected_format = adapter
+                .read_file(owner, &id, &filename)
+                .ok()
+                .and_then(|content| {
+                    detect(&String::from_utf8_lossy(&content))
+                        .into_iter()
+                        .next()
+                })
+                .filter(|detection| detection.score >= DETECTION_THRESHOLD)
+                .map(|detection| detection.format);
+            match adapter.load_file(owner, &id, &filename) {
+                Ok(loaded) => {
+                    let number_of_entries = loaded.ledgers.iter().map(|l| l.records.len()).sum();
+                    let duplicates = merge(&mut ledgers, loaded.ledgers);
+                    LedgerFile {
+                        filename,
+                        number_of_entries: Some(number_of_entries),
+                        duplicates: Some(duplicates),
+                        detected_format,
+                        error: None,
+                        diagnostics: loaded.diagnostics,
+                    }
+                }
+                Err(error) => {
+                    let error = error.chain().join("\n");
+                    LedgerFile {
+                        diagnostics: vec![Diagnostic::error(&filename, &error)],
+                        filename,
+                        number_of_entries: None,
+                        duplicates: None,
+                        detected_format,
+                        error: Some(error),
+                    }
+                }
             }
         })
         .collect();
 
-    Ok(Json(LedgerFiles { id, files }))
+    Ok(LedgerFiles { id, files })
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, TS)]
@@ -48,5 +96,11 @@ pub struct LedgerFiles {
 pub struct LedgerFile {
     pub filename: String,
     pub number_of_entries: Option<usize>,
+    /// The number of entries dropped because an earlier file already contained them.
+    pub duplicates: Option<usize>,
+    /// The format the file clearly is in, if any.
+    pub detected_format: Option<BankFormat>,
     pub error: Option<String>,
+    /// The problems found in the file, including the error if it could not be parsed.
+    pub diagnostics: Vec<Diagnostic>,
 }
@@ -1,14 +1,19 @@
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context, Result};
 use axum::{
     debug_handler,
     extract::{Multipart, Path, State},
     Json,
 };
-use itertools::Itertools;
 
-use crate::{error::AppError, handler::auth::user::User, state::PortfolioAdapter};
+use crate::{
+    banks::mismatch,
+    cli::BankFormat,
+    error::{AppError, BadRequest},
+    handler::auth::user::User,
+    state::PortfolioAdapter,
+};
 
-use super::get::{LedgerFile, LedgerFiles};
+use super::get::{ledger_files, LedgerFiles};
 
 #[debug_handler(state = crate::state::AppState)]
 pub async fn handler(
@@ -17,30 +22,27 @@ pub async fn handler(
     user: User,
     mut multipart: Multipart,
 ) -> Result<Json<LedgerFiles>, AppError> {
+    let format = adapter.format(&user.sub, &id)?;
     while let Some(field) = multipart.next_field().await? {
         let name = field.name().unwrap().to_string();
         let content = field.bytes().await.unwrap().into_iter().collect::<Vec<_>>();
+        check_format(format, &name, &String::from_utf8_lossy(&content))?;
 
         adapter
             .add_file(&user.sub, &id, &name, content)
             .with_context(|| anyhow!("{name} could not be added"))?;
     }
 
-    let files = adapter.list_files(&user.sub)?;
-    let Some(paths) = files.get(&id) else {
-        return Err(anyhow!("{id} was not found"))?;
-    };
-    let files = paths
-        .iter()
-        .map(|path| {
-            let entries = adapter.load_file(&user.sub, &id);
-            LedgerFile {
-                filename: path.display().to_string(),
-                number_of_entries: entries.as_ref().ok().map(|e| e.len()),
-                error: entries.err().map(|e| e.chain().join("\n")),
-            }
-        })
-        .collect();
+    Ok(Json(ledger_files(&adapter, &user.sub, id)?))
+}
 
-    Ok(Json(LedgerFiles { id, files }))
+/// Rejects a file which clearly belongs to another format than the one of the account.
+pub(crate) fn check_format(format: BankFormat, name: &str, content: &str) -> Result<()> {
+    if let Some(detection) = mismatch(format, content) {
+        bail!(BadRequest(format!(
+            "{name} looks like a {:?} file but the account expects {format:?}",
+            detection.format
+        )));
+    }
+    Ok(())
 }
@@ -0,0 +1,177 @@
+use anyhow::{anyhow, Context};
+use axum::{
+    debug_handler,
+    extract::{Multipart, Path, State},
+    Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ts_rs::TS;
+
+use crate::{
+    banks::{duplicates, merge, Diagnostic, Ledger, LedgerKind},
+    error::AppError,
+    handler::auth::user::User,
+    money::Money,
+    realms::portfolio::adapter::check_file_name,
+    state::{PendingUploads, PortfolioAdapter},
+};
+
+use super::post::check_format;
+
+/// Parses an uploaded file with the format of the account and reports what importing it would
+/// do. Nothing is stored until the upload is confirmed.
+#[debug_handler(state = crate::state::AppState)]
+pub async fn handler(
+    State(adapter): State<PortfolioAdapter>,
+    State(pending): State<PendingUploads>,
+    Path(id): Path<String>,
+    user: User,
+    mut multipart: Multipart,
+) -> Result<Json<PreviewFileResponse>, AppError> {
+    let Some(field) = multipart.next_field().await? else {
+        return Err(anyhow!("No file found in payload").into());
+    };
+    let filename = field.name().context("The file has no name")?.to_string();
+    check_file_name(&filename)?;
+    let content = field.bytes().await?.to_vec();
+    let text = String::from_utf8_lossy(&content).to_string();
+
+    let format = adapter.format(&user.sub, &id)?;
+    check_format(format, &filename, &text)?;
+    let parsed = adapter
+        .parse_file(&user.sub, &id, &filename, text)
+        .with_context(|| anyhow!("{filename} could not be parsed"))?;
+
+    let mut diagnostics = parsed.diagnostics;
+    let mut names = adapter
+        .list_files(&user.sub)?
+        .remove(&id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>();
+    if names.contains(&filename) {
+        diagnostics.push(Diagnostic::warning(
+            None,
+            None,
+            format!("{filename} already exists and will be replaced"),
+        ));
+    }
+    names.sort();
+
+    // Files are merged in name order when loading, so only the records of the files before the
+    // upload make its records duplicates. The upload replaces a file with the same name.
+    let mut existing = Vec::<Ledger>::new();
+    for name in names.iter().take_while(|name| **name < filename) {
+        if let Ok(loaded) = adapter.load_file(&user.sub, &id, name) {
+            merge(&mut existing, loaded.ledgers);
+        }
+    }
+
+    let ledgers = parsed
+        .ledgers
+        .into_iter()
+        .map(|ledger| {
+            let flags = existing
+                .iter()
+                .find(|l| l.symbol == ledger.symbol)
+                .map(|l| duplicates(&l.records, &ledger.records))
+                .unwrap_or_else(|| vec![false; ledger.records.len()]);
+            PreviewLedger {
+                symbol: ledger.symbol.to_string(),
+                kind: ledger.kind,
+                total: ledger.records.iter().map(|r| r.amount).sum(),
+                records: ledger
+                    .records
+                    .into_iter()
+                    .zip(flags)
+                    .map(|(record, duplicate)| PreviewRecord {
+                        id: record.id,
+                        date: record.date,
+                        amount: record.amount,
+                        description: record.description,
+                        category: record.category,
+                        duplicate,
+                    })
+                    .collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let records = ledgers.iter().flat_map(|l| &l.records);
+    let from = records.clone().map(|r| r.date).min();
+    let to = records.clone().map(|r| r.date).max();
+    let duplicates = records.filter(|r| r.duplicate).count();
+    if ledgers.iter().all(|l| l.records.is_empty()) {
+        diagnostics.push(Diagnostic::warning(
+            None,
+            None,
+            format!("{filename} contains no records"),
+        ));
+    }
+
+    for diagnostic in &mut diagnostics {
+        diagnostic.file = filename.clone();
+    }
+
+    let token = Sha256::digest(&content)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    pending
+        .lock()
+        .await
+        .insert((user.sub, id, filename.clone()), token.clone(), content);
+
+    Ok(Json(PreviewFileResponse {
+        token,
+        filename,
+        ledgers,
+        from,
+        to,
+        duplicates,
+        diagnostics,
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PreviewFileResponse {
+    /// Has to be passed to the confirm call to store the file.
+    pub token: String,
+    pub filename: String,
+    pub ledgers: Vec<PreviewLedger>,
+    #[ts(type = "number")]
+    pub from: Option<NaiveDate>,
+    #[ts(type = "number")]
+    pub to: Option<NaiveDate>,
+    /// The number of records which are already contained in other files of the account.
+    pub duplicates: usize,
+    /// The rows which would be skipped and other problems with the upload.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PreviewLedger {
+    pub symbol: String,
+    pub kind: LedgerKind,
+    /// The sum of all records, including the duplicates.
+    pub total: Money,
+    pub records: Vec<PreviewRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PreviewRecord {
+    pub id: Option<String>,
+    #[ts(type = "number")]
+    pub date: NaiveDate,
+    pub amount: Money,
+    pub description: String,
+    pub category: String,
+    /// The record is already contained in another file and will be skipped.
+    pub duplicate: bool,
+}
@@ -7,18 +7,23 @@ use axum::{
 use chrono::NaiveDate;
 
 use crate::{
-    error::AppError, handler::auth::user::User, realms::portfolio::state::Account,
-    state::PortfolioAdapter,
+    error::AppError,
+    handler::{auth::user::User, portfolio::get::match_transfers},
+    realms::portfolio::state::Account,
+    state::{CacheState, PortfolioAdapter},
 };
 
 #[debug_handler(state = crate::state::AppState)]
 pub async fn handler(
     State(adapter): State<PortfolioAdapter>,
+    State(cache): State<CacheState>,
     Path(id): Path<String>,
     Query(filter): Query<Filter>,
     user: User,
 ) -> Result<Json<Account>, AppError> {
-    let portfolio = user.portfolio(adapter)?;
+    let mut portfolio = user.portfolio(adapter)?;
+    // The records which could not be matched are only reported with the summaries.
+    match_transfers(&cache, &mut portfolio).await?;
     let account = &portfolio.accounts.get(&id);
     let Some(account) = account else {
         return Err(anyhow!("{id} was not found"))?;
@@ -46,6 +51,7 @@ pub async fn handler(
         owner: user.sub,
         name: account.name.clone(),
         format: account.format,
+        custom_format: account.custom_format.clone(),
         ledgers,
         initial_balance: account.initial_balance,
         initial_date: account.initial_date,
@@ -0,0 +1,28 @@
+use axum::{debug_handler, extract::State, Json};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    error::AppError,
+    handler::auth::user::User,
+    processing::{action_totals, ActionTotals},
+    state::PortfolioAdapter,
+};
+
+/// Reports the dividends, interest, taxes and fees of all accounts per year and currency.
+#[debug_handler(state = crate::state::AppState)]
+pub async fn handler(
+    State(adapter): State<PortfolioAdapter>,
+    user: User,
+) -> Result<Json<IncomeSummary>, AppError> {
+    let portfolio = user.portfolio(adapter)?;
+    Ok(Json(IncomeSummary {
+        totals: action_totals(portfolio.accounts.values()),
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct IncomeSummary {
+    pub totals: Vec<ActionTotals>,
+}
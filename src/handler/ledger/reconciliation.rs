@@ -0,0 +1,59 @@
+use anyhow::anyhow;
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    error::AppError,
+    fx::Symbol,
+    handler::auth::user::User,
+    processing::{reconcile, Reconciliation},
+    state::PortfolioAdapter,
+};
+
+/// Compares the balances reported in the statements of an account with the computed ones.
+#[debug_handler(state = crate::state::AppState)]
+pub async fn handler(
+    State(adapter): State<PortfolioAdapter>,
+    Path(id): Path<String>,
+    user: User,
+) -> Result<Json<ReconciliationResponse>, AppError> {
+    let portfolio = user.portfolio(adapter)?;
+    let Some(account) = portfolio.accounts.get(&id) else {
+        return Err(anyhow!("{id} was not found"))?;
+    };
+
+    if account.owner != user.sub {
+        return Err(anyhow!("Not authorized!"))?;
+    }
+
+    let ledgers = account
+        .ledgers
+        .iter()
+        .filter(|l| !l.statements.is_empty())
+        .map(|l| LedgerReconciliation {
+            symbol: l.symbol.clone(),
+            balances: reconcile(l),
+        })
+        .collect();
+
+    Ok(Json(ReconciliationResponse { id, ledgers }))
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ReconciliationResponse {
+    pub id: String,
+    pub ledgers: Vec<LedgerReconciliation>,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct LedgerReconciliation {
+    pub symbol: Symbol,
+    pub balances: Vec<Reconciliation>,
+}
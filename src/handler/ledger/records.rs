@@ -0,0 +1,2 @@
+pub mod patch;
+pub mod patch_single;
@@ -0,0 +1,95 @@
+use anyhow::anyhow;
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    banks::ExtendedLedgerRecord, error::AppError, handler::auth::user::User,
+    state::PortfolioAdapter,
+};
+
+#[debug_handler(state = crate::state::AppState)]
+pub async fn handler(
+    State(adapter): State<PortfolioAdapter>,
+    Path(id): Path<String>,
+    user: User,
+    Json(payload): Json<UpdateRecordsRequest>,
+) -> Result<Json<UpdateRecordsResponse>, AppError> {
+    Ok(Json(update_records(adapter, &user, id, payload.records)?))
+}
+
+/// Stores the given edits in the account's overlay and returns the edited records as they look
+/// after reloading the account.
+pub(super) fn update_records(
+    adapter: PortfolioAdapter,
+    user: &User,
+    id: String,
+    records: Vec<UpdateRecordRequest>,
+) -> anyhow::Result<UpdateRecordsResponse> {
+    let portfolio = user.portfolio(adapter.clone())?;
+    let Some(account) = portfolio.accounts.get(&id) else {
+        return Err(anyhow!("{id} was not found"));
+    };
+
+    if account.owner != user.sub {
+        return Err(anyhow!("Not authorized!"));
+    }
+
+    for record in &records {
+        if !account
+            .ledgers
+            .iter()
+            .any(|l| l.records.iter().any(|r| r.id == record.id))
+        {
+            return Err(anyhow!("Record {} was not found in {id}", record.id));
+        }
+    }
+
+    let ids = records.iter().map(|r| r.id.clone()).collect::<Vec<_>>();
+    adapter.update_records(&user.sub, &id, records)?;
+
+    let portfolio = user.portfolio(adapter)?;
+    let records = portfolio
+        .accounts
+        .get(&id)
+        .into_iter()
+        .flat_map(|a| a.ledgers.iter())
+        .flat_map(|l| l.records.iter())
+        .filter(|r| ids.contains(&r.id))
+        .cloned()
+        .collect();
+
+    Ok(UpdateRecordsResponse { id, records })
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct UpdateRecordsRequest {
+    pub records: Vec<UpdateRecordRequest>,
+}
+
+/// An edit of a single record. Fields which are not given are left untouched.
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct UpdateRecordRequest {
+    pub id: String,
+    #[serde(default)]
+    pub checked: Option<bool>,
+    #[serde(default)]
+    pub comments: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct UpdateRecordsResponse {
+    pub id: String,
+    pub records: Vec<ExtendedLedgerRecord>,
+}
@@ -0,0 +1,52 @@
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{error::AppError, handler::auth::user::User, state::PortfolioAdapter};
+
+use super::patch::{update_records, UpdateRecordRequest, UpdateRecordsResponse};
+
+#[debug_handler(state = crate::state::AppState)]
+pub async fn handler(
+    State(adapter): State<PortfolioAdapter>,
+    Path((id, record_id)): Path<(String, String)>,
+    user: User,
+    Json(payload): Json<UpdateSingleRecordRequest>,
+) -> Result<Json<UpdateRecordsResponse>, AppError> {
+    let UpdateSingleRecordRequest {
+        checked,
+        comments,
+        category,
+        description,
+    } = payload;
+
+    Ok(Json(update_records(
+        adapter,
+        &user,
+        id,
+        vec![UpdateRecordRequest {
+            id: record_id,
+            checked,
+            comments,
+            category,
+            description,
+        }],
+    )?))
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct UpdateSingleRecordRequest {
+    #[serde(default)]
+    pub checked: Option<bool>,
+    #[serde(default)]
+    pub comments: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
@@ -10,20 +10,27 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-use crate::{error::AppError, handler::auth::user::User, state::PortfolioAdapter};
+use crate::{
+    error::AppError,
+    handler::{auth::user::User, portfolio::get::match_transfers},
+    money::Money,
+    state::{CacheState, PortfolioAdapter},
+};
 
 #[debug_handler(state = crate::state::AppState)]
 pub async fn handler(
     State(adapter): State<PortfolioAdapter>,
+    State(cache): State<CacheState>,
     date_range: Query<DateRange>,
     user: User,
 ) -> Result<Json<LedgerSummary>, AppError> {
-    let portfolio = user.portfolio(adapter)?;
+    let mut portfolio = user.portfolio(adapter)?;
+    let unmatched = match_transfers(&cache, &mut portfolio).await?;
     let mut spending = HashMap::new();
     for (id, account) in &portfolio.accounts {
         let mut categories = vec![];
         for ledger in &account.ledgers {
-            categories.extend(&ledger.records);
+            categories.extend(ledger.records.iter().filter(|v| v.transfer.is_none()));
         }
 
         let categories = if let Some(from) = &date_range.from {
@@ -44,6 +51,7 @@ pub async fn handler(
 
         let categories = categories
             .into_iter()
+            .sorted_by(|a, b| a.category.cmp(&b.category))
             .group_by(|v| v.category.clone())
             .into_iter()
             .map(|(k, v)| (k.clone(), v.into_iter().map(|v| v.amount).sum()))
@@ -52,7 +60,10 @@ pub async fn handler(
         spending.insert(id.clone(), SpendingSummary { categories });
     }
 
-    Ok(Json(LedgerSummary { spending }))
+    Ok(Json(LedgerSummary {
+        spending,
+        unmatched,
+    }))
 }
 
 #[derive(Deserialize, Debug)]
@@ -65,10 +76,12 @@ pub struct DateRange {
 #[ts(export)]
 pub struct LedgerSummary {
     spending: HashMap<String, SpendingSummary>,
+    /// Why some records could not be matched as transfers between the accounts.
+    unmatched: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct SpendingSummary {
-    categories: HashMap<String, f64>,
+    categories: HashMap<String, Money>,
 }
@@ -7,7 +7,10 @@ use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-use crate::{cli::BankFormat, error::AppError, handler::auth::user::User, state::PortfolioAdapter};
+use crate::{
+    banks::CustomFormat, cli::BankFormat, error::AppError, handler::auth::user::User,
+    money::Money, state::PortfolioAdapter,
+};
 
 #[debug_handler(state = crate::state::AppState)]
 pub async fn handler(
@@ -27,7 +30,10 @@ pub async fn handler(
 #[ts(export)]
 pub struct UpdateLedgerRequest {
     pub format: BankFormat,
-    pub initial_balance: Option<f64>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub custom_format: Option<CustomFormat>,
+    pub initial_balance: Option<Money>,
     #[ts(type = "number")]
     pub initial_date: Option<NaiveDate>,
     pub name: String,
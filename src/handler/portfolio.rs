@@ -1 +1,3 @@
 pub mod get;
+pub mod holdings;
+pub mod performance;
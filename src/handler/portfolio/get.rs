@@ -1,18 +1,31 @@
-use std::collections::{BTreeMap, HashMap};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    ops::Range,
+};
 
 use anyhow::{bail, Context};
-use axum::{debug_handler, extract::State, Json};
-use chrono::{Datelike, Days, NaiveDate, NaiveTime, Utc};
+use axum::{
+    debug_handler,
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveTime, Utc, Weekday};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
 use crate::{
-    banks::LedgerKind,
-    error::AppError,
-    fx::{Currency, Symbol},
+    banks::{ExtendedLedger, ExtendedLedgerRecord, LedgerKind},
+    error::{AppError, BadRequest},
+    fx::{Currency, Pair, Symbol},
     handler::auth::user::User,
-    realms::portfolio::state::Account,
+    money::Money,
+    processing,
+    realms::portfolio::{
+        forecast::{simulate, AssetClass, Flows},
+        performance::external_flows,
+        state::{Account, Portfolio},
+    },
     state::{CacheState, PortfolioAdapter},
 };
 
@@ -20,191 +33,313 @@ use crate::{
 pub async fn handler(
     State(adapter): State<PortfolioAdapter>,
     State(cache): State<CacheState>,
+    Query(query): Query<DataQuery>,
     user: User,
 ) -> Result<Json<PortfolioSummaryResponse>, AppError> {
     let portfolio = user.portfolio(adapter)?;
+    Ok(Json(summary(portfolio, &cache, &query).await?))
+}
 
+async fn summary(
+    mut portfolio: Portfolio,
+    cache: &CacheState,
+    query: &DataQuery,
+) -> Result<PortfolioSummaryResponse, AppError> {
+    let horizon = query.horizon.unwrap_or(12);
+    if !(1..=MAX_HORIZON).contains(&horizon) {
+        return Err(BadRequest(format!("The horizon has to be 1 to {MAX_HORIZON} months")).into());
+    }
     if portfolio.accounts.is_empty() {
-        return Ok(Json(PortfolioSummaryResponse::new()));
+        return Ok(PortfolioSummaryResponse::new(query.resolution));
     }
 
     let dates = get_date_series(&portfolio.accounts);
-    const NUM_SAMPLES: usize = 3 * 365;
+    let start = dates
+        .first()
+        .copied()
+        .unwrap_or_else(|| Utc::now().naive_utc().date());
     let dates_len = dates.len();
-    let samples_to_skip = dates_len.saturating_sub(NUM_SAMPLES);
+    let window = query.window(&dates)?;
+    let samples = periods(&dates[window.clone()], query.resolution);
 
-    let mut accounts = HashMap::new();
+    // Each ledger is converted with the rates of its symbol and, for stocks quoted in another
+    // currency than the base currency, the rates of that currency.
+    let mut conversions = vec![];
     for account in portfolio.accounts.values() {
-        let mut ledger_balances = HashMap::<Symbol, Vec<_>>::new();
-
-        // TODO: Handle multiple account currencies.
-        // Currently if e.g. revolut accounts would have stocks and multiple currency accounts
-        // It would fail to properly value the bought stock because it choses one of ledger currencies
-        // randomly for the conversion.
+        // Stocks without a known quote currency are assumed to trade in the currency of the
+        // account's cash.
         let account_currency = account
             .ledgers
             .iter()
-            .find_map(|l| (l.kind == LedgerKind::Bank).then(|| l.symbol.currency()))
-            .with_context(|| "No ledger currency was found for this ledger")?;
+            .find_map(|l| (l.kind == LedgerKind::Bank).then(|| l.symbol.currency()));
 
         for ledger in &account.ledgers {
-            let mut ledger_worth_on_date = 0.0;
-            // If Some this contains the base currency rates against the ticker.
-            // This is None if the ticker is traded against the base currency.
-            let mut ticker_to_base = None;
-            // We need to get the ticker_to_base_rates.
-            let needs_ticker_to_base_transform =
-                ledger.kind == LedgerKind::Stock && account_currency != portfolio.base_currency;
-            let rates = if ledger.symbol != portfolio.base_currency {
-                if needs_ticker_to_base_transform {
-                    ticker_to_base = Some(
-                        fetch_rate(
-                            cache.clone(),
-                            &Symbol::Currency(account_currency),
-                            portfolio.base_currency,
-                        )
-                        .await?,
-                    );
-                }
-                Some(fetch_rate(cache.clone(), &ledger.symbol, portfolio.base_currency).await?)
+            let quote_currency = if ledger.kind == LedgerKind::Stock {
+                Some(
+                    ledger
+                        .quote_currency
+                        .or(account_currency)
+                        .with_context(|| {
+                            format!("No quote currency was found for {}", ledger.symbol)
+                        })?,
+                )
             } else {
                 None
             };
-            for date in &dates {
-                let sum_on_date = ledger
-                    .records
-                    .iter()
-                    .filter(|v| &v.date == date)
-                    .map(|v| v.amount)
-                    .sum::<f64>();
-
-                let rate_currency = ticker_to_base
-                    .as_ref()
-                    .map_or(1.0, |ttb| rate_for_date(ttb, date));
-                let rate = if let Some(rates) = &rates {
-                    rate_for_date(rates, date)
-                } else {
-                    1.0
-                };
-
-                ledger_worth_on_date += sum_on_date;
-                let amount = ledger_worth_on_date * rate * rate_currency;
-
-                ledger_balances
-                    .entry(ledger.symbol.clone())
-                    .or_default()
-                    .push(amount);
-            }
+            let quote = quote_currency
+                .filter(|c| {
+                    ledger.symbol != portfolio.base_currency && *c != portfolio.base_currency
+                })
+                .map(Symbol::Currency);
+            conversions.push((&account.id, ledger, quote));
         }
+    }
 
-        let mut account_balances = vec![0.0; dates.len()];
-        for b in ledger_balances.into_values() {
-            for (t, b) in account_balances.iter_mut().zip(b) {
-                *t += b;
+    // The rates of each symbol are needed from the first record of the ledgers using it.
+    let mut since = HashMap::<Symbol, NaiveDate>::new();
+    for (_, ledger, quote) in &conversions {
+        let first = ledger.records.iter().map(|r| r.date).min().unwrap_or(start);
+        for symbol in [Some(ledger.symbol.clone()), quote.clone()]
+            .into_iter()
+            .flatten()
+            .filter(|symbol| *symbol != portfolio.base_currency)
+        {
+            since
+                .entry(symbol)
+                .and_modify(|date| *date = first.min(*date))
+                .or_insert(first);
+        }
+    }
+    let pairs = fetch_rates(cache, since, portfolio.base_currency).await?;
+    let stale = pairs.values().any(|pair| pair.stale);
+    let daily = pairs
+        .iter()
+        .map(|(symbol, pair)| (symbol.clone(), daily_rates(&pair.rates, &dates)))
+        .collect::<HashMap<_, _>>();
+
+    let mut accounts = portfolio
+        .accounts
+        .values()
+        .map(|account| {
+            let balances = vec![Money::ZERO; dates.len()];
+            (account.id.clone(), (account.name.clone(), balances))
+        })
+        .collect::<HashMap<_, _>>();
+    // The value of each asset class at the end of the window, which the forecast starts from.
+    let mut assets = BTreeMap::<AssetClass, f64>::new();
+    for (id, ledger, quote) in conversions {
+        let (_, account_balances) = accounts.get_mut(id).unwrap();
+        let rates = daily.get(&ledger.symbol);
+        let ticker_to_base = quote.and_then(|quote| daily.get(&quote));
+
+        let mut ledger_worth_on_date = Money::ZERO;
+        for (i, sum_on_date) in daily_sums(&ledger.records, &dates).into_iter().enumerate() {
+            let rate = rates.map_or(1.0, |rates| rates[i]);
+            let rate_currency = ticker_to_base.map_or(1.0, |ttb| ttb[i]);
+
+            ledger_worth_on_date += sum_on_date;
+            let worth =
+                ledger_worth_on_date.convert(rate * rate_currency, portfolio.base_currency)?;
+            account_balances[i] += worth;
+            if i + 1 == window.end {
+                let class = match ledger.kind {
+                    LedgerKind::Stock => AssetClass::Stocks,
+                    _ => AssetClass::Cash,
+                };
+                *assets.entry(class).or_default() += worth.to_f64();
             }
         }
-
-        accounts.insert(account.id.clone(), (account.name.clone(), account_balances));
     }
 
     let mut balances = Vec::new();
-    let mut total = vec![0.0; dates_len];
-    for (id, (name, mut transactions)) in accounts.into_iter() {
+    let mut total = vec![Money::ZERO; dates_len];
+    for (id, (name, transactions)) in accounts.into_iter() {
         for (total, b) in total.iter_mut().zip(transactions.iter()) {
-            *total += b;
+            *total += *b;
         }
 
-        // Take 3 years worth of data.
-        balances.push(PortfolioLedgerData {
-            id,
-            name,
-            series: transactions.drain(samples_to_skip..).collect(),
-        });
+        let series = transactions[window.clone()]
+            .iter()
+            .copied()
+            .map(Money::to_f64)
+            .collect::<Vec<_>>();
+        balances.push(PortfolioLedgerData::resampled(id, name, &series, &samples));
     }
 
-    const TAKE: usize = 300;
-    let xs = std::iter::repeat(())
-        .take(TAKE)
-        .enumerate()
-        .map(|(i, _)| i as f64)
-        .collect::<Vec<_>>();
-    let ys = total
+    let total = total[..window.end]
         .iter()
         .copied()
-        .rev()
-        .take(TAKE)
-        .rev()
+        .map(Money::to_f64)
         .collect::<Vec<_>>();
-    let (m, q) = linear_regression(&xs, &ys)?;
-
-    let total_prediction = PortfolioLedgerData {
-        id: "total-prediction".to_string(),
-        name: "Prediction of the total".to_string(),
-        series: (0..365).map(|x| m * ((x + TAKE) as f64) + q).collect(),
+    let (prediction_dates, prediction) = predict(&dates[..window.end], &total)?;
+    let prediction_periods = periods(&prediction_dates, query.resolution);
+    let total_prediction = PortfolioLedgerData::resampled(
+        "total-prediction".to_string(),
+        "Prediction of the total".to_string(),
+        &prediction,
+        &prediction_periods,
+    );
+
+    // Internal transfers between the owner's accounts are neither income nor spending.
+    let unmatched = match_transfers(cache, &mut portfolio).await?;
+
+    let forecast = match window.end.checked_sub(1) {
+        Some(last) => forecast(&portfolio, &pairs, &assets, dates[last], horizon)?,
+        None => Forecast::default(),
     };
 
     let mut data = HashMap::new();
     for account in portfolio.accounts.values().filter(|a| a.spending) {
         for ledger in &account.ledgers {
+            let rates = pairs.get(&ledger.symbol).map(|pair| &pair.rates);
             let mut transactions = ledger.records.clone();
             for transaction in &mut transactions {
-                let rate = if ledger.symbol != Symbol::Currency(portfolio.base_currency) {
-                    let rates =
-                        fetch_rate(cache.clone(), &ledger.symbol, portfolio.base_currency).await?;
-                    rates[&transaction.date]
-                } else {
-                    1.0
-                };
-                transaction.amount *= rate;
+                if let Some(rates) = rates {
+                    transaction.amount = transaction.amount.convert(
+                        rate_for_date(rates, &transaction.date),
+                        portfolio.base_currency,
+                    )?;
+                }
             }
 
             let categories = transactions
                 .iter()
-                .filter(|v| v.amount < 0.0)
+                .filter(|v| v.amount.is_sign_negative() && v.transfer.is_none())
                 .sorted_by_key(|v| (v.date.year(), v.date.month(), v.category.clone()))
                 .group_by(|v| (v.date.year(), v.date.month(), v.category.clone()))
                 .into_iter()
-                .map(|(g, v)| (g.clone(), v.into_iter().map(|v| v.amount).sum::<f64>()))
+                .map(|(g, v)| (g.clone(), v.into_iter().map(|v| v.amount).sum::<Money>()))
                 .collect::<HashMap<_, _>>();
 
             for ((year, month, category), amount) in categories {
                 let amount = -amount;
                 let months = data.entry(month).or_insert(HashMap::new());
                 let categories = months.entry(year).or_insert(HashMap::new());
-                let total = categories.entry(category.clone()).or_insert(0.0);
+                let total = categories.entry(category.clone()).or_insert(Money::ZERO);
                 *total += amount;
             }
         }
     }
 
-    Ok(Json(PortfolioSummaryResponse {
+    Ok(PortfolioSummaryResponse {
         total_balance: PortfolioLedgersData {
             balances,
-            timestamps: dates
-                .into_iter()
-                .skip(samples_to_skip)
-                .map(|v| v.and_time(NaiveTime::default()).and_utc().timestamp())
-                .collect(),
+            timestamps: timestamps(&dates[window], &samples),
         },
         total_prediction,
+        prediction_timestamps: timestamps(&prediction_dates, &prediction_periods),
+        forecast,
         spend_per_month: SpendPerMonth { months: data },
         base_currency: portfolio.base_currency,
-    }))
+        resolution: query.resolution,
+        stale,
+        unmatched,
+    })
 }
 
-fn rate_for_date(rates: &BTreeMap<NaiveDate, f64>, date: &NaiveDate) -> f64 {
-    let mut result = None;
-    let mut days = 0;
-    while result.is_none() {
-        let date = date.checked_sub_days(Days::new(days)).unwrap();
-        days += 1;
-        result = rates.get(&date).copied()
+/// Continues the trend of the last 300 days of the daily `total` over the year after the last of
+/// the `dates`. Nothing is predicted from less than two days.
+fn predict(dates: &[NaiveDate], total: &[f64]) -> anyhow::Result<(Vec<NaiveDate>, Vec<f64>)> {
+    const TAKE: usize = 300;
+    let ys = &total[total.len().saturating_sub(TAKE)..];
+    let Some(last) = dates.last().filter(|_| ys.len() >= 2) else {
+        return Ok((vec![], vec![]));
+    };
+    let xs = (0..ys.len()).map(|x| x as f64).collect::<Vec<_>>();
+    let (m, q) = linear_regression(&xs, ys)?;
+
+    let dates = last.iter_days().skip(1).take(365).collect();
+    let prediction = (0..365).map(|x| m * ((x + ys.len()) as f64) + q).collect();
+    Ok((dates, prediction))
+}
+
+/// Forecasts the total for `horizon` months after `to` from the recurring flows of all accounts
+/// and the expected returns of the assets.
+fn forecast(
+    portfolio: &Portfolio,
+    pairs: &HashMap<Symbol, Pair>,
+    assets: &BTreeMap<AssetClass, f64>,
+    to: NaiveDate,
+    horizon: u32,
+) -> anyhow::Result<Forecast> {
+    let mut flows = vec![];
+    for account in portfolio.accounts.values() {
+        for (ledger, record) in external_flows(account) {
+            // Transfers between the owner's accounts do not change the total.
+            if record.transfer.is_some() || record.original_category == "initial" {
+                continue;
+            }
+            let rate = pairs
+                .get(&ledger.symbol)
+                .map_or(1.0, |pair| rate_for_date(&pair.rates, &record.date));
+            let amount = record.amount.convert(rate, portfolio.base_currency)?;
+            flows.push((record.date, record.category.clone(), amount.to_f64()));
+        }
     }
-    result.unwrap_or(1.0)
+    let flows = Flows::detect(&flows, to);
+    let band = simulate(assets, &flows, horizon, FORECAST_PATHS, 0.1);
+
+    Ok(Forecast {
+        timestamps: (1..=horizon)
+            .map(|i| to + Months::new(i))
+            .map(|v| v.and_time(NaiveTime::default()).and_utc().timestamp())
+            .collect(),
+        median: band.median,
+        low: band.low,
+        high: band.high,
+        monthly_flow: flows.monthly(),
+        recurring: flows
+            .recurring
+            .into_iter()
+            .map(|flow| RecurringFlowData {
+                name: flow.name,
+                monthly: flow.monthly,
+            })
+            .collect(),
+    })
+}
+
+/// The longest forecast in months.
+const MAX_HORIZON: u32 = 600;
+
+/// The number of simulated paths of the forecast.
+const FORECAST_PATHS: usize = 1000;
+
+/// The timestamp of the last day of each period, when its sample is taken.
+fn timestamps(dates: &[NaiveDate], periods: &[Range<usize>]) -> Vec<i64> {
+    periods
+        .iter()
+        .map(|period| dates[period.end - 1])
+        .map(|v| v.and_time(NaiveTime::default()).and_utc().timestamp())
+        .collect()
+}
+
+/// The ranges of the consecutive `dates` which fall into the same period.
+fn periods(dates: &[NaiveDate], resolution: Resolution) -> Vec<Range<usize>> {
+    let mut periods: Vec<Range<usize>> = vec![];
+    for (i, date) in dates.iter().enumerate() {
+        match periods.last_mut() {
+            Some(period) if resolution.start(dates[period.start]) == resolution.start(*date) => {
+                period.end = i + 1;
+            }
+            _ => periods.push(i..i + 1),
+        }
+    }
+    periods
+}
+
+/// Gets the rate of `date` or the closest earlier day, e.g. on weekends. Days before the first
+/// rate take the first rate.
+pub(super) fn rate_for_date(rates: &BTreeMap<NaiveDate, f64>, date: &NaiveDate) -> f64 {
+    rates
+        .range(..=date)
+        .next_back()
+        .or_else(|| rates.first_key_value())
+        .map_or(1.0, |(_, rate)| *rate)
 }
 
 /// Get all the dates from the oldest found transaction to today.
-fn get_date_series(accounts: &HashMap<String, Account>) -> Vec<NaiveDate> {
+pub(super) fn get_date_series(accounts: &HashMap<String, Account>) -> Vec<NaiveDate> {
     let max_date = Utc::now().naive_utc().date();
     let mut min_date = NaiveDate::MAX;
     for account in accounts.values() {
@@ -227,12 +362,93 @@ fn get_date_series(accounts: &HashMap<String, Account>) -> Vec<NaiveDate> {
     (min_date.iter_days().take_while(|d| d <= &max_date)).collect::<Vec<_>>()
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct DataQuery {
+    /// The first day of the series, by default three years before `to`.
+    from: Option<NaiveDate>,
+    /// The last day of the series, by default today.
+    to: Option<NaiveDate>,
+    #[serde(default)]
+    resolution: Resolution,
+    /// The number of months which are forecast, by default a year and at most [`MAX_HORIZON`].
+    horizon: Option<u32>,
+}
+
+impl DataQuery {
+    /// The days of the consecutive `dates` which are part of the series, none if the window does
+    /// not overlap them.
+    fn window(&self, dates: &[NaiveDate]) -> anyhow::Result<Range<usize>> {
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                bail!(BadRequest(format!("from {from} is after to {to}")));
+            }
+        }
+        let (Some(first), Some(last)) = (dates.first(), dates.last()) else {
+            return Ok(0..0);
+        };
+        let to = self.to.map_or(*last, |to| to.min(*last));
+        let from = self.from.unwrap_or(to - Days::new(3 * 365 - 1)).max(*first);
+        if from > to {
+            return Ok(0..0);
+        }
+        let index = |date: NaiveDate| (date - *first).num_days() as usize;
+        Ok(index(from)..index(to) + 1)
+    }
+}
+
+/// The length of the periods in which the series are sampled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    #[default]
+    Day,
+    Week,
+    Month,
+    Quarter,
+}
+
+impl Resolution {
+    /// The first day of the period which contains `date`.
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Resolution::Day => date,
+            Resolution::Week => date.week(Weekday::Mon).first_day(),
+            Resolution::Month => date.with_day(1).unwrap(),
+            Resolution::Quarter => {
+                NaiveDate::from_ymd_opt(date.year(), date.month0() / 3 * 3 + 1, 1).unwrap()
+            }
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct PortfolioLedgerData {
     pub id: String,
     pub name: String,
+    /// The balance at the end of each period.
     pub series: Vec<f64>,
+    /// The average daily balance over each period.
+    pub average: Vec<f64>,
+}
+
+impl PortfolioLedgerData {
+    /// Samples the daily balances in `series` once per period.
+    fn resampled(id: String, name: String, series: &[f64], periods: &[Range<usize>]) -> Self {
+        Self {
+            id,
+            name,
+            series: periods
+                .iter()
+                .map(|period| series[period.end - 1])
+                .collect(),
+            average: periods
+                .iter()
+                .map(|period| series[period.clone()].iter().sum::<f64>() / period.len() as f64)
+                .collect(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, TS)]
@@ -243,10 +459,34 @@ pub struct PortfolioLedgersData {
     pub timestamps: Vec<i64>,
 }
 
+/// The simulated total at the end of each month after the series.
+#[derive(Debug, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Forecast {
+    #[ts(type = "number[]")]
+    pub timestamps: Vec<i64>,
+    pub median: Vec<f64>,
+    /// The 10th percentile.
+    pub low: Vec<f64>,
+    /// The 90th percentile.
+    pub high: Vec<f64>,
+    /// The expected net income per month, including the irregular flows.
+    pub monthly_flow: f64,
+    pub recurring: Vec<RecurringFlowData>,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct RecurringFlowData {
+    pub name: String,
+    /// The average amount per month, negative for expenses.
+    pub monthly: f64,
+}
+
 #[derive(Debug, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct SpendPerMonth {
-    pub months: HashMap<u32, HashMap<i32, HashMap<String, f64>>>,
+    pub months: HashMap<u32, HashMap<i32, HashMap<String, Money>>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, TS)]
@@ -254,12 +494,20 @@ pub struct SpendPerMonth {
 pub struct PortfolioSummaryResponse {
     pub total_balance: PortfolioLedgersData,
     pub total_prediction: PortfolioLedgerData,
+    #[ts(type = "number[]")]
+    pub prediction_timestamps: Vec<i64>,
+    pub forecast: Forecast,
     pub spend_per_month: SpendPerMonth,
     pub base_currency: Currency,
+    pub resolution: Resolution,
+    /// Some rates could not be updated, so the latest values might be off.
+    pub stale: bool,
+    /// Why some records could not be matched as transfers between the accounts.
+    pub unmatched: Vec<String>,
 }
 
 impl PortfolioSummaryResponse {
-    pub fn new() -> Self {
+    pub fn new(resolution: Resolution) -> Self {
         Self {
             total_balance: PortfolioLedgersData {
                 balances: vec![],
@@ -269,23 +517,147 @@ impl PortfolioSummaryResponse {
                 id: "total-prediction".to_string(),
                 name: "Prediction of the total".to_string(),
                 series: vec![],
+                average: vec![],
             },
+            prediction_timestamps: vec![],
+            forecast: Forecast::default(),
             spend_per_month: SpendPerMonth {
                 months: HashMap::new(),
             },
             base_currency: Currency::CHF,
+            resolution,
+            stale: false,
+            unmatched: vec![],
         }
     }
 }
 
-async fn fetch_rate(
+/// Tags the transfers between the accounts of `portfolio`, see
+/// [`processing::match_transfers`].
+///
+/// The rates of the currencies of the bank ledgers are fetched first, so the matching does not
+/// depend on what other requests happened to cache. If they cannot be fetched, only the
+/// transfers within a currency are matched. Returns why records were left unmatched.
+pub(crate) async fn match_transfers(
+    cache: &CacheState,
+    portfolio: &mut Portfolio,
+) -> Result<Vec<String>, AppError> {
+    let base_currency = portfolio.base_currency;
+    let base = Symbol::Currency(base_currency);
+    let ledgers = portfolio
+        .accounts
+        .values()
+        .flat_map(|account| &account.ledgers)
+        .filter(|ledger| ledger.kind == LedgerKind::Bank && ledger.symbol != base_currency);
+
+    let mut unmatched = vec![];
+    let mut rates = HashMap::new();
+    let mut snapshot = cache.lock().await.snapshot();
+    let mut dates = first_dates(ledgers).into_iter().collect::<Vec<_>>();
+    dates.sort_by_key(|(symbol, _)| symbol.to_string());
+    for (symbol, since) in dates {
+        match snapshot.get(&symbol, &base, since).await {
+            Ok(pair) => {
+                rates.insert(symbol, pair.clone());
+            }
+            Err(error) => unmatched.push(format!(
+                "The rates from {symbol} to {base_currency} could not be fetched: {error:#}"
+            )),
+        }
+    }
+    cache.lock().await.merge(snapshot)?;
+
+    unmatched.extend(processing::match_transfers(
+        &mut portfolio.accounts,
+        &base,
+        &rates,
+    ));
+    Ok(unmatched)
+}
+
+/// The day of the first record of each symbol in `ledgers`, which its rates are needed from.
+fn first_dates<'a>(
+    ledgers: impl IntoIterator<Item = &'a ExtendedLedger>,
+) -> HashMap<Symbol, NaiveDate> {
+    let mut dates = HashMap::<Symbol, NaiveDate>::new();
+    for ledger in ledgers {
+        let Some(first) = ledger.records.iter().map(|record| record.date).min() else {
+            continue;
+        };
+        dates
+            .entry(ledger.symbol.clone())
+            .and_modify(|date| *date = first.min(*date))
+            .or_insert(first);
+    }
+    dates
+}
+
+/// Gets the rates of `symbol` in the base currency from `since`, usually the first transaction.
+pub(super) async fn fetch_rate(
     cache: CacheState,
     symbol: &Symbol,
     base_currency: Currency,
-) -> Result<BTreeMap<NaiveDate, f64>, AppError> {
-    let mut cache = cache.lock().await;
-    let rate = cache.get(symbol, &Symbol::Currency(base_currency)).await?;
-    Ok(rate.rates.clone())
+    since: NaiveDate,
+) -> Result<Pair, AppError> {
+    let mut pairs = fetch_rates(&cache, [(symbol.clone(), since)], base_currency).await?;
+    Ok(pairs.remove(symbol).unwrap())
+}
+
+/// Gets the rates of each of `symbols` in the base currency from the date given with it.
+///
+/// The rates are fetched on a snapshot of the cache, so that other requests are not blocked
+/// while a provider is asked.
+pub(super) async fn fetch_rates(
+    cache: &CacheState,
+    symbols: impl IntoIterator<Item = (Symbol, NaiveDate)>,
+    base_currency: Currency,
+) -> Result<HashMap<Symbol, Pair>, AppError> {
+    let mut snapshot = cache.lock().await.snapshot();
+    let mut pairs = HashMap::new();
+    for (symbol, since) in symbols {
+        if let Entry::Vacant(entry) = pairs.entry(symbol) {
+            let pair = snapshot
+                .get(entry.key(), &Symbol::Currency(base_currency), since)
+                .await?;
+            entry.insert(pair.clone());
+        }
+    }
+    cache.lock().await.merge(snapshot)?;
+    Ok(pairs)
+}
+
+/// The rate of each of the consecutive `dates`, as [`rate_for_date`] gets them.
+fn daily_rates(rates: &BTreeMap<NaiveDate, f64>, dates: &[NaiveDate]) -> Vec<f64> {
+    let Some(first) = dates.first() else {
+        return vec![];
+    };
+    let mut rate = rate_for_date(rates, first);
+    let mut changes = rates.range(first..).peekable();
+    dates
+        .iter()
+        .map(|date| {
+            while let Some((_, changed)) = changes.next_if(|(day, _)| *day <= date) {
+                rate = *changed;
+            }
+            rate
+        })
+        .collect()
+}
+
+/// The sum of the amounts of the records on each of the consecutive `dates`. Records outside of
+/// them are left out.
+fn daily_sums(records: &[ExtendedLedgerRecord], dates: &[NaiveDate]) -> Vec<Money> {
+    let mut sums = vec![Money::ZERO; dates.len()];
+    let Some(first) = dates.first() else {
+        return sums;
+    };
+    for record in records {
+        let day = usize::try_from((record.date - *first).num_days()).ok();
+        if let Some(sum) = day.and_then(|day| sums.get_mut(day)) {
+            *sum += record.amount;
+        }
+    }
+    sums
 }
 
 /// Calculates a linear regression with a known mean.
@@ -357,3 +729,220 @@ pub fn linear_regression(xs: &[f64], ys: &[f64]) -> anyhow::Result<(f64, f64)> {
 
     lin_reg(xs.iter().copied().zip(ys.iter().copied()), x_mean, y_mean)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        path::PathBuf,
+        sync::Arc,
+    };
+
+    use axum::{async_trait, http::StatusCode, response::IntoResponse};
+    use chrono::{Datelike, Days, NaiveDate, Weekday};
+    use tokio::sync::Mutex;
+
+    use crate::{
+        banks::LedgerKind,
+        fx::{
+            provider::{ProviderKind, Providers, RateProvider},
+            HistoryCache, Symbol,
+        },
+        money::Money,
+        realms::portfolio::{
+            adapter::{Adapter, Production},
+            state::{Owner, Portfolio},
+        },
+        state::CacheState,
+    };
+
+    use super::{
+        fetch_rate, get_date_series, periods, rate_for_date, summary, DataQuery,
+        PortfolioLedgerData, Resolution,
+    };
+
+    /// Rates on weekdays which differ from day to day and from symbol to symbol.
+    #[derive(Debug)]
+    struct Weekdays;
+
+    #[async_trait]
+    impl RateProvider for Weekdays {
+        fn kind(&self) -> ProviderKind {
+            ProviderKind::Manual
+        }
+
+        async fn rates(
+            &self,
+            from: &Symbol,
+            _: &Symbol,
+            start: NaiveDate,
+            end: NaiveDate,
+        ) -> anyhow::Result<BTreeMap<NaiveDate, f64>> {
+            let offset = from.to_string().len() as f64;
+            Ok(start
+                .iter_days()
+                .take_while(|d| *d <= end)
+                .filter(|d| !matches!(d.weekday(), Weekday::Sat | Weekday::Sun))
+                .map(|d| (d, offset + f64::from(d.ordinal()) / 1000.0))
+                .collect())
+        }
+    }
+
+    /// The balances as they were computed before, by summing the records of every single day.
+    async fn per_day(portfolio: &Portfolio, cache: CacheState) -> HashMap<String, Vec<f64>> {
+        let dates = get_date_series(&portfolio.accounts);
+        let base = portfolio.base_currency;
+        let mut balances = HashMap::new();
+        for account in portfolio.accounts.values() {
+            let account_currency = account
+                .ledgers
+                .iter()
+                .find_map(|l| (l.kind == LedgerKind::Bank).then(|| l.symbol.currency()));
+            let mut account_balances = vec![Money::ZERO; dates.len()];
+            for ledger in &account.ledgers {
+                let mut rates = None;
+                let mut ticker_to_base = None;
+                if ledger.symbol != base {
+                    let quote = (ledger.kind == LedgerKind::Stock)
+                        .then(|| ledger.quote_currency.or(account_currency).unwrap())
+                        .filter(|c| *c != base);
+                    if let Some(quote) = quote.map(Symbol::Currency) {
+                        let pair = fetch_rate(cache.clone(), &quote, base, dates[0]);
+                        ticker_to_base = Some(pair.await.unwrap().rates);
+                    }
+                    let pair = fetch_rate(cache.clone(), &ledger.symbol, base, dates[0]);
+                    rates = Some(pair.await.unwrap().rates);
+                }
+                let mut worth = Money::ZERO;
+                for (date, balance) in dates.iter().zip(account_balances.iter_mut()) {
+                    worth += ledger
+                        .records
+                        .iter()
+                        .filter(|v| &v.date == date)
+                        .map(|v| v.amount)
+                        .sum::<Money>();
+                    let rate = rates.as_ref().map_or(1.0, |r| rate_for_date(r, date));
+                    let rate_currency = ticker_to_base
+                        .as_ref()
+                        .map_or(1.0, |r| rate_for_date(r, date));
+                    *balance += worth.convert(rate * rate_currency, base).unwrap();
+                }
+            }
+            let skip = account_balances.len().saturating_sub(3 * 365);
+            let series = account_balances[skip..].iter().map(|b| b.to_f64());
+            balances.insert(account.id.clone(), series.collect());
+        }
+        balances
+    }
+
+    #[tokio::test]
+    async fn same_balances_as_per_day() {
+        let portfolio = Production::new(PathBuf::from("portfolio-test"))
+            .load(Owner::new("123456789".to_string()))
+            .unwrap();
+        let cache: CacheState = Arc::new(Mutex::new(HistoryCache::with_providers(Providers::new(
+            vec![Box::new(Weekdays)],
+        ))));
+
+        let expected = per_day(&portfolio, cache.clone()).await;
+        let response = summary(portfolio.clone(), &cache, &DataQuery::default())
+            .await
+            .unwrap();
+        let balances = response
+            .total_balance
+            .balances
+            .into_iter()
+            .map(|ledger| (ledger.id, ledger.series))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(balances, expected);
+        assert!(!response.stale);
+
+        let forecast = response.forecast;
+        assert_eq!(forecast.median.len(), 12);
+        assert_eq!(forecast.timestamps.len(), 12);
+        assert!((0..12).all(|i| forecast.low[i] <= forecast.median[i]));
+        assert!((0..12).all(|i| forecast.median[i] <= forecast.high[i]));
+
+        // Windows shorter than the trend and outside of the records are not an error.
+        let first = get_date_series(&portfolio.accounts)[0];
+        for (from, to) in [(None, first + Days::new(10)), (None, first - Days::new(1))] {
+            let query = DataQuery {
+                from,
+                to: Some(to),
+                ..Default::default()
+            };
+            summary(portfolio.clone(), &cache, &query).await.unwrap();
+        }
+
+        let query = DataQuery {
+            horizon: Some(100_000_000),
+            ..Default::default()
+        };
+        let empty = Portfolio {
+            accounts: HashMap::new(),
+            ..Production::new(PathBuf::from("portfolio-test"))
+                .load(Owner::new("123456789".to_string()))
+                .unwrap()
+        };
+        let error = summary(empty, &cache, &query).await.unwrap_err();
+        assert_eq!(error.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn resample_by_period() {
+        let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        let dates = date(1, 30).iter_days().take(70).collect::<Vec<_>>();
+        let series = (0..70).map(f64::from).collect::<Vec<_>>();
+        let resample = |resolution| {
+            let periods = periods(&dates, resolution);
+            let data =
+                PortfolioLedgerData::resampled(String::new(), String::new(), &series, &periods);
+            (periods, data.series, data.average)
+        };
+
+        // January ends after two days, February after 29 more and March after 31 more.
+        assert_eq!(
+            resample(Resolution::Month),
+            (
+                vec![0..2, 2..31, 31..62, 62..70],
+                vec![1.0, 30.0, 61.0, 69.0],
+                vec![0.5, 16.0, 46.0, 65.5]
+            )
+        );
+        assert_eq!(resample(Resolution::Quarter).0, vec![0..62, 62..70]);
+        // The 30th of January 2024 is a Tuesday.
+        assert_eq!(resample(Resolution::Week).0[..2], [0..6, 6..13]);
+        assert_eq!(resample(Resolution::Day).1, series);
+
+        let query = |from, to| DataQuery {
+            from,
+            to,
+            resolution: Resolution::Day,
+            horizon: None,
+        };
+        assert_eq!(query(None, None).window(&dates).unwrap(), 0..70);
+        assert_eq!(
+            query(Some(date(2, 1)), Some(date(2, 29)))
+                .window(&dates)
+                .unwrap(),
+            2..31
+        );
+        assert_eq!(
+            query(Some(date(1, 1)), Some(date(12, 31)))
+                .window(&dates)
+                .unwrap(),
+            0..70
+        );
+        assert!(query(Some(date(3, 1)), Some(date(2, 1)))
+            .window(&dates)
+            .is_err());
+        assert_eq!(query(None, Some(date(1, 1))).window(&dates).unwrap(), 0..0);
+        assert_eq!(query(Some(date(5, 1)), None).window(&dates).unwrap(), 0..0);
+
+        // The trend of short series is continued after their last day.
+        let (next, prediction) = super::predict(&dates[..3], &[1.0, 2.0, 3.0]).unwrap();
+        assert_eq!(next[0], date(2, 2));
+        assert!((prediction[0] - 4.0).abs() < 1e-9);
+        assert!(super::predict(&dates[..1], &[1.0]).unwrap().1.is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use axum::{debug_handler, extract::State, Json};
+use chrono::{Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    banks::LedgerKind,
+    error::AppError,
+    fx::{Currency, Symbol},
+    handler::auth::user::User,
+    realms::portfolio::holdings::Position,
+    state::{CacheState, PortfolioAdapter},
+};
+
+use super::get::{fetch_rate, rate_for_date};
+
+/// Reports the stocks held in each account with their cost basis and gains.
+#[debug_handler(state = crate::state::AppState)]
+pub async fn handler(
+    State(adapter): State<PortfolioAdapter>,
+    State(cache): State<CacheState>,
+    user: User,
+) -> Result<Json<HoldingsResponse>, AppError> {
+    let portfolio = user.portfolio(adapter)?;
+    let base_currency = portfolio.base_currency;
+    let today = Utc::now().naive_utc().date();
+
+    let mut holdings = vec![];
+    let mut stale = false;
+    for account in portfolio.accounts.values() {
+        // Stocks without a known quote currency are assumed to trade in the currency of the
+        // account's cash.
+        let account_currency = account
+            .ledgers
+            .iter()
+            .find_map(|l| (l.kind == LedgerKind::Bank).then(|| l.symbol.currency()));
+
+        for ledger in account
+            .ledgers
+            .iter()
+            .filter(|l| l.kind == LedgerKind::Stock)
+        {
+            let quote_currency = ledger
+                .quote_currency
+                .or(account_currency)
+                .with_context(|| format!("No quote currency was found for {}", ledger.symbol))?;
+            let position = Position::new(&ledger.records);
+            let since = ledger.records.iter().map(|r| r.date).min().unwrap_or(today);
+
+            let prices = fetch_rate(cache.clone(), &ledger.symbol, base_currency, since).await?;
+            stale |= prices.stale;
+            let price = prices
+                .rates
+                .last_key_value()
+                .map_or(0.0, |(_, price)| *price);
+            let quote_to_base = if quote_currency != base_currency {
+                let pair = fetch_rate(
+                    cache.clone(),
+                    &Symbol::Currency(quote_currency),
+                    base_currency,
+                    since,
+                )
+                .await?;
+                stale |= pair.stale;
+                Some(pair.rates)
+            } else {
+                None
+            };
+            let rate = |date| {
+                quote_to_base
+                    .as_ref()
+                    .map_or(1.0, |rates| rate_for_date(rates, date))
+            };
+
+            let mut realized_gains = BTreeMap::<i32, f64>::new();
+            for (date, gain) in &position.realized {
+                *realized_gains.entry(date.year()).or_default() += gain * rate(date);
+            }
+
+            let market_value = position.quantity * price;
+            holdings.push(Holding {
+                account: account.id.clone(),
+                symbol: ledger.symbol.to_string(),
+                quote_currency,
+                quantity: position.quantity,
+                average_cost_basis: position.average_cost_basis,
+                fifo_cost_basis: position.fifo_cost_basis,
+                price,
+                value: market_value * rate(&today),
+                unrealized_gain: (market_value - position.fifo_cost_basis) * rate(&today),
+                realized_gains,
+            });
+        }
+    }
+    holdings.sort_by(|a, b| (&a.account, &a.symbol).cmp(&(&b.account, &b.symbol)));
+
+    Ok(Json(HoldingsResponse {
+        base_currency,
+        holdings,
+        stale,
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct HoldingsResponse {
+    pub base_currency: Currency,
+    pub holdings: Vec<Holding>,
+    /// Some prices or rates could not be updated, so the values might be off.
+    pub stale: bool,
+}
+
+/// A stock held in an account.
+///
+/// Prices and cost bases are in the quote currency of the stock, values and gains in the base
+/// currency of the portfolio.
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Holding {
+    pub account: String,
+    pub symbol: String,
+    pub quote_currency: Currency,
+    pub quantity: f64,
+    pub average_cost_basis: f64,
+    pub fifo_cost_basis: f64,
+    /// The latest price of a single share.
+    pub price: f64,
+    pub value: f64,
+    /// The gain of the held shares against their FIFO cost basis at today's exchange rate.
+    pub unrealized_gain: f64,
+    /// The gains of the sells by year, at the exchange rate of each sell.
+    pub realized_gains: BTreeMap<i32, f64>,
+}
@@ -0,0 +1,308 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use axum::{
+    debug_handler,
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Datelike, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    banks::LedgerKind,
+    error::AppError,
+    fx::{Currency, Symbol},
+    handler::auth::user::User,
+    money::Money,
+    realms::portfolio::{
+        performance::{external_flows, time_weighted_return, xirr},
+        state::Account,
+    },
+    state::{CacheState, PortfolioAdapter},
+};
+
+use super::get::{fetch_rate, get_date_series, match_transfers, rate_for_date};
+
+/// Reports the time- and money-weighted returns of each account and the whole portfolio.
+#[debug_handler(state = crate::state::AppState)]
+pub async fn handler(
+    State(adapter): State<PortfolioAdapter>,
+    State(cache): State<CacheState>,
+    Query(query): Query<PerformanceQuery>,
+    user: User,
+) -> Result<Json<PerformanceResponse>, AppError> {
+    let mut portfolio = user.portfolio(adapter)?;
+    let base_currency = portfolio.base_currency;
+    // Transfers between the owner's accounts only move money within the portfolio.
+    let unmatched = match_transfers(&cache, &mut portfolio).await?;
+
+    let dates = get_date_series(&portfolio.accounts);
+    let (Some(first), Some(last)) = (dates.first().copied(), dates.last().copied()) else {
+        return Ok(Json(PerformanceResponse {
+            base_currency,
+            period: query.period,
+            from: None,
+            to: None,
+            accounts: vec![],
+            total: None,
+            stale: false,
+            unmatched,
+        }));
+    };
+    let from = query.period.start(last).max(first);
+    let start = (from - first).num_days() as usize;
+
+    let mut accounts = vec![];
+    let mut total = Series::new(dates.len());
+    for account in portfolio.accounts.values() {
+        let series = Series::of(account, &dates, cache.clone(), base_currency).await?;
+        total.stale |= series.stale;
+        total.add(&series);
+        accounts.push(series.performance(&account.id, &account.name, &dates, start, false));
+    }
+    accounts.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(Json(PerformanceResponse {
+        base_currency,
+        period: query.period,
+        from: Some(from),
+        to: Some(last),
+        accounts,
+        total: Some(total.performance("total", "Total", &dates, start, true)),
+        stale: total.stale,
+        unmatched,
+    }))
+}
+
+/// The daily values and external flows of an account in the base currency.
+struct Series {
+    values: Vec<f64>,
+    flows: Vec<f64>,
+    /// The flows without the transfers to and from other accounts of the portfolio.
+    portfolio_flows: Vec<f64>,
+    /// Some rates could not be updated.
+    stale: bool,
+}
+
+impl Series {
+    fn new(len: usize) -> Self {
+        Self {
+            values: vec![0.0; len],
+            flows: vec![0.0; len],
+            portfolio_flows: vec![0.0; len],
+            stale: false,
+        }
+    }
+
+    async fn of(
+        account: &Account,
+        dates: &[NaiveDate],
+        cache: CacheState,
+        base_currency: Currency,
+    ) -> Result<Self, AppError> {
+        let mut series = Self::new(dates.len());
+        let external = external_flows(account)
+            .into_iter()
+            .map(|(ledger, record)| (&ledger.symbol, record.id.as_str()))
+            .collect::<HashSet<_>>();
+        // Stocks without a known quote currency are assumed to trade in the currency of the
+        // account's cash.
+        let account_currency = account
+            .ledgers
+            .iter()
+            .find_map(|l| (l.kind == LedgerKind::Bank).then(|| l.symbol.currency()));
+
+        for ledger in &account.ledgers {
+            let rates = if ledger.symbol != base_currency {
+                let pair =
+                    fetch_rate(cache.clone(), &ledger.symbol, base_currency, dates[0]).await?;
+                series.stale |= pair.stale;
+                Some(pair.rates)
+            } else {
+                None
+            };
+            let quote_currency = if ledger.kind == LedgerKind::Stock {
+                Some(
+                    ledger
+                        .quote_currency
+                        .or(account_currency)
+                        .with_context(|| {
+                            format!("No quote currency was found for {}", ledger.symbol)
+                        })?,
+                )
+            } else {
+                None
+            };
+            let quote_to_base = match quote_currency {
+                Some(quote_currency) if quote_currency != base_currency => {
+                    let pair = fetch_rate(
+                        cache.clone(),
+                        &Symbol::Currency(quote_currency),
+                        base_currency,
+                        dates[0],
+                    )
+                    .await?;
+                    series.stale |= pair.stale;
+                    Some(pair.rates)
+                }
+                _ => None,
+            };
+            let rate = |date: &NaiveDate| {
+                rates.as_ref().map_or(1.0, |r| rate_for_date(r, date))
+                    * quote_to_base
+                        .as_ref()
+                        .map_or(1.0, |r| rate_for_date(r, date))
+            };
+
+            let mut changes = vec![Money::ZERO; dates.len()];
+            for record in &ledger.records {
+                let index = (record.date - dates[0]).num_days() as usize;
+                let Some(change) = changes.get_mut(index) else {
+                    continue;
+                };
+                *change += record.amount;
+                if external.contains(&(&ledger.symbol, record.id.as_str())) {
+                    let amount = record
+                        .amount
+                        .convert(rate(&record.date), base_currency)?
+                        .to_f64();
+                    series.flows[index] += amount;
+                    if record.transfer.is_none() {
+                        series.portfolio_flows[index] += amount;
+                    }
+                }
+            }
+
+            let mut balance = Money::ZERO;
+            for ((value, change), date) in series.values.iter_mut().zip(changes).zip(dates) {
+                balance += change;
+                *value += balance.convert(rate(date), base_currency)?.to_f64();
+            }
+        }
+
+        Ok(series)
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (total, value) in self.values.iter_mut().zip(&other.values) {
+            *total += value;
+        }
+        for (total, flow) in self.portfolio_flows.iter_mut().zip(&other.portfolio_flows) {
+            *total += flow;
+        }
+    }
+
+    /// The returns from the day with the index `start` until the last day.
+    fn performance(
+        &self,
+        id: &str,
+        name: &str,
+        dates: &[NaiveDate],
+        start: usize,
+        portfolio: bool,
+    ) -> Performance {
+        let flows = if portfolio {
+            &self.portfolio_flows[start..]
+        } else {
+            &self.flows[start..]
+        };
+        let values = &self.values[start..];
+        let start_value = start
+            .checked_sub(1)
+            .map_or(0.0, |previous| self.values[previous]);
+        let end_value = values.last().copied().unwrap_or_default();
+
+        // The investor pays in the start value and every deposit and gets the end value.
+        let mut cash_flows = vec![(dates[start], -start_value)];
+        cash_flows.extend(
+            dates[start..]
+                .iter()
+                .zip(flows)
+                .filter(|(_, flow)| **flow != 0.0)
+                .map(|(date, flow)| (*date, -flow)),
+        );
+        cash_flows.extend(dates.last().map(|date| (*date, end_value)));
+
+        Performance {
+            id: id.to_string(),
+            name: name.to_string(),
+            start_value,
+            end_value,
+            net_flows: flows.iter().sum(),
+            time_weighted_return: time_weighted_return(start_value, values, flows),
+            money_weighted_return: xirr(&cash_flows),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PerformanceQuery {
+    #[serde(default)]
+    period: Period,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum Period {
+    #[serde(rename = "ytd")]
+    YearToDate,
+    #[serde(rename = "1y")]
+    OneYear,
+    #[serde(rename = "3y")]
+    ThreeYears,
+    #[serde(rename = "5y")]
+    FiveYears,
+    #[default]
+    #[serde(rename = "inception")]
+    Inception,
+}
+
+impl Period {
+    /// The first day of the period ending on `end`.
+    fn start(self, end: NaiveDate) -> NaiveDate {
+        let years = |years: u32| end - Months::new(12 * years);
+        match self {
+            Period::YearToDate => NaiveDate::from_ymd_opt(end.year(), 1, 1).unwrap_or(end),
+            Period::OneYear => years(1),
+            Period::ThreeYears => years(3),
+            Period::FiveYears => years(5),
+            Period::Inception => NaiveDate::MIN,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PerformanceResponse {
+    pub base_currency: Currency,
+    pub period: Period,
+    #[ts(type = "number")]
+    pub from: Option<NaiveDate>,
+    #[ts(type = "number")]
+    pub to: Option<NaiveDate>,
+    pub accounts: Vec<Performance>,
+    pub total: Option<Performance>,
+    /// Some prices or rates could not be updated, so the latest values might be off.
+    pub stale: bool,
+    /// Why some records could not be matched as transfers between the accounts.
+    pub unmatched: Vec<String>,
+}
+
+/// The returns of an account over a period, in the base currency.
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Performance {
+    pub id: String,
+    pub name: String,
+    /// The value at the end of the day before the period.
+    pub start_value: f64,
+    pub end_value: f64,
+    /// The deposits minus the withdrawals during the period.
+    pub net_flows: f64,
+    /// The growth over the whole period, regardless of the deposits and withdrawals.
+    pub time_weighted_return: f64,
+    /// The annual rate of return of the money invested (XIRR).
+    pub money_weighted_return: Option<f64>,
+}
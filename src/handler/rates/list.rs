@@ -0,0 +1,56 @@
+use axum::{debug_handler, extract::State, Json};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    error::AppError,
+    fx::{Derivation, Symbol},
+    handler::auth::user::User,
+    state::CacheState,
+};
+
+/// Lists the cached rate pairs and how their rates were obtained.
+#[debug_handler(state = crate::state::AppState)]
+pub async fn handler(
+    State(cache): State<CacheState>,
+    _user: User,
+) -> Result<Json<ListRatesResponse>, AppError> {
+    let cache = cache.lock().await;
+    let mut pairs = cache
+        .fx
+        .values()
+        .map(|pair| RatePair {
+            from: pair.from.clone(),
+            to: pair.to.clone(),
+            derivation: pair.derivation,
+            first: pair.rates.first_key_value().map(|(date, _)| *date),
+            last: pair.rates.last_key_value().map(|(date, _)| *date),
+            stale: pair.stale,
+            error: pair.error.clone(),
+        })
+        .collect::<Vec<_>>();
+    pairs.sort_by_key(|p| (p.from.to_string(), p.to.to_string()));
+    Ok(Json(ListRatesResponse { pairs }))
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ListRatesResponse {
+    pub pairs: Vec<RatePair>,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct RatePair {
+    pub from: Symbol,
+    pub to: Symbol,
+    pub derivation: Derivation,
+    #[ts(type = "number")]
+    pub first: Option<NaiveDate>,
+    #[ts(type = "number")]
+    pub last: Option<NaiveDate>,
+    pub stale: bool,
+    /// Why the rates could not be updated, if they are stale.
+    pub error: Option<String>,
+}
@@ -0,0 +1,68 @@
+use axum::{debug_handler, extract::State, Json};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    banks::ExtendedLedgerRecord,
+    error::{AppError, BadRequest},
+    fx::Symbol,
+    handler::auth::user::User,
+    realms::portfolio::rules::{Rule, Rules},
+    state::PortfolioAdapter,
+};
+
+/// Evaluates a single rule against all the existing records of the portfolio without storing it.
+#[debug_handler(state = crate::state::AppState)]
+pub async fn handler(
+    State(adapter): State<PortfolioAdapter>,
+    user: User,
+    Json(rule): Json<Rule>,
+) -> Result<Json<EvaluateRuleResponse>, AppError> {
+    let portfolio = user.portfolio(adapter)?;
+    let rules = Rules::new(std::slice::from_ref(&rule))
+        .map_err(|error| BadRequest(format!("{error:#}")))?;
+
+    let mut matches = vec![];
+    for (id, account) in &portfolio.accounts {
+        for ledger in &account.ledgers {
+            for record in &ledger.records {
+                if rules
+                    .categorize(
+                        id,
+                        &record.date,
+                        record.amount,
+                        &record.original_description,
+                    )
+                    .is_some()
+                {
+                    matches.push(RuleMatch {
+                        account: id.clone(),
+                        symbol: ledger.symbol.clone(),
+                        record: ExtendedLedgerRecord {
+                            category: rule.category.clone(),
+                            ..record.clone()
+                        },
+                    });
+                }
+            }
+        }
+    }
+    matches.sort_by_key(|m| std::cmp::Reverse(m.record.date));
+
+    Ok(Json(EvaluateRuleResponse { matches }))
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct EvaluateRuleResponse {
+    pub matches: Vec<RuleMatch>,
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct RuleMatch {
+    pub account: String,
+    pub symbol: Symbol,
+    /// The record as it would look like with the rule applied.
+    pub record: ExtendedLedgerRecord,
+}
@@ -0,0 +1,25 @@
+use axum::{debug_handler, extract::State, Json};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    error::AppError, handler::auth::user::User, realms::portfolio::rules::Rule,
+    state::PortfolioAdapter,
+};
+
+#[debug_handler(state = crate::state::AppState)]
+pub async fn handler(
+    State(adapter): State<PortfolioAdapter>,
+    user: User,
+) -> Result<Json<ListRulesResponse>, AppError> {
+    let portfolio = user.portfolio(adapter)?;
+    Ok(Json(ListRulesResponse {
+        rules: portfolio.rules,
+    }))
+}
+
+#[derive(Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ListRulesResponse {
+    pub rules: Vec<Rule>,
+}
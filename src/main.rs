@@ -1,9 +1,13 @@
+// The derives of the currency enum nest deeper than the default limit.
+#![recursion_limit = "256"]
+
 mod banks;
 mod cli;
 mod error;
 pub mod fx;
 mod handler;
 mod import;
+pub mod money;
 pub mod processing;
 pub mod realms;
 mod state;
@@ -11,7 +15,7 @@ mod state;
 use anyhow::Result;
 use axum::http::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
 use axum::http::Method;
-use axum::routing::{get, post, put};
+use axum::routing::{get, patch, post, put};
 use axum::Router;
 use clap::Parser;
 use reqwest::header::ACCESS_CONTROL_ALLOW_CREDENTIALS;
@@ -27,6 +31,7 @@ async fn main() -> anyhow::Result<()> {
 
     match cli::Args::parse().command {
         cli::Command::Serve(_args) => serve().await?,
+        cli::Command::Migrate(args) => migrate(args)?,
     }
 
     Ok(())
@@ -40,8 +45,15 @@ async fn serve() -> anyhow::Result<()> {
             &base_path,
             Router::<AppState>::new()
                 .route("/data", get(handler::portfolio::get::handler))
+                .route("/holdings", get(handler::portfolio::holdings::handler))
+                .route("/performance", get(handler::portfolio::performance::handler))
+                .route("/rates", get(handler::rates::list::handler))
                 .route("/ledgers", get(handler::ledger::list::handler))
                 .route("/ledgers/summary", get(handler::ledger::summary::handler))
+                .route("/ledgers/income", get(handler::ledger::income::handler))
+                .route("/rules", get(handler::rules::get::handler))
+                .route("/rules/evaluate", post(handler::rules::evaluate::handler))
+                .route("/detect", post(handler::ledger::detect::handler))
                 .nest(
                     "/ledger/:id",
                     Router::<AppState>::new()
@@ -52,6 +64,19 @@ async fn serve() -> anyhow::Result<()> {
                                 .put(handler::ledger::update::handler)
                                 .delete(handler::ledger::delete::handler),
                         )
+                        .route(
+                            "/reconciliation",
+                            get(handler::ledger::reconciliation::handler),
+                        )
+                        .nest(
+                            "/records",
+                            Router::<AppState>::new()
+                                .route("/", patch(handler::ledger::records::patch::handler))
+                                .route(
+                                    "/:recordId",
+                                    patch(handler::ledger::records::patch_single::handler),
+                                ),
+                        )
                         .nest(
                             "/files",
                             Router::<AppState>::new()
@@ -60,6 +85,8 @@ async fn serve() -> anyhow::Result<()> {
                                     get(handler::ledger::files::get::handler)
                                         .post(handler::ledger::files::post::handler),
                                 )
+                                .route("/preview", post(handler::ledger::files::preview::handler))
+                                .route("/confirm", post(handler::ledger::files::confirm::handler))
                                 .nest(
                                     "/:fileName",
                                     Router::<AppState>::new().route(
@@ -99,6 +126,7 @@ async fn serve() -> anyhow::Result<()> {
                     Method::GET,
                     Method::POST,
                     Method::PUT,
+                    Method::PATCH,
                     Method::DELETE,
                     Method::OPTIONS,
                 ])
@@ -121,6 +149,15 @@ async fn serve() -> anyhow::Result<()> {
     Ok(())
 }
 
+fn migrate(args: cli::Migrate) -> Result<()> {
+    let database = realms::portfolio::adapter::sqlite::Sqlite::open(&args.database)?;
+    for owner in database.import(&args.from)? {
+        println!("imported the portfolio of {}", &*owner);
+    }
+
+    Ok(())
+}
+
 fn init() -> Result<()> {
     std::fs::create_dir_all("portfolio/ledgers")?;
     std::fs::create_dir_all("portfolio/fx")?;
@@ -0,0 +1,269 @@
+use std::{
+    fmt::{Debug, Display},
+    iter::Sum,
+    ops::{Add, AddAssign, Neg, Sub, SubAssign},
+    str::FromStr,
+};
+
+use anyhow::Context;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+use ts_rs::TS;
+
+use crate::fx::Currency;
+
+/// An exact decimal amount of money or a quantity of shares.
+///
+/// Amounts are only rounded when they are converted to another currency. They are serialized as
+/// plain JSON numbers.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, TS)]
+pub struct Money(#[ts(type = "number")] Decimal);
+
+impl Money {
+    pub const ZERO: Money = Money(Decimal::ZERO);
+
+    pub fn new(value: Decimal) -> Self {
+        Self(value)
+    }
+
+    /// Takes the shortest decimal which reads back as the same `f64`, so 0.1 is exactly a tenth.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        Decimal::from_str(&value.to_string()).ok().map(Self)
+    }
+
+    /// The closest `f64`, e.g. for statistics.
+    pub fn to_f64(self) -> f64 {
+        self.0.to_string().parse().unwrap_or_default()
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn is_sign_negative(self) -> bool {
+        self.0.is_sign_negative() && !self.0.is_zero()
+    }
+
+    /// Drops trailing zeros, so equal amounts are written the same way however they were read.
+    pub fn normalize(self) -> Self {
+        Self(self.0.normalize())
+    }
+
+    pub fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    /// Rounds half away from zero to the minor unit of `currency`, e.g. to cents.
+    pub fn round(self, currency: Currency) -> Self {
+        Self(self.0.round_dp_with_strategy(
+            currency.minor_units(),
+            RoundingStrategy::MidpointAwayFromZero,
+        ))
+    }
+
+    /// Converts the amount with an exchange rate and rounds it to the minor unit of `currency`,
+    /// the currency it is converted to.
+    pub fn convert(self, rate: f64, currency: Currency) -> anyhow::Result<Self> {
+        let converted = self
+            .scale(rate)
+            .with_context(|| format!("{self} could not be converted at a rate of {rate}"))?;
+        Ok(converted.round(currency))
+    }
+
+    /// Multiplies the amount without rounding, e.g. a quantity of shares by a split ratio.
+    ///
+    /// Returns `None` if the factor is not a finite number or the product is out of range.
+    pub fn scale(self, factor: f64) -> Option<Self> {
+        self.0.checked_mul(Money::from_f64(factor)?.0).map(Self)
+    }
+}
+
+impl From<Decimal> for Money {
+    fn from(value: Decimal) -> Self {
+        Self(value)
+    }
+}
+
+impl From<i64> for Money {
+    fn from(value: i64) -> Self {
+        Self(Decimal::from(value))
+    }
+}
+
+impl FromStr for Money {
+    type Err = rust_decimal::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Decimal::from_str(s)
+            .or_else(|_| Decimal::from_scientific(s))
+            .map(Self)
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl Debug for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Self::Output {
+        Self(-self.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0;
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MoneyVisitor;
+
+        impl Visitor<'_> for MoneyVisitor {
+            type Value = Money;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a number or a decimal string")
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(Money::from(v))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(Money(Decimal::from(v)))
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<Self::Value, E> {
+                Money::from_f64(v).ok_or_else(|| E::custom(format!("{v} is not a valid amount")))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Money::from_str(v).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fx::Currency;
+
+    use super::Money;
+
+    fn money(value: &str) -> Money {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn sum_without_drift() {
+        let sum = std::iter::repeat_n(money("0.1"), 10).sum::<Money>();
+        assert_eq!(sum, Money::from(1));
+        assert_eq!(money("0.1") + money("0.2"), money("0.3"));
+        assert_eq!(
+            Money::from_f64(0.1).unwrap() + Money::from_f64(0.2).unwrap(),
+            money("0.3")
+        );
+    }
+
+    #[test]
+    fn round_to_minor_units() {
+        assert_eq!(money("1.005").round(Currency::CHF), money("1.01"));
+        assert_eq!(money("-1.005").round(Currency::CHF), money("-1.01"));
+        assert_eq!(money("1234.5").round(Currency::JPY), money("1235"));
+        assert_eq!(money("1.23456").round(Currency::KWD), money("1.235"));
+
+        // 100 CHF at 1.0675 EUR per CHF.
+        assert_eq!(
+            Money::from(100).convert(1.0675, Currency::EUR).unwrap(),
+            money("106.75")
+        );
+        assert_eq!(
+            money("33.33").convert(0.9, Currency::EUR).unwrap(),
+            money("30.00")
+        );
+        assert_eq!(money("10.5").scale(4.0), Some(money("42")));
+
+        // Invalid factors are not taken as zero.
+        assert_eq!(money("10.5").scale(f64::NAN), None);
+        assert_eq!(money("10.5").scale(f64::INFINITY), None);
+        assert!(money("10.5").convert(f64::NAN, Currency::EUR).is_err());
+    }
+
+    #[test]
+    fn serialize_as_number() {
+        let json = serde_json::to_string(&money("-62.75")).unwrap();
+        assert_eq!(json, "-62.75");
+        assert_eq!(
+            serde_json::from_str::<Money>(&json).unwrap(),
+            money("-62.75")
+        );
+        assert_eq!(
+            serde_json::from_str::<Money>("3688").unwrap(),
+            Money::from(3688)
+        );
+        assert_eq!(
+            serde_json::from_str::<Money>("\"0.30\"").unwrap(),
+            money("0.3")
+        );
+        assert_eq!(
+            serde_yaml::from_str::<Money>("1000").unwrap(),
+            Money::from(1000)
+        );
+        assert!(serde_json::from_str::<Money>("\"abc\"").is_err());
+    }
+}
@@ -1,23 +1,53 @@
-use chrono::NaiveDate;
+use std::collections::HashMap;
 
-use crate::banks::{ExtendedLedgerRecord, LedgerRecord};
+use anyhow::Context;
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    banks::{ExtendedLedger, ExtendedLedgerRecord, LedgerKind, LedgerRecord, Split},
+    fx::{Currency, Pair, Symbol},
+    money::Money,
+    realms::portfolio::{
+        overlay::SerdeOverlay,
+        rules::Rules,
+        state::{Account, Action},
+    },
+};
+
+/// The maximum number of days the two legs of a transfer may be booked apart.
+const TRANSFER_MAX_DAYS: i64 = 5;
+/// The absolute tolerance in base currency for transfers within the same currency.
+const TRANSFER_SAME_CURRENCY_TOLERANCE: f64 = 0.01;
+/// The relative tolerance for transfers that involve an FX conversion (spread and fees).
+const TRANSFER_FX_TOLERANCE: f64 = 0.02;
 
 pub fn process(
+    account: &str,
     data: Vec<LedgerRecord>,
-    initial_balance: Option<f64>,
+    initial_balance: Option<Money>,
     initial_date: Option<NaiveDate>,
+    rules: &Rules,
 ) -> anyhow::Result<Vec<ExtendedLedgerRecord>> {
+    let mut occurrences = HashMap::new();
     let mut incoming = data
         .into_iter()
         .map(|v| ExtendedLedgerRecord {
+            id: record_id(&v, &mut occurrences),
             date: v.date,
             amount: v.amount,
             description: v.description.clone(),
+            category: rules
+                .categorize(account, &v.date, v.amount, &v.description)
+                .map_or_else(|| v.category.clone(), ToString::to_string),
             original_description: v.description,
-            category: v.category.clone(),
             original_category: v.category,
             comments: "".to_string(),
             checked: false,
+            transfer: None,
+            action: v.action,
+            price: v.price,
         })
         .collect::<Vec<_>>();
 
@@ -26,6 +56,7 @@ pub fn process(
             let initial_description = "Initial Balance";
             let initial_category = "initial";
             let initial = ExtendedLedgerRecord {
+                id: initial_category.to_string(),
                 date: initial_date,
                 amount: initial_balance,
                 description: initial_description.to_string(),
@@ -34,6 +65,9 @@ pub fn process(
                 original_category: initial_category.to_string(),
                 comments: "".to_string(),
                 checked: false,
+                transfer: None,
+                action: Action::Update,
+                price: None,
             };
 
             incoming.push(initial);
@@ -44,3 +78,674 @@ pub fn process(
 
     Ok(records)
 }
+
+/// Derives a stable id for a record from its fingerprint.
+///
+/// Identical records (e.g. two equal payments on the same day) are told apart by the order in
+/// which they occur.
+fn record_id(record: &LedgerRecord, occurrences: &mut HashMap<String, usize>) -> String {
+    let id = record.fingerprint();
+    let occurrence = occurrences.entry(id.clone()).or_insert(0);
+    *occurrence += 1;
+    if *occurrence > 1 {
+        format!("{id}-{occurrence}")
+    } else {
+        id
+    }
+}
+
+/// Scopes the id of a record to its ledger, as the legs of a trade share the bank's transaction
+/// id.
+pub fn scoped_id(symbol: &Symbol, id: &str) -> String {
+    format!("{symbol}:{id}")
+}
+
+/// The id of a record without the ledger it was scoped to by [`scoped_id`].
+pub fn unscoped_id<'a>(symbol: &Symbol, id: &'a str) -> &'a str {
+    id.strip_prefix(format!("{symbol}:").as_str()).unwrap_or(id)
+}
+
+/// Multiplies the quantities of a stock's records before each split by its ratio and divides
+/// their prices by it, so they stay consistent with the split-adjusted price history.
+///
+/// Declared splits take precedence over the reported ones of the same date. Returns the applied
+/// splits in date order.
+pub fn apply_splits(
+    records: &mut [ExtendedLedgerRecord],
+    reported: Vec<Split>,
+    declared: &[Split],
+) -> anyhow::Result<Vec<Split>> {
+    let mut splits = declared.to_vec();
+    for split in reported {
+        if !splits.iter().any(|s| s.date == split.date) {
+            splits.push(split);
+        }
+    }
+    splits.sort_by_key(|s| s.date);
+
+    for record in records.iter_mut() {
+        for split in splits.iter().filter(|s| record.date < s.date) {
+            record.amount = record.amount.scale(split.ratio).with_context(|| {
+                format!(
+                    "The split of {} has an invalid ratio of {}",
+                    split.date, split.ratio
+                )
+            })?;
+            record.price = record.price.map(|price| price / split.ratio);
+        }
+    }
+    Ok(splits)
+}
+
+/// Applies the stored user edits to freshly processed records.
+pub fn apply_overlay(records: &mut [ExtendedLedgerRecord], overlay: &SerdeOverlay) {
+    for record in records {
+        let Some(edit) = overlay.records.get(&record.id) else {
+            continue;
+        };
+
+        if let Some(checked) = edit.checked {
+            record.checked = checked;
+        }
+        if let Some(comments) = &edit.comments {
+            record.comments = comments.clone();
+        }
+        if let Some(category) = &edit.category {
+            record.category = category.clone();
+        }
+        if let Some(description) = &edit.description {
+            record.description = description.clone();
+        }
+    }
+}
+
+/// A balance reported by a bank statement next to the balance computed from the records.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Reconciliation {
+    pub iban: Option<String>,
+    #[ts(type = "number")]
+    pub date: NaiveDate,
+    pub kind: BalanceKind,
+    pub reported: Money,
+    pub computed: Money,
+    pub difference: Money,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "kebab-case")]
+pub enum BalanceKind {
+    Opening,
+    Closing,
+}
+
+/// Compares the balances reported by the statements of a ledger with the balance computed from
+/// its records.
+///
+/// An opening balance is compared against all records before its date and a closing balance
+/// against all records up to and including its date.
+pub fn reconcile(ledger: &ExtendedLedger) -> Vec<Reconciliation> {
+    let balance_until = |date: NaiveDate, inclusive: bool| {
+        ledger
+            .records
+            .iter()
+            .filter(|r| r.date < date || (inclusive && r.date == date))
+            .map(|r| r.amount)
+            .sum::<Money>()
+    };
+
+    let mut reconciliations = ledger
+        .statements
+        .iter()
+        .flat_map(|statement| {
+            [
+                (BalanceKind::Opening, &statement.opening_balance),
+                (BalanceKind::Closing, &statement.closing_balance),
+            ]
+            .into_iter()
+            .filter_map(|(kind, balance)| {
+                let balance = balance.as_ref()?;
+                let computed = balance_until(balance.date, kind == BalanceKind::Closing);
+                Some(Reconciliation {
+                    iban: statement.iban.clone(),
+                    date: balance.date,
+                    kind,
+                    reported: balance.amount,
+                    computed,
+                    difference: balance.amount - computed,
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+    reconciliations.sort_by_key(|r| r.date);
+    reconciliations
+}
+
+/// The dividends, interest, taxes and fees booked in one currency during a year.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ActionTotals {
+    pub year: i32,
+    pub currency: Currency,
+    pub dividends: Money,
+    pub interest: Money,
+    /// Negative for taxes paid.
+    pub taxes: Money,
+    /// Negative for fees paid.
+    pub fees: Money,
+}
+
+/// Sums the records of the cash ledgers by year, currency and action.
+///
+/// Regular records are not part of the totals.
+pub fn action_totals<'a>(accounts: impl IntoIterator<Item = &'a Account>) -> Vec<ActionTotals> {
+    let mut totals = HashMap::<(i32, Currency), ActionTotals>::new();
+    for ledger in accounts.into_iter().flat_map(|a| &a.ledgers) {
+        let Symbol::Currency(currency) = ledger.symbol else {
+            continue;
+        };
+        for record in &ledger.records {
+            let year = record.date.year();
+            let total = totals
+                .entry((year, currency))
+                .or_insert_with(|| ActionTotals {
+                    year,
+                    currency,
+                    dividends: Money::ZERO,
+                    interest: Money::ZERO,
+                    taxes: Money::ZERO,
+                    fees: Money::ZERO,
+                });
+            match record.action {
+                Action::Update => continue,
+                Action::Dividend => total.dividends += record.amount,
+                Action::Interest => total.interest += record.amount,
+                Action::Tax => total.taxes += record.amount,
+                Action::Fee => total.fees += record.amount,
+            }
+        }
+    }
+
+    let mut totals = totals
+        .into_values()
+        .filter(|t| [t.dividends, t.interest, t.taxes, t.fees] != [Money::ZERO; 4])
+        .collect::<Vec<_>>();
+    totals.sort_by_key(|t| (t.year, t.currency.to_string()));
+    totals
+}
+
+/// A single bank record that could be one leg of an internal transfer.
+struct TransferCandidate {
+    account: String,
+    ledger: usize,
+    record: usize,
+    symbol: Symbol,
+    date: NaiveDate,
+    /// The amount converted to the base currency.
+    amount: f64,
+}
+
+/// Pairs opposite-signed bank records across the accounts of one owner that look like
+/// internal account-to-account transfers and tags both legs with the id of the other account.
+///
+/// Amounts in different currencies are compared in `base_currency` using `rates`, the pairs of
+/// the ledger currencies to it. Records without a rate are left unmatched, and the returned
+/// messages tell which.
+pub fn match_transfers(
+    accounts: &mut HashMap<String, Account>,
+    base_currency: &Symbol,
+    rates: &HashMap<Symbol, Pair>,
+) -> Vec<String> {
+    let mut unmatched = vec![];
+    let mut outgoing = vec![];
+    let mut incoming = vec![];
+    for (id, account) in accounts.iter() {
+        for (ledger_index, ledger) in account.ledgers.iter().enumerate() {
+            if ledger.kind != LedgerKind::Bank {
+                continue;
+            }
+
+            let pair = rates.get(&ledger.symbol);
+            let mut missing = 0;
+            for (record_index, record) in ledger.records.iter().enumerate() {
+                if record.amount.is_zero() || record.original_category == "initial" {
+                    continue;
+                }
+
+                // Days before the first rate take the first rate, like when valuing a ledger.
+                let rate = if &ledger.symbol == base_currency {
+                    Some(1.0)
+                } else {
+                    pair.and_then(|pair| {
+                        let rates = &pair.rates;
+                        let rate = rates.range(..=record.date).next_back();
+                        rate.or_else(|| rates.first_key_value())
+                            .map(|(_, rate)| *rate)
+                    })
+                };
+                let Some(rate) = rate else {
+                    missing += 1;
+                    continue;
+                };
+
+                let candidate = TransferCandidate {
+                    account: id.clone(),
+                    ledger: ledger_index,
+                    record: record_index,
+                    symbol: ledger.symbol.clone(),
+                    date: record.date,
+                    amount: record.amount.to_f64() * rate,
+                };
+                if candidate.amount < 0.0 {
+                    outgoing.push(candidate);
+                } else {
+                    incoming.push(candidate);
+                }
+            }
+
+            if missing > 0 {
+                let reason = pair.and_then(|pair| pair.error.as_deref());
+                unmatched.push(format!(
+                    "{} has no rate from {} to {base_currency}, so {missing} of its records were \
+                     not matched as transfers{}",
+                    account.name,
+                    ledger.symbol,
+                    reason
+                        .map(|reason| format!(": {reason}"))
+                        .unwrap_or_default()
+                ));
+            }
+        }
+    }
+    unmatched.sort();
+
+    // Walk the outgoing legs in chronological order so that recurring transfers of the
+    // same amount pair up with their closest counterpart.
+    outgoing.sort_by_key(|c| (c.date, c.account.clone(), c.ledger, c.record));
+    incoming.sort_by_key(|c| (c.date, c.account.clone(), c.ledger, c.record));
+
+    let mut taken = vec![false; incoming.len()];
+    let mut pairs = vec![];
+    for out in &outgoing {
+        let best = incoming
+            .iter()
+            .enumerate()
+            .filter(|(i, inc)| {
+                !taken[*i]
+                    && inc.account != out.account
+                    && (inc.date - out.date).num_days().abs() <= TRANSFER_MAX_DAYS
+            })
+            .filter_map(|(i, inc)| {
+                let difference = (inc.amount + out.amount).abs();
+                let tolerance = if inc.symbol == out.symbol {
+                    TRANSFER_SAME_CURRENCY_TOLERANCE
+                } else {
+                    out.amount.abs() * TRANSFER_FX_TOLERANCE
+                };
+                (difference <= tolerance).then_some((
+                    i,
+                    (inc.date - out.date).num_days().abs(),
+                    difference,
+                ))
+            })
+            .min_by(|a, b| a.1.cmp(&b.1).then(a.2.total_cmp(&b.2)));
+
+        if let Some((i, _, _)) = best {
+            taken[i] = true;
+            pairs.push((out, &incoming[i]));
+        }
+    }
+
+    for (out, inc) in pairs {
+        tag_transfer(accounts, out, &inc.account);
+        tag_transfer(accounts, inc, &out.account);
+    }
+    unmatched
+}
+
+fn tag_transfer(
+    accounts: &mut HashMap<String, Account>,
+    candidate: &TransferCandidate,
+    counterpart: &str,
+) {
+    if let Some(record) = accounts
+        .get_mut(&candidate.account)
+        .and_then(|a| a.ledgers.get_mut(candidate.ledger))
+        .and_then(|l| l.records.get_mut(candidate.record))
+    {
+        record.transfer = Some(counterpart.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::NaiveDate;
+
+    use crate::{
+        banks::{ExtendedLedger, LedgerKind, LedgerRecord, Split, Statement, StatementBalance},
+        cli::BankFormat,
+        fx::{Currency, Derivation, Pair, Symbol},
+        money::Money,
+        realms::portfolio::{
+            overlay::{RecordOverlay, SerdeOverlay},
+            rules::Rules,
+            state::{Account, Action, Owner},
+        },
+    };
+
+    use super::{
+        action_totals, apply_overlay, apply_splits, match_transfers, process, reconcile,
+        ActionTotals, BalanceKind,
+    };
+
+    fn money(amount: f64) -> Money {
+        Money::from_f64(amount).unwrap()
+    }
+
+    fn record(date: &str, amount: f64, description: &str) -> LedgerRecord {
+        LedgerRecord {
+            id: None,
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            amount: money(amount),
+            description: description.to_string(),
+            category: "".to_string(),
+            action: Action::Update,
+            price: None,
+        }
+    }
+
+    fn account(id: &str, currency: Currency, records: Vec<LedgerRecord>) -> (String, Account) {
+        (
+            id.to_string(),
+            Account {
+                id: id.to_string(),
+                owner: Owner::new("owner".to_string()),
+                name: id.to_string(),
+                format: BankFormat::Ubs,
+                custom_format: None,
+                ledgers: vec![ExtendedLedger {
+                    name: id.to_string(),
+                    symbol: Symbol::Currency(currency),
+                    records: process(id, records, None, None, &Rules::default()).unwrap(),
+                    kind: LedgerKind::Bank,
+                    statements: vec![],
+                    quote_currency: None,
+                    splits: vec![],
+                }],
+                initial_balance: None,
+                initial_date: None,
+                spending: true,
+            },
+        )
+    }
+
+    fn transfers(accounts: &HashMap<String, Account>, id: &str) -> Vec<Option<String>> {
+        accounts[id].ledgers[0]
+            .records
+            .iter()
+            .map(|r| r.transfer.clone())
+            .collect()
+    }
+
+    #[test]
+    fn match_same_currency() {
+        let mut accounts = HashMap::from([
+            account(
+                "ubs",
+                Currency::CHF,
+                vec![
+                    record("2024-03-01", -500.0, "To Revolut"),
+                    record("2024-03-02", -500.0, "Rent"),
+                ],
+            ),
+            account(
+                "revolut",
+                Currency::CHF,
+                vec![
+                    record("2024-03-03", 500.0, "Top-Up"),
+                    record("2024-03-20", 500.0, "Refund"),
+                ],
+            ),
+        ]);
+
+        let unmatched = match_transfers(
+            &mut accounts,
+            &Symbol::Currency(Currency::CHF),
+            &HashMap::new(),
+        );
+
+        assert!(unmatched.is_empty());
+        assert_eq!(
+            transfers(&accounts, "ubs"),
+            vec![Some("revolut".to_string()), None]
+        );
+        assert_eq!(
+            transfers(&accounts, "revolut"),
+            vec![Some("ubs".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn match_across_currencies() {
+        let rates = HashMap::from([(
+            Symbol::Currency(Currency::EUR),
+            Pair {
+                from: Symbol::Currency(Currency::EUR),
+                to: Symbol::Currency(Currency::CHF),
+                since: None,
+                dirty: false,
+                stale: false,
+                error: None,
+                derivation: Derivation::Fetched,
+                rates: [(NaiveDate::from_ymd_opt(2024, 2, 28).unwrap(), 0.95)].into(),
+            },
+        )]);
+        let accounts = || {
+            HashMap::from([
+                account(
+                    "ubs",
+                    Currency::CHF,
+                    vec![record("2024-03-01", -950.0, "To Wise")],
+                ),
+                account(
+                    "wise",
+                    Currency::EUR,
+                    vec![record("2024-03-04", 995.0, "Received money")],
+                ),
+            ])
+        };
+        let chf = Symbol::Currency(Currency::CHF);
+
+        let mut matched = accounts();
+        assert!(match_transfers(&mut matched, &chf, &rates).is_empty());
+        assert_eq!(transfers(&matched, "ubs"), vec![Some("wise".to_string())]);
+        assert_eq!(transfers(&matched, "wise"), vec![Some("ubs".to_string())]);
+
+        // Without a rate the records are left unmatched, and the reason is reported.
+        let mut unmatched = accounts();
+        let reason = "wise has no rate from EUR to CHF, so 1 of its records were not matched as \
+                      transfers";
+        assert_eq!(
+            match_transfers(&mut unmatched, &chf, &HashMap::new()),
+            vec![reason]
+        );
+        assert_eq!(transfers(&unmatched, "ubs"), vec![None]);
+        assert_eq!(transfers(&unmatched, "wise"), vec![None]);
+    }
+
+    #[test]
+    fn overlay_survives_reprocessing() {
+        let records = || {
+            vec![
+                record("2019-05-10", 30.0, "neon Switzerland AG"),
+                record("2019-05-10", 30.0, "neon Switzerland AG"),
+                record("2019-05-09", 3000.0, "Technokrat GmbH"),
+            ]
+        };
+        let first = process("neon", records(), None, None, &Rules::default()).unwrap();
+        assert_ne!(first[0].id, first[1].id);
+
+        let overlay = SerdeOverlay {
+            records: [(
+                first[1].id.clone(),
+                RecordOverlay {
+                    checked: Some(true),
+                    category: Some("bonus".to_string()),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+        };
+
+        let mut second = process("neon", records(), None, None, &Rules::default()).unwrap();
+        apply_overlay(&mut second, &overlay);
+
+        assert_eq!(
+            second.iter().map(|r| r.checked).collect::<Vec<_>>(),
+            vec![false, true, false]
+        );
+        assert_eq!(second[1].category, "bonus");
+        assert_eq!(second[1].original_category, "");
+    }
+
+    #[test]
+    fn reconcile_statement() {
+        let (_, account) = account(
+            "ubs",
+            Currency::CHF,
+            vec![
+                record("2021-01-22", 3688.0, "TECHNOKRAT GMBH"),
+                record("2021-01-27", -62.75, "CAISSE DES MEDECINS"),
+                record("2021-01-29", -10.0, "Balance closing of service prices"),
+            ],
+        );
+        let mut ledger = account.ledgers[0].clone();
+        ledger.records.extend(
+            process(
+                "ubs",
+                vec![],
+                Some(money(100.0)),
+                NaiveDate::from_ymd_opt(2021, 1, 1),
+                &Rules::default(),
+            )
+            .unwrap(),
+        );
+        ledger.statements.push(Statement {
+            iban: Some("CH5800230230123456789".to_string()),
+            opening_balance: Some(StatementBalance {
+                date: NaiveDate::from_ymd_opt(2021, 1, 22).unwrap(),
+                amount: money(100.0),
+            }),
+            closing_balance: Some(StatementBalance {
+                date: NaiveDate::from_ymd_opt(2021, 1, 29).unwrap(),
+                amount: money(3725.25),
+            }),
+        });
+
+        let reconciliations = reconcile(&ledger);
+
+        assert_eq!(reconciliations.len(), 2);
+        assert_eq!(reconciliations[0].kind, BalanceKind::Opening);
+        assert_eq!(reconciliations[0].computed, money(100.0));
+        assert_eq!(reconciliations[1].kind, BalanceKind::Closing);
+        assert_eq!(reconciliations[1].computed, money(3715.25));
+        assert_eq!(reconciliations[1].difference, money(10.0));
+    }
+
+    #[test]
+    fn totals_by_year_and_action() {
+        let action = |date, amount, action| LedgerRecord {
+            action,
+            ..record(date, amount, "IBKR")
+        };
+        let accounts = HashMap::from([
+            account(
+                "ibkr-usd",
+                Currency::USD,
+                vec![
+                    action("2023-12-15", 40.0, Action::Dividend),
+                    action("2023-12-15", -6.0, Action::Tax),
+                    action("2024-03-20", 50.0, Action::Dividend),
+                    action("2024-03-20", -7.5, Action::Tax),
+                    action("2024-06-24", -1.0, Action::Fee),
+                    action("2024-06-24", -11300.0, Action::Update),
+                ],
+            ),
+            account(
+                "ibkr-chf",
+                Currency::CHF,
+                vec![action("2024-01-03", 2.5, Action::Interest)],
+            ),
+        ]);
+
+        assert_eq!(
+            action_totals(accounts.values()),
+            vec![
+                ActionTotals {
+                    year: 2023,
+                    currency: Currency::USD,
+                    dividends: money(40.0),
+                    interest: money(0.0),
+                    taxes: money(-6.0),
+                    fees: money(0.0),
+                },
+                ActionTotals {
+                    year: 2024,
+                    currency: Currency::CHF,
+                    dividends: money(0.0),
+                    interest: money(2.5),
+                    taxes: money(0.0),
+                    fees: money(0.0),
+                },
+                ActionTotals {
+                    year: 2024,
+                    currency: Currency::USD,
+                    dividends: money(50.0),
+                    interest: money(0.0),
+                    taxes: money(-7.5),
+                    fees: money(-1.0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn adjust_quantities_for_splits() {
+        let split = |date: &str, ratio| Split {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            ratio,
+        };
+        let mut records = process(
+            "ibkr",
+            vec![
+                record("2020-01-02", 10.0, "AAPL"),
+                record("2020-08-31", 5.0, "AAPL"),
+                record("2021-01-04", -20.0, "AAPL"),
+            ],
+            None,
+            None,
+            &Rules::default(),
+        )
+        .unwrap();
+
+        // The declared split wins over the reported one of the same date.
+        let splits = apply_splits(
+            &mut records,
+            vec![split("2020-08-31", 2.0), split("2020-12-01", 2.0)],
+            &[split("2020-08-31", 4.0)],
+        )
+        .unwrap();
+
+        assert_eq!(
+            splits,
+            vec![split("2020-08-31", 4.0), split("2020-12-01", 2.0)]
+        );
+        assert_eq!(
+            records.iter().map(|r| r.amount).collect::<Vec<_>>(),
+            vec![money(80.0), money(10.0), money(-20.0)]
+        );
+    }
+}
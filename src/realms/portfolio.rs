@@ -1,2 +1,7 @@
 pub mod adapter;
+pub mod forecast;
+pub mod holdings;
+pub mod overlay;
+pub mod performance;
+pub mod rules;
 pub mod state;
@@ -1,15 +1,29 @@
+pub mod cached;
+pub mod sqlite;
+
 use std::{collections::HashMap, fs::File, path::PathBuf};
 
 use anyhow::{anyhow, bail, Context, Ok, Result};
 use axum::async_trait;
+use itertools::Itertools;
 
 use crate::{
-    banks::{load, ExtendedLedger, Ledger},
-    handler::ledger::{create::CreateLedgerRequest, update::UpdateLedgerRequest},
-    processing::process,
+    banks::{load, merge, parse, ExtendedLedger, Ledger, LedgerKind, ParsedAccount, Split},
+    cli::BankFormat,
+    error::BadRequest,
+    handler::ledger::{
+        create::CreateLedgerRequest, records::patch::UpdateRecordRequest,
+        update::UpdateLedgerRequest,
+    },
+    processing::{apply_overlay, apply_splits, process, scoped_id},
 };
 
-use super::state::{Account, Owner, Portfolio, SerdeAccount, SerdePortfolio};
+use super::{
+    holdings::stocks,
+    overlay::SerdeOverlay,
+    rules::{Rules, SerdeRules},
+    state::{Account, Owner, Portfolio, SerdeAccount, SerdePortfolio},
+};
 
 #[async_trait]
 pub trait Adapter: Send + Sync {
@@ -28,10 +42,26 @@ pub trait Adapter: Send + Sync {
     ) -> Result<String>;
     async fn delete_ledger(&self, portfolio: Portfolio, id: &str) -> Result<()>;
     fn list_files(&self, owner: &Owner) -> Result<HashMap<String, Vec<PathBuf>>>;
-    fn load_file(&self, owner: &Owner, id: &str) -> Result<Vec<Ledger>>;
+    fn load_file(&self, owner: &Owner, id: &str, name: &str) -> Result<ParsedAccount>;
+    fn read_file(&self, owner: &Owner, id: &str, name: &str) -> Result<Vec<u8>>;
+    /// Parses the content of a file with the format of the account without storing it.
+    fn parse_file(
+        &self,
+        owner: &Owner,
+        id: &str,
+        name: &str,
+        content: String,
+    ) -> Result<ParsedAccount>;
+    fn format(&self, owner: &Owner, id: &str) -> Result<BankFormat>;
     fn add_file(&self, owner: &Owner, id: &str, name: &str, content: Vec<u8>) -> Result<()>;
     fn update_file(&self, owner: &Owner, id: &str, name: &str, content: Vec<u8>) -> Result<()>;
     fn delete_file(&self, owner: &Owner, id: &str, name: &str) -> Result<()>;
+    fn update_records(
+        &self,
+        owner: &Owner,
+        id: &str,
+        records: Vec<UpdateRecordRequest>,
+    ) -> Result<()>;
 }
 
 pub struct Production {
@@ -40,11 +70,140 @@ pub struct Production {
 
 impl Production {
     const PORTFOLIO_FILE_NAME: &'static str = "portfolio.yaml";
+    const RULES_FILE_NAME: &'static str = "rules.yaml";
+    const OVERLAY_FILE_NAME: &'static str = ".overlay.yaml";
     const PORTFOLIO_LEDGER_DIR: &'static str = "ledgers";
 
     pub(crate) fn new(path: PathBuf) -> Production {
         Production { path }
     }
+
+    /// Hidden files in an account directory (like the overlay) are not ledger files.
+    fn is_ledger_file(path: &std::path::Path) -> bool {
+        !path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+    }
+
+    /// Reads the `portfolio.yaml` only, without loading any ledger.
+    fn load_serde_portfolio(&self, owner: &Owner) -> Result<SerdePortfolio> {
+        let path = self
+            .path
+            .join(Self::PORTFOLIO_LEDGER_DIR)
+            .join(owner)
+            .join(Self::PORTFOLIO_FILE_NAME);
+        let file =
+            File::open(&path).with_context(|| format!("{} could not be opened", path.display()))?;
+        serde_yaml::from_reader(file).context("portfolio could not be read")
+    }
+
+    fn load_overlay(&self, owner: &Owner, id: &str) -> Result<SerdeOverlay> {
+        let path = self
+            .path
+            .join(Self::PORTFOLIO_LEDGER_DIR)
+            .join(owner)
+            .join(id)
+            .join(Self::OVERLAY_FILE_NAME);
+        if !path.exists() {
+            return Ok(SerdeOverlay::default());
+        }
+
+        let file =
+            File::open(&path).with_context(|| anyhow!("Could not open {}", path.display()))?;
+        serde_yaml::from_reader(file)
+            .with_context(|| anyhow!("{} could not be read", path.display()))
+    }
+
+    fn load_rules(&self, owner: &Owner) -> Result<SerdeRules> {
+        let path = self
+            .path
+            .join(Self::PORTFOLIO_LEDGER_DIR)
+            .join(owner)
+            .join(Self::RULES_FILE_NAME);
+        if !path.exists() {
+            return Ok(SerdeRules::default());
+        }
+
+        let file =
+            File::open(&path).with_context(|| anyhow!("Could not open {}", path.display()))?;
+        serde_yaml::from_reader(file)
+            .with_context(|| anyhow!("{} could not be read", path.display()))
+    }
+}
+
+/// Rejects names which are not plain ledger files, like the hidden overlay or paths leading out
+/// of the account's directory.
+pub(crate) fn check_file_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        bail!(BadRequest(format!("{name} is not a valid file name")));
+    }
+    Ok(())
+}
+
+/// Drops the declared splits whose ratio is not valid, so that a single bad entry does not keep
+/// the whole portfolio from loading.
+fn valid_splits(mut splits: HashMap<String, Vec<Split>>) -> HashMap<String, Vec<Split>> {
+    for declared in splits.values_mut() {
+        declared.retain(Split::is_valid);
+    }
+    splits
+}
+
+/// Processes the merged ledgers of an account into the records the handlers work with.
+fn build_account(
+    id: &str,
+    account: SerdeAccount,
+    ledgers: Vec<Ledger>,
+    overlay: &SerdeOverlay,
+    rules: &Rules,
+    splits: &HashMap<String, Vec<Split>>,
+) -> Result<Account> {
+    let ledgers = ledgers
+        .into_iter()
+        .map(|ledger| {
+            let mut records = process(
+                id,
+                ledger.records,
+                account.initial_balance,
+                account.initial_date,
+                rules,
+            )?;
+            // The legs of a trade share the bank's transaction id, so a record is only identified
+            // within the account together with its ledger.
+            for record in &mut records {
+                record.id = scoped_id(&ledger.symbol, &record.id);
+            }
+            apply_overlay(&mut records, overlay);
+            let splits = if ledger.kind == LedgerKind::Stock {
+                let declared = splits
+                    .get(&ledger.symbol.to_string())
+                    .map_or(&[][..], Vec::as_slice);
+                apply_splits(&mut records, ledger.splits, declared)?
+            } else {
+                vec![]
+            };
+            Ok(ExtendedLedger {
+                records,
+                name: ledger.name,
+                symbol: ledger.symbol,
+                kind: ledger.kind,
+                statements: ledger.statements,
+                quote_currency: ledger.quote_currency,
+                splits,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Account {
+        id: id.to_string(),
+        owner: account.owner,
+        name: account.name,
+        format: account.format,
+        custom_format: account.custom_format,
+        ledgers,
+        initial_balance: account.initial_balance,
+        initial_date: account.initial_date,
+        spending: account.spending,
+    })
 }
 
 #[async_trait]
@@ -53,20 +212,7 @@ impl Adapter for Production {
         let accounts = portfolio
             .accounts
             .iter()
-            .map(|(id, ledger)| {
-                (
-                    id.clone(),
-                    SerdeAccount {
-                        id: ledger.id.clone(),
-                        owner: ledger.owner.clone(),
-                        name: ledger.name.clone(),
-                        format: ledger.format,
-                        initial_balance: ledger.initial_balance,
-                        initial_date: ledger.initial_date,
-                        spending: ledger.spending,
-                    },
-                )
-            })
+            .map(|(id, account)| (id.clone(), SerdeAccount::from(account)))
             .collect::<HashMap<String, SerdeAccount>>();
         let path = self
             .path
@@ -78,6 +224,7 @@ impl Adapter for Production {
                 base_currency: portfolio.base_currency,
                 accounts,
                 stocks: vec![],
+                splits: portfolio.splits.clone(),
             },
         )?;
         std::fs::create_dir_all(&path)
@@ -98,70 +245,47 @@ impl Adapter for Production {
             .with_context(|| anyhow!("Could not open/create {}", portfolio_path.display()))?;
         let portfolio: SerdePortfolio = serde_yaml::from_reader(file)?;
 
+        let serde_rules = self.load_rules(&owner)?;
+        let rules = Rules::new(&serde_rules.rules)?;
+        let splits = valid_splits(portfolio.splits);
+
         let mut accounts = HashMap::new();
         for (id, account) in portfolio.accounts.into_iter() {
-            let path = path.join(account.id);
+            let path = path.join(&account.id);
             let mut ledgers = Vec::<Ledger>::new();
-            let dir_entries = std::fs::read_dir(&path)
-                .with_context(|| anyhow!("could not open dir {}", path.display()))?;
-            for entry in dir_entries {
-                let path = entry?.path();
-                let loaded_ledgers = load(&id, path, account.format)?;
-                for ledger in loaded_ledgers {
-                    if let Some(found_ledger) =
-                        ledgers.iter_mut().find(|l| l.symbol == ledger.symbol)
-                    {
-                        found_ledger.records.extend(ledger.records);
-                    } else {
-                        ledgers.push(ledger);
-                    }
+            let mut paths = std::fs::read_dir(&path)
+                .with_context(|| anyhow!("could not open dir {}", path.display()))?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            // Files are merged in name order so the same duplicates are dropped on every load.
+            paths.sort();
+            for path in paths {
+                if !Self::is_ledger_file(&path) {
+                    continue;
                 }
+                merge(
+                    &mut ledgers,
+                    load(&id, path, account.format, account.custom_format.as_ref())?.ledgers,
+                );
             }
-
-            accounts.insert(
-                id.clone(),
-                Account {
-                    id: id.clone(),
-                    owner: account.owner.clone(),
-                    name: account.name.clone(),
-                    format: account.format,
-                    ledgers: ledgers
-                        .into_iter()
-                        .map(|ledger| {
-                            Ok(ExtendedLedger {
-                                records: process(
-                                    ledger.records,
-                                    account.initial_balance,
-                                    account.initial_date,
-                                )?,
-                                name: ledger.name,
-                                symbol: ledger.symbol,
-                                kind: ledger.kind,
-                            })
-                        })
-                        .collect::<Result<Vec<_>>>()?,
-                    initial_balance: account.initial_balance,
-                    initial_date: account.initial_date,
-                    spending: account.spending,
-                },
-            );
+            let overlay = self.load_overlay(&owner, &id)?;
+            let account = build_account(&id, account, ledgers, &overlay, &rules, &splits)?;
+            accounts.insert(id, account);
         }
 
         Ok(Portfolio {
             base_currency: portfolio.base_currency,
-            stocks: vec![],
+            stocks: stocks(accounts.values()),
             accounts,
+            rules: serde_rules.rules,
+            splits,
             owner,
         })
     }
 
     fn list_files(&self, owner: &Owner) -> Result<HashMap<String, Vec<PathBuf>>> {
         let path = self.path.join(Self::PORTFOLIO_LEDGER_DIR).join(owner);
-        let protfolio_path = path.join(Self::PORTFOLIO_FILE_NAME);
-        let file = File::open(&protfolio_path)
-            .with_context(|| format!("{} could not be opened", protfolio_path.display()))?;
-        let portfolio: SerdePortfolio =
-            serde_yaml::from_reader(file).context("portfolio could not be read")?;
+        let portfolio = self.load_serde_portfolio(owner)?;
 
         let lists = portfolio
             .accounts
@@ -170,35 +294,80 @@ impl Adapter for Production {
                 let path = path.join(account.id);
                 let files = std::fs::read_dir(&path)
                     .with_context(|| format!("could not read ledger directory {}", path.display()))?
+                    .filter(|dir_entry| {
+                        dir_entry
+                            .as_ref()
+                            .map_or(true, |dir_entry| Self::is_ledger_file(&dir_entry.path()))
+                    })
                     .map(|dir_entry| {
                         let dir_entry = dir_entry.context("dir entry could not be read")?;
 
                         Ok(dir_entry.path().file_name().unwrap().into())
                     })
-                    .collect::<Result<Vec<_>, _>>()?;
-                Ok((id, files))
+                    .collect::<Result<Vec<PathBuf>, _>>()?;
+                Ok((id, files.into_iter().sorted().collect()))
             })
             .collect::<Result<HashMap<_, _>>>()?;
 
         Ok(lists)
     }
 
-    fn load_file(&self, owner: &Owner, id: &str) -> Result<Vec<Ledger>> {
+    fn load_file(&self, owner: &Owner, id: &str, name: &str) -> Result<ParsedAccount> {
+        check_file_name(name)?;
         let path = self.path.join(Self::PORTFOLIO_LEDGER_DIR).join(owner);
-        let protfolio_path = path.join(Self::PORTFOLIO_FILE_NAME);
-        let file = File::open(&protfolio_path)
-            .with_context(|| format!("{} could not be opened", protfolio_path.display()))?;
-        let portfolio: SerdePortfolio =
-            serde_yaml::from_reader(file).context("portfolio could not be read")?;
+        let portfolio = self.load_serde_portfolio(owner)?;
 
         let ledger = portfolio
             .accounts
             .get(id)
             .with_context(|| format!("{id} does not exist in the ledgers"))?;
-        load(id, path.join(id).join(path), ledger.format)
+        load(
+            id,
+            path.join(id).join(name),
+            ledger.format,
+            ledger.custom_format.as_ref(),
+        )
+    }
+
+    fn read_file(&self, owner: &Owner, id: &str, name: &str) -> Result<Vec<u8>> {
+        check_file_name(name)?;
+        let dir_path = self.path.join(Self::PORTFOLIO_LEDGER_DIR).join(owner);
+        let file_path = dir_path.join(id).join(name);
+        std::fs::read(&file_path).with_context(|| format!("Failed to read {}", file_path.display()))
+    }
+
+    fn parse_file(
+        &self,
+        owner: &Owner,
+        id: &str,
+        name: &str,
+        content: String,
+    ) -> Result<ParsedAccount> {
+        let portfolio = self.load_serde_portfolio(owner)?;
+        let account = portfolio
+            .accounts
+            .get(id)
+            .with_context(|| format!("{id} does not exist in the ledgers"))?;
+        parse(
+            id,
+            name,
+            content,
+            account.format,
+            account.custom_format.as_ref(),
+        )
+    }
+
+    fn format(&self, owner: &Owner, id: &str) -> Result<BankFormat> {
+        let portfolio = self.load_serde_portfolio(owner)?;
+        let account = portfolio
+            .accounts
+            .get(id)
+            .with_context(|| format!("{id} does not exist in the ledgers"))?;
+        Ok(account.format)
     }
 
     fn add_file(&self, owner: &Owner, id: &str, name: &str, content: Vec<u8>) -> Result<()> {
+        check_file_name(name)?;
         let dir_path = self.path.join(Self::PORTFOLIO_LEDGER_DIR).join(owner);
         let file_path = dir_path.join(id).join(name);
         std::fs::write(&file_path, &content)
@@ -207,6 +376,7 @@ impl Adapter for Production {
     }
 
     fn update_file(&self, owner: &Owner, id: &str, name: &str, content: Vec<u8>) -> Result<()> {
+        check_file_name(name)?;
         let dir_path = self.path.join(Self::PORTFOLIO_LEDGER_DIR).join(owner);
         let file_path = dir_path.join(id).join(name);
         std::fs::write(&file_path, &content)
@@ -215,11 +385,48 @@ impl Adapter for Production {
     }
 
     fn delete_file(&self, owner: &Owner, id: &str, name: &str) -> Result<()> {
+        check_file_name(name)?;
         let dir_path = self.path.join(Self::PORTFOLIO_LEDGER_DIR).join(owner);
         std::fs::remove_file(dir_path.join(id).join(name))?;
         Ok(())
     }
 
+    fn update_records(
+        &self,
+        owner: &Owner,
+        id: &str,
+        records: Vec<UpdateRecordRequest>,
+    ) -> Result<()> {
+        let mut overlay = self.load_overlay(owner, id)?;
+        for record in records {
+            let entry = overlay.records.entry(record.id).or_default();
+            if let Some(checked) = record.checked {
+                entry.checked = Some(checked);
+            }
+            if let Some(comments) = record.comments {
+                entry.comments = Some(comments);
+            }
+            if let Some(category) = record.category {
+                entry.category = Some(category);
+            }
+            if let Some(description) = record.description {
+                entry.description = Some(description);
+            }
+        }
+
+        let path = self
+            .path
+            .join(Self::PORTFOLIO_LEDGER_DIR)
+            .join(owner)
+            .join(id)
+            .join(Self::OVERLAY_FILE_NAME);
+        serde_yaml::to_writer(
+            File::create(&path).with_context(|| anyhow!("Could not create {}", path.display()))?,
+            &overlay,
+        )?;
+        Ok(())
+    }
+
     async fn create_ledger(
         &self,
         mut portfolio: Portfolio,
@@ -229,6 +436,7 @@ impl Adapter for Production {
         let CreateLedgerRequest {
             name,
             format,
+            custom_format,
             initial_balance,
             initial_date,
             spending,
@@ -254,6 +462,7 @@ impl Adapter for Production {
                 owner,
                 name,
                 format,
+                custom_format,
                 initial_balance,
                 initial_date,
                 spending,
@@ -276,6 +485,7 @@ impl Adapter for Production {
         let UpdateLedgerRequest {
             name,
             format,
+            custom_format,
             initial_balance,
             initial_date,
             spending,
@@ -288,6 +498,8 @@ impl Adapter for Production {
         if &account.owner != owner {
             bail!("Owner does not match!");
         }
+        // The mapping is usually maintained in the portfolio.yaml, so keep it if none is sent.
+        let custom_format = custom_format.or_else(|| account.custom_format.clone());
         portfolio.accounts.insert(
             new_id.clone(),
             Account {
@@ -295,6 +507,7 @@ impl Adapter for Production {
                 owner: owner.clone(),
                 name,
                 format,
+                custom_format,
                 initial_balance,
                 initial_date,
                 spending,
@@ -337,6 +550,10 @@ impl Adapter for Production {
             .join(Self::PORTFOLIO_LEDGER_DIR)
             .join(portfolio.owner)
             .join(id);
+        let overlay_path = dir_path.join(Self::OVERLAY_FILE_NAME);
+        if overlay_path.exists() {
+            std::fs::remove_file(&overlay_path)?;
+        }
         std::fs::remove_dir(&dir_path).with_context(|| {
             anyhow!(
                 "Could not create ledger directory at `{}`",
@@ -357,6 +574,8 @@ impl Adapter for Test {
             base_currency: crate::fx::Currency::CHF,
             stocks: Default::default(),
             accounts: Default::default(),
+            rules: Default::default(),
+            splits: Default::default(),
             owner,
         })
     }
@@ -369,10 +588,28 @@ impl Adapter for Test {
         Ok(Default::default())
     }
 
-    fn load_file(&self, _owner: &Owner, _id: &str) -> Result<Vec<Ledger>> {
+    fn load_file(&self, _owner: &Owner, _id: &str, _name: &str) -> Result<ParsedAccount> {
+        Ok(Default::default())
+    }
+
+    fn read_file(&self, _owner: &Owner, _id: &str, _name: &str) -> Result<Vec<u8>> {
+        Ok(Default::default())
+    }
+
+    fn parse_file(
+        &self,
+        _owner: &Owner,
+        _id: &str,
+        _name: &str,
+        _content: String,
+    ) -> Result<ParsedAccount> {
         Ok(Default::default())
     }
 
+    fn format(&self, _owner: &Owner, _id: &str) -> Result<BankFormat> {
+        Ok(BankFormat::Ubs)
+    }
+
     fn add_file(&self, _owner: &Owner, _id: &str, _name: &str, _content: Vec<u8>) -> Result<()> {
         Ok(())
     }
@@ -385,6 +622,15 @@ impl Adapter for Test {
         Ok(())
     }
 
+    fn update_records(
+        &self,
+        _owner: &Owner,
+        _id: &str,
+        _records: Vec<UpdateRecordRequest>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
     async fn create_ledger(
         &self,
         _portfolio: Portfolio,
@@ -406,3 +652,52 @@ impl Adapter for Test {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use chrono::NaiveDate;
+
+    use crate::{banks::Split, error::BadRequest, realms::portfolio::state::Owner};
+
+    use super::{valid_splits, Adapter, Production};
+
+    #[test]
+    fn reject_reserved_file_names() {
+        let path = std::env::temp_dir().join(format!("reserved-names-{}", std::process::id()));
+        let account = path.join("ledgers").join("owner").join("ubs");
+        std::fs::create_dir_all(&account).unwrap();
+        std::fs::write(account.join(".overlay.yaml"), "").unwrap();
+
+        let production = Production::new(PathBuf::from(&path));
+        let owner = Owner::new("owner".to_string());
+        let deleted = production.delete_file(&owner, "ubs", ".overlay.yaml");
+        assert!(deleted.unwrap_err().is::<BadRequest>());
+        assert!(account.join(".overlay.yaml").exists());
+        let read = production.read_file(&owner, "ubs", "../ubs/.overlay.yaml");
+        assert!(read.unwrap_err().is::<BadRequest>());
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn drop_invalid_splits() {
+        let split = |day, ratio| Split {
+            date: NaiveDate::from_ymd_opt(2024, 6, day).unwrap(),
+            ratio,
+        };
+        let declared = HashMap::from([(
+            "NVDA".to_string(),
+            vec![
+                split(10, 10.0),
+                split(11, 0.0),
+                split(12, -2.0),
+                split(13, f64::INFINITY),
+                split(14, f64::NAN),
+            ],
+        )]);
+
+        assert_eq!(valid_splits(declared)["NVDA"], vec![split(10, 10.0)]);
+    }
+}
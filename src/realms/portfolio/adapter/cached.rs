@@ -0,0 +1,336 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use anyhow::{Context, Result};
+use axum::async_trait;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{
+    banks::ParsedAccount,
+    cli::BankFormat,
+    handler::ledger::{
+        create::CreateLedgerRequest, records::patch::UpdateRecordRequest,
+        update::UpdateLedgerRequest,
+    },
+    realms::portfolio::state::{Owner, Portfolio},
+};
+
+use super::{Adapter, Production};
+
+/// The loaded portfolios by owner.
+#[derive(Debug, Default)]
+pub struct PortfolioCache {
+    inner: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    portfolios: HashMap<Owner, Portfolio>,
+    /// Counts the invalidations, so a portfolio which was loaded before one is not stored.
+    generation: u64,
+}
+
+impl PortfolioCache {
+    fn entries(&self) -> MutexGuard<'_, Entries> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the cached portfolio of `owner` or loads it. The lock is not held while loading.
+    pub fn get_or_load(
+        &self,
+        owner: &Owner,
+        load: impl FnOnce() -> Result<Portfolio>,
+    ) -> Result<Portfolio> {
+        let generation = {
+            let entries = self.entries();
+            if let Some(portfolio) = entries.portfolios.get(owner) {
+                return Ok(portfolio.clone());
+            }
+            entries.generation
+        };
+
+        let portfolio = load()?;
+        let mut entries = self.entries();
+        if entries.generation == generation {
+            entries.portfolios.insert(owner.clone(), portfolio.clone());
+        }
+        Ok(portfolio)
+    }
+
+    pub fn invalidate(&self, owner: &Owner) {
+        let mut entries = self.entries();
+        entries.generation += 1;
+        entries.portfolios.remove(owner);
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries();
+        entries.generation += 1;
+        entries.portfolios.clear();
+    }
+
+    /// Invalidates the portfolio of an owner whenever something below the `ledgers/<owner>`
+    /// directory of a file based portfolio at `path` changes, e.g. when a file is edited by hand.
+    ///
+    /// The cache is only watched as long as the returned watcher lives.
+    pub fn watch(self: &Arc<Self>, path: &Path) -> Result<RecommendedWatcher> {
+        let directory = path.join(Production::PORTFOLIO_LEDGER_DIR);
+        let root = directory
+            .canonicalize()
+            .with_context(|| format!("{} could not be watched", directory.display()))?;
+        let cache = self.clone();
+        let events = root.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else {
+                cache.clear();
+                return;
+            };
+            for path in event.paths {
+                match owner(&events, &path) {
+                    Some(owner) => cache.invalidate(&owner),
+                    None => cache.clear(),
+                }
+            }
+        })?;
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+        Ok(watcher)
+    }
+}
+
+/// The owner whose portfolio contains `path`, which is somewhere below `root`.
+fn owner(root: &Path, path: &Path) -> Option<Owner> {
+    let owner = path.strip_prefix(root).ok()?.components().next()?;
+    Some(Owner::new(owner.as_os_str().to_string_lossy().to_string()))
+}
+
+/// Keeps the loaded portfolios of another adapter in memory.
+///
+/// Loading a portfolio parses all of its files, which is too slow to do on every request. The
+/// portfolio of an owner is loaded again after any change to it through this adapter.
+pub struct Cached {
+    adapter: Arc<dyn Adapter>,
+    cache: Arc<PortfolioCache>,
+}
+
+impl Cached {
+    pub fn new(adapter: Arc<dyn Adapter>, cache: Arc<PortfolioCache>) -> Self {
+        Self { adapter, cache }
+    }
+
+    /// Invalidates the portfolio of `owner` whether the change succeeded or not, as a failed
+    /// change might have been applied in part.
+    fn change<T>(&self, owner: &Owner, change: impl FnOnce() -> Result<T>) -> Result<T> {
+        let result = change();
+        self.cache.invalidate(owner);
+        result
+    }
+}
+
+#[async_trait]
+impl Adapter for Cached {
+    fn load(&self, owner: Owner) -> Result<Portfolio> {
+        self.cache
+            .get_or_load(&owner, || self.adapter.load(owner.clone()))
+    }
+
+    fn store(&self, state: &Portfolio) -> Result<()> {
+        self.change(&state.owner, || self.adapter.store(state))
+    }
+
+    async fn create_ledger(
+        &self,
+        portfolio: Portfolio,
+        account: CreateLedgerRequest,
+    ) -> Result<String> {
+        let owner = portfolio.owner.clone();
+        let result = self.adapter.create_ledger(portfolio, account).await;
+        self.cache.invalidate(&owner);
+        result
+    }
+
+    async fn update_ledger(
+        &self,
+        portfolio: Portfolio,
+        id: String,
+        account: UpdateLedgerRequest,
+    ) -> Result<String> {
+        let owner = portfolio.owner.clone();
+        let result = self.adapter.update_ledger(portfolio, id, account).await;
+        self.cache.invalidate(&owner);
+        result
+    }
+
+    async fn delete_ledger(&self, portfolio: Portfolio, id: &str) -> Result<()> {
+        let owner = portfolio.owner.clone();
+        let result = self.adapter.delete_ledger(portfolio, id).await;
+        self.cache.invalidate(&owner);
+        result
+    }
+
+    fn list_files(&self, owner: &Owner) -> Result<HashMap<String, Vec<PathBuf>>> {
+        self.adapter.list_files(owner)
+    }
+
+    fn load_file(&self, owner: &Owner, id: &str, name: &str) -> Result<ParsedAccount> {
+        self.adapter.load_file(owner, id, name)
+    }
+
+    fn read_file(&self, owner: &Owner, id: &str, name: &str) -> Result<Vec<u8>> {
+        self.adapter.read_file(owner, id, name)
+    }
+
+    fn parse_file(
+        &self,
+        owner: &Owner,
+        id: &str,
+        name: &str,
+        content: String,
+    ) -> Result<ParsedAccount> {
+        self.adapter.parse_file(owner, id, name, content)
+    }
+
+    fn format(&self, owner: &Owner, id: &str) -> Result<BankFormat> {
+        self.adapter.format(owner, id)
+    }
+
+    fn add_file(&self, owner: &Owner, id: &str, name: &str, content: Vec<u8>) -> Result<()> {
+        self.change(owner, || self.adapter.add_file(owner, id, name, content))
+    }
+
+    fn update_file(&self, owner: &Owner, id: &str, name: &str, content: Vec<u8>) -> Result<()> {
+        self.change(owner, || self.adapter.update_file(owner, id, name, content))
+    }
+
+    fn delete_file(&self, owner: &Owner, id: &str, name: &str) -> Result<()> {
+        self.change(owner, || self.adapter.delete_file(owner, id, name))
+    }
+
+    fn update_records(
+        &self,
+        owner: &Owner,
+        id: &str,
+        records: Vec<UpdateRecordRequest>,
+    ) -> Result<()> {
+        self.change(owner, || self.adapter.update_records(owner, id, records))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        cli::BankFormat,
+        handler::ledger::create::CreateLedgerRequest,
+        realms::portfolio::{
+            adapter::{sqlite::Sqlite, Adapter},
+            state::{Owner, Portfolio},
+        },
+    };
+
+    use super::{owner, Cached, PortfolioCache};
+
+    fn request(name: &str) -> CreateLedgerRequest {
+        CreateLedgerRequest {
+            format: BankFormat::Ubs,
+            custom_format: None,
+            initial_balance: None,
+            initial_date: None,
+            name: name.to_string(),
+            spending: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn invalidate_on_change() {
+        let inner = Arc::new(Sqlite::in_memory().unwrap());
+        let cache = Arc::<PortfolioCache>::default();
+        let cached = Cached::new(inner.clone(), cache.clone());
+        let owner = Owner::new("owner".to_string());
+
+        let portfolio = cached.load(owner.clone()).unwrap();
+        cached
+            .create_ledger(portfolio.clone(), request("UBS"))
+            .await
+            .unwrap();
+        assert!(cached
+            .load(owner.clone())
+            .unwrap()
+            .accounts
+            .contains_key("ubs"));
+
+        // Changes which bypass the cache are only seen once it is invalidated.
+        inner
+            .create_ledger(portfolio, request("Neon"))
+            .await
+            .unwrap();
+        assert!(!cached
+            .load(owner.clone())
+            .unwrap()
+            .accounts
+            .contains_key("neon"));
+        cache.invalidate(&owner);
+        assert!(cached
+            .load(owner.clone())
+            .unwrap()
+            .accounts
+            .contains_key("neon"));
+
+        // Failed changes invalidate as well.
+        inner
+            .create_ledger(cached.load(owner.clone()).unwrap(), request("Wise"))
+            .await
+            .unwrap();
+        assert!(cached.delete_file(&owner, "ubs", "missing.csv").is_err());
+        assert!(cached.load(owner).unwrap().accounts.contains_key("wise"));
+    }
+
+    #[test]
+    fn owner_of_path() {
+        let root = Path::new("/portfolio/ledgers");
+        assert_eq!(
+            owner(root, Path::new("/portfolio/ledgers/123/ubs/2024.csv")),
+            Some(Owner::new("123".to_string()))
+        );
+        assert_eq!(owner(root, Path::new("/portfolio/fx/EUR:CHF.json")), None);
+        assert_eq!(owner(root, root), None);
+    }
+
+    #[test]
+    fn watch_files() {
+        let path = std::env::temp_dir().join(format!("watch-files-{}", std::process::id()));
+        let account = path.join("ledgers").join("owner").join("ubs");
+        std::fs::create_dir_all(&account).unwrap();
+
+        let cache = Arc::<PortfolioCache>::default();
+        let owner = Owner::new("owner".to_string());
+        let _watcher = cache.watch(&path).unwrap();
+        cache.get_or_load(&owner, || Ok(empty(&owner))).unwrap();
+
+        std::fs::write(account.join("2024.csv"), "").unwrap();
+        let start = Instant::now();
+        while cache.entries().portfolios.contains_key(&owner) {
+            assert!(start.elapsed() < Duration::from_secs(5), "not invalidated");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    fn empty(owner: &Owner) -> Portfolio {
+        Portfolio {
+            base_currency: Default::default(),
+            stocks: vec![],
+            accounts: Default::default(),
+            rules: vec![],
+            splits: Default::default(),
+            owner: owner.clone(),
+        }
+    }
+}
@@ -0,0 +1,1075 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use anyhow::{bail, Context, Result};
+use axum::async_trait;
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    banks::{merge, parse, Ledger, LedgerKind, LedgerRecord, ParsedAccount, Split},
+    cli::BankFormat,
+    fx::{Currency, Symbol},
+    handler::ledger::{
+        create::CreateLedgerRequest, records::patch::UpdateRecordRequest,
+        update::UpdateLedgerRequest,
+    },
+    realms::portfolio::{
+        holdings::stocks,
+        overlay::{RecordOverlay, SerdeOverlay},
+        rules::{Rule, Rules},
+        state::{Owner, Portfolio, SerdeAccount},
+    },
+};
+
+use super::{build_account, check_file_name, valid_splits, Adapter, Production};
+
+/// The tables of the database. Deleting or renaming an account cascades to its files, their
+/// records and the user edits.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS portfolios (
+    owner TEXT PRIMARY KEY,
+    base_currency TEXT NOT NULL,
+    rules TEXT NOT NULL DEFAULT '[]',
+    splits TEXT NOT NULL DEFAULT '{}'
+);
+CREATE TABLE IF NOT EXISTS accounts (
+    owner TEXT NOT NULL REFERENCES portfolios (owner) ON DELETE CASCADE,
+    id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    format TEXT NOT NULL,
+    custom_format TEXT,
+    initial_balance TEXT,
+    initial_date TEXT,
+    spending INTEGER NOT NULL,
+    PRIMARY KEY (owner, id)
+);
+CREATE TABLE IF NOT EXISTS files (
+    owner TEXT NOT NULL,
+    account TEXT NOT NULL,
+    name TEXT NOT NULL,
+    content BLOB NOT NULL,
+    error TEXT,
+    diagnostics TEXT NOT NULL,
+    PRIMARY KEY (owner, account, name),
+    FOREIGN KEY (owner, account) REFERENCES accounts (owner, id)
+        ON UPDATE CASCADE ON DELETE CASCADE
+);
+CREATE TABLE IF NOT EXISTS ledgers (
+    owner TEXT NOT NULL,
+    account TEXT NOT NULL,
+    file TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    quote_currency TEXT,
+    statements TEXT NOT NULL,
+    splits TEXT NOT NULL,
+    PRIMARY KEY (owner, account, file, position),
+    FOREIGN KEY (owner, account, file) REFERENCES files (owner, account, name)
+        ON UPDATE CASCADE ON DELETE CASCADE
+);
+CREATE TABLE IF NOT EXISTS records (
+    owner TEXT NOT NULL,
+    account TEXT NOT NULL,
+    file TEXT NOT NULL,
+    ledger INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    id TEXT,
+    date TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    description TEXT NOT NULL,
+    category TEXT NOT NULL,
+    action TEXT NOT NULL,
+    price REAL,
+    PRIMARY KEY (owner, account, file, ledger, position),
+    FOREIGN KEY (owner, account, file, ledger) REFERENCES ledgers (owner, account, file, position)
+        ON UPDATE CASCADE ON DELETE CASCADE
+);
+CREATE TABLE IF NOT EXISTS overlays (
+    owner TEXT NOT NULL,
+    account TEXT NOT NULL,
+    record TEXT NOT NULL,
+    checked INTEGER,
+    comments TEXT,
+    category TEXT,
+    description TEXT,
+    PRIMARY KEY (owner, account, record),
+    FOREIGN KEY (owner, account) REFERENCES accounts (owner, id)
+        ON UPDATE CASCADE ON DELETE CASCADE
+);
+";
+
+/// Stores the portfolios in an embedded SQLite database.
+///
+/// Files are parsed once when they are stored, so loading a portfolio only reads the normalized
+/// records instead of every file. Each change runs in a transaction of its own.
+pub struct Sqlite {
+    connection: Mutex<Connection>,
+}
+
+impl Sqlite {
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)
+            .with_context(|| format!("{} could not be opened", path.display()))?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        Self::new(connection)
+    }
+
+    pub fn in_memory() -> Result<Self> {
+        Self::new(Connection::open_in_memory()?)
+    }
+
+    fn new(connection: Connection) -> Result<Self> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        connection
+            .execute_batch(SCHEMA)
+            .context("The database schema could not be created")?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// A failed change is rolled back, so the connection stays usable after a panic.
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Imports the `ledgers/<owner>` trees of a file based portfolio at `path`.
+    ///
+    /// The portfolios of the imported owners are replaced as a whole. Returns the owners in the
+    /// order they were imported.
+    pub fn import(&self, path: &Path) -> Result<Vec<Owner>> {
+        let production = Production::new(path.to_path_buf());
+        let directory = path.join(Production::PORTFOLIO_LEDGER_DIR);
+        let mut owners = std::fs::read_dir(&directory)
+            .with_context(|| format!("could not open dir {}", directory.display()))?
+            .filter_map(|entry| {
+                entry
+                    .map(|entry| {
+                        entry
+                            .path()
+                            .is_dir()
+                            .then(|| Owner::new(entry.file_name().to_string_lossy().to_string()))
+                    })
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        owners.sort();
+
+        for owner in &owners {
+            self.import_owner(&production, owner)
+                .with_context(|| format!("The portfolio of {} could not be imported", &**owner))?;
+        }
+        Ok(owners)
+    }
+
+    fn import_owner(&self, production: &Production, owner: &Owner) -> Result<()> {
+        let portfolio = production.load_serde_portfolio(owner)?;
+        let rules = production.load_rules(owner)?;
+        let files = production.list_files(owner)?;
+
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        transaction.execute("DELETE FROM portfolios WHERE owner = ?1", [&**owner])?;
+        put_portfolio(
+            &transaction,
+            owner,
+            portfolio.base_currency,
+            &rules.rules,
+            &portfolio.splits,
+        )?;
+        for (id, account) in portfolio.accounts {
+            let account = SerdeAccount {
+                id: id.clone(),
+                owner: owner.clone(),
+                ..account
+            };
+            put_account(&transaction, &account)?;
+            for name in files.get(&id).into_iter().flatten() {
+                let name = name.display().to_string();
+                let content = production.read_file(owner, &id, &name)?;
+                put_file(&transaction, &account, &name, &content)?;
+            }
+            for (record, edit) in production.load_overlay(owner, &id)?.records {
+                put_overlay(&transaction, owner, &id, &record, &edit)?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Adapter for Sqlite {
+    fn load(&self, owner: Owner) -> Result<Portfolio> {
+        let connection = self.connection();
+        let (base_currency, rules, splits) = load_portfolio(&connection, &owner)?;
+        let compiled = Rules::new(&rules)?;
+        let splits = valid_splits(splits);
+
+        let mut accounts = HashMap::new();
+        for account in load_accounts(&connection, &owner, None)? {
+            let mut ledgers = Vec::<Ledger>::new();
+            // Files are merged in name order so the same duplicates are dropped on every load.
+            for file in file_names(&connection, &owner, &account.id)? {
+                let parsed = load_parsed(&connection, &owner, &account.id, &file)
+                    .with_context(|| format!("{file} of {} could not be loaded", account.id))?;
+                merge(&mut ledgers, parsed.ledgers);
+            }
+            let overlay = load_overlay(&connection, &owner, &account.id)?;
+            let id = account.id.clone();
+            let account = build_account(&id, account, ledgers, &overlay, &compiled, &splits)?;
+            accounts.insert(id, account);
+        }
+
+        Ok(Portfolio {
+            base_currency,
+            stocks: stocks(accounts.values()),
+            accounts,
+            rules,
+            splits,
+            owner,
+        })
+    }
+
+    /// Stores the settings and accounts of the portfolio. Accounts are only removed by
+    /// [`Adapter::delete_ledger`], so a stale portfolio does not drop the accounts created since.
+    fn store(&self, portfolio: &Portfolio) -> Result<()> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        put_portfolio(
+            &transaction,
+            &portfolio.owner,
+            portfolio.base_currency,
+            &portfolio.rules,
+            &portfolio.splits,
+        )?;
+        for account in portfolio.accounts.values() {
+            put_account(&transaction, &SerdeAccount::from(account))?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    async fn create_ledger(
+        &self,
+        portfolio: Portfolio,
+        account: CreateLedgerRequest,
+    ) -> Result<String> {
+        let id = slug::slugify(&account.name);
+        let CreateLedgerRequest {
+            name,
+            format,
+            custom_format,
+            initial_balance,
+            initial_date,
+            spending,
+        } = account;
+
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO portfolios (owner, base_currency) VALUES (?1, ?2)
+             ON CONFLICT (owner) DO NOTHING",
+            params![&*portfolio.owner, portfolio.base_currency.to_string()],
+        )?;
+        if !load_accounts(&transaction, &portfolio.owner, Some(&id))?.is_empty() {
+            bail!("{id} already exists");
+        }
+        put_account(
+            &transaction,
+            &SerdeAccount {
+                id: id.clone(),
+                owner: portfolio.owner.clone(),
+                name,
+                format,
+                custom_format,
+                initial_balance,
+                initial_date,
+                spending,
+            },
+        )?;
+        transaction.commit()?;
+
+        Ok(id)
+    }
+
+    async fn update_ledger(
+        &self,
+        portfolio: Portfolio,
+        id: String,
+        account: UpdateLedgerRequest,
+    ) -> Result<String> {
+        let new_id = slug::slugify(&account.name);
+        let owner = &portfolio.owner;
+        let UpdateLedgerRequest {
+            name,
+            format,
+            custom_format,
+            initial_balance,
+            initial_date,
+            spending,
+        } = account;
+
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        let Some(current) = load_accounts(&transaction, owner, Some(&id))?.pop() else {
+            bail!("Ledger does not exist.");
+        };
+        // The mapping is usually maintained by the import, so keep it if none is sent.
+        let account = SerdeAccount {
+            id: new_id.clone(),
+            owner: owner.clone(),
+            name,
+            format,
+            custom_format: custom_format.or(current.custom_format),
+            initial_balance,
+            initial_date,
+            spending,
+        };
+        transaction.execute(
+            "UPDATE accounts SET id = ?3 WHERE owner = ?1 AND id = ?2",
+            params![&**owner, id, new_id],
+        )?;
+        put_account(&transaction, &account)?;
+
+        // The ledgers are named after the account and the format may have changed.
+        let files = {
+            let mut statement = transaction
+                .prepare("SELECT name, content FROM files WHERE owner = ?1 AND account = ?2")?;
+            let files = statement
+                .query_map(params![&**owner, new_id], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            files
+        };
+        for (name, content) in files {
+            put_file(&transaction, &account, &name, &content)?;
+        }
+        transaction.commit()?;
+
+        Ok(new_id)
+    }
+
+    async fn delete_ledger(&self, portfolio: Portfolio, id: &str) -> Result<()> {
+        let connection = self.connection();
+        let deleted = connection.execute(
+            "DELETE FROM accounts WHERE owner = ?1 AND id = ?2",
+            params![&*portfolio.owner, id],
+        )?;
+        if deleted == 0 {
+            bail!("Ledger does not exist.");
+        }
+        Ok(())
+    }
+
+    fn list_files(&self, owner: &Owner) -> Result<HashMap<String, Vec<PathBuf>>> {
+        let connection = self.connection();
+        load_accounts(&connection, owner, None)?
+            .into_iter()
+            .map(|account| {
+                let files = file_names(&connection, owner, &account.id)?
+                    .into_iter()
+                    .map(PathBuf::from)
+                    .collect();
+                Ok((account.id, files))
+            })
+            .collect()
+    }
+
+    fn load_file(&self, owner: &Owner, id: &str, name: &str) -> Result<ParsedAccount> {
+        check_file_name(name)?;
+        load_parsed(&self.connection(), owner, id, name)
+    }
+
+    fn read_file(&self, owner: &Owner, id: &str, name: &str) -> Result<Vec<u8>> {
+        check_file_name(name)?;
+        self.connection()
+            .query_row(
+                "SELECT content FROM files WHERE owner = ?1 AND account = ?2 AND name = ?3",
+                params![&**owner, id, name],
+                |row| row.get(0),
+            )
+            .optional()?
+            .with_context(|| format!("Failed to read {name} of {id}"))
+    }
+
+    fn parse_file(
+        &self,
+        owner: &Owner,
+        id: &str,
+        name: &str,
+        content: String,
+    ) -> Result<ParsedAccount> {
+        let account = load_account(&self.connection(), owner, id)?;
+        parse(
+            id,
+            name,
+            content,
+            account.format,
+            account.custom_format.as_ref(),
+        )
+    }
+
+    fn format(&self, owner: &Owner, id: &str) -> Result<BankFormat> {
+        Ok(load_account(&self.connection(), owner, id)?.format)
+    }
+
+    fn add_file(&self, owner: &Owner, id: &str, name: &str, content: Vec<u8>) -> Result<()> {
+        check_file_name(name)?;
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        let account = load_account(&transaction, owner, id)?;
+        put_file(&transaction, &account, name, &content)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn update_file(&self, owner: &Owner, id: &str, name: &str, content: Vec<u8>) -> Result<()> {
+        self.add_file(owner, id, name, content)
+    }
+
+    fn delete_file(&self, owner: &Owner, id: &str, name: &str) -> Result<()> {
+        check_file_name(name)?;
+        let deleted = self.connection().execute(
+            "DELETE FROM files WHERE owner = ?1 AND account = ?2 AND name = ?3",
+            params![&**owner, id, name],
+        )?;
+        if deleted == 0 {
+            bail!("{name} does not exist in {id}");
+        }
+        Ok(())
+    }
+
+    fn update_records(
+        &self,
+        owner: &Owner,
+        id: &str,
+        records: Vec<UpdateRecordRequest>,
+    ) -> Result<()> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+        for record in records {
+            let edit = RecordOverlay {
+                checked: record.checked,
+                comments: record.comments,
+                category: record.category,
+                description: record.description,
+            };
+            put_overlay(&transaction, owner, id, &record.id, &edit)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+/// Writes a unit enum like [`BankFormat`] by its serde name.
+fn to_text(value: &impl Serialize) -> Result<String> {
+    Ok(match serde_json::to_value(value)? {
+        serde_json::Value::String(text) => text,
+        value => value.to_string(),
+    })
+}
+
+fn from_text<T: DeserializeOwned>(text: &str) -> Result<T> {
+    serde_json::from_value(serde_json::Value::String(text.to_string()))
+        .with_context(|| format!("{text} could not be read"))
+}
+
+type Settings = (Currency, Vec<Rule>, HashMap<String, Vec<Split>>);
+
+/// Reads the settings of a portfolio, which are the defaults for an owner without one.
+fn load_portfolio(connection: &Connection, owner: &Owner) -> Result<Settings> {
+    let row = connection
+        .query_row(
+            "SELECT base_currency, rules, splits FROM portfolios WHERE owner = ?1",
+            [&**owner],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            },
+        )
+        .optional()?;
+    let Some((base_currency, rules, splits)) = row else {
+        return Ok(Default::default());
+    };
+    Ok((
+        from_text(&base_currency)?,
+        serde_json::from_str(&rules).context("The rules could not be read")?,
+        serde_json::from_str(&splits).context("The splits could not be read")?,
+    ))
+}
+
+fn put_portfolio(
+    connection: &Connection,
+    owner: &Owner,
+    base_currency: Currency,
+    rules: &[Rule],
+    splits: &HashMap<String, Vec<Split>>,
+) -> Result<()> {
+    connection.execute(
+        "INSERT INTO portfolios (owner, base_currency, rules, splits) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (owner) DO UPDATE SET
+            base_currency = excluded.base_currency,
+            rules = excluded.rules,
+            splits = excluded.splits",
+        params![
+            &**owner,
+            base_currency.to_string(),
+            serde_json::to_string(rules)?,
+            serde_json::to_string(splits)?,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Reads the accounts of `owner` ordered by id, or only the one with the given id.
+fn load_accounts(
+    connection: &Connection,
+    owner: &Owner,
+    id: Option<&str>,
+) -> Result<Vec<SerdeAccount>> {
+    let mut statement = connection.prepare(
+        "SELECT id, name, format, custom_format, initial_balance, initial_date, spending
+         FROM accounts WHERE owner = ?1 AND (?2 IS NULL OR id = ?2) ORDER BY id",
+    )?;
+    let rows = statement
+        .query_map(params![&**owner, id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, Option<NaiveDate>>(5)?,
+                row.get::<_, bool>(6)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    rows.into_iter()
+        .map(
+            |(id, name, format, custom_format, initial_balance, initial_date, spending)| {
+                Ok(SerdeAccount {
+                    id,
+                    owner: owner.clone(),
+                    name,
+                    format: from_text(&format)?,
+                    custom_format: custom_format
+                        .map(|format| serde_json::from_str(&format))
+                        .transpose()
+                        .context("The custom format could not be read")?,
+                    initial_balance: initial_balance
+                        .map(|balance| balance.parse())
+                        .transpose()
+                        .context("The initial balance could not be read")?,
+                    initial_date,
+                    spending,
+                })
+            },
+        )
+        .collect()
+}
+
+fn load_account(connection: &Connection, owner: &Owner, id: &str) -> Result<SerdeAccount> {
+    load_accounts(connection, owner, Some(id))?
+        .pop()
+        .with_context(|| format!("{id} does not exist in the ledgers"))
+}
+
+fn put_account(connection: &Connection, account: &SerdeAccount) -> Result<()> {
+    connection.execute(
+        "INSERT INTO accounts
+            (owner, id, name, format, custom_format, initial_balance, initial_date, spending)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT (owner, id) DO UPDATE SET
+            name = excluded.name,
+            format = excluded.format,
+            custom_format = excluded.custom_format,
+            initial_balance = excluded.initial_balance,
+            initial_date = excluded.initial_date,
+            spending = excluded.spending",
+        params![
+            &*account.owner,
+            account.id,
+            account.name,
+            to_text(&account.format)?,
+            account
+                .custom_format
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?,
+            account.initial_balance.map(|balance| balance.to_string()),
+            account.initial_date,
+            account.spending,
+        ],
+    )?;
+    Ok(())
+}
+
+fn file_names(connection: &Connection, owner: &Owner, id: &str) -> Result<Vec<String>> {
+    let mut statement = connection
+        .prepare("SELECT name FROM files WHERE owner = ?1 AND account = ?2 ORDER BY name")?;
+    let names = statement
+        .query_map(params![&**owner, id], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(names)
+}
+
+/// Stores a file together with its records, which replace those of an earlier version.
+///
+/// A file which can not be parsed is stored as well. Loading it fails with the same error
+/// as loading it from disk would.
+fn put_file(
+    connection: &Connection,
+    account: &SerdeAccount,
+    name: &str,
+    content: &[u8],
+) -> Result<()> {
+    let owner = &*account.owner;
+    connection.execute(
+        "DELETE FROM files WHERE owner = ?1 AND account = ?2 AND name = ?3",
+        params![owner, account.id, name],
+    )?;
+
+    let parsed = String::from_utf8(content.to_vec())
+        .context("The file is not valid UTF-8")
+        .and_then(|content| {
+            parse(
+                &account.id,
+                name,
+                content,
+                account.format,
+                account.custom_format.as_ref(),
+            )
+        });
+    let (error, diagnostics) = match &parsed {
+        Ok(parsed) => (None, serde_json::to_string(&parsed.diagnostics)?),
+        Err(error) => (Some(format!("{error:#}")), "[]".to_string()),
+    };
+    connection.execute(
+        "INSERT INTO files (owner, account, name, content, error, diagnostics)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![owner, account.id, name, content, error, diagnostics],
+    )?;
+    let Ok(parsed) = parsed else {
+        return Ok(());
+    };
+
+    let mut insert_record = connection.prepare(
+        "INSERT INTO records
+            (owner, account, file, ledger, position, id, date, amount, description, category,
+             action, price)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+    )?;
+    for (position, ledger) in parsed.ledgers.iter().enumerate() {
+        connection.execute(
+            "INSERT INTO ledgers
+                (owner, account, file, position, name, symbol, kind, quote_currency, statements,
+                 splits)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                owner,
+                account.id,
+                name,
+                position,
+                ledger.name,
+                ledger.symbol.to_string(),
+                to_text(&ledger.kind)?,
+                ledger.quote_currency.map(|c| c.to_string()),
+                serde_json::to_string(&ledger.statements)?,
+                serde_json::to_string(&ledger.splits)?,
+            ],
+        )?;
+        for (index, record) in ledger.records.iter().enumerate() {
+            insert_record.execute(params![
+                owner,
+                account.id,
+                name,
+                position,
+                index,
+                record.id,
+                record.date,
+                record.amount.to_string(),
+                record.description,
+                record.category,
+                to_text(&record.action)?,
+                record.price,
+            ])?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the records of a stored file as they were parsed.
+fn load_parsed(
+    connection: &Connection,
+    owner: &Owner,
+    id: &str,
+    name: &str,
+) -> Result<ParsedAccount> {
+    let (error, diagnostics) = connection
+        .query_row(
+            "SELECT error, diagnostics FROM files
+             WHERE owner = ?1 AND account = ?2 AND name = ?3",
+            params![&**owner, id, name],
+            |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()?
+        .with_context(|| format!("{name} does not exist in {id}"))?;
+    if let Some(error) = error {
+        bail!("{error}");
+    }
+
+    let mut statement = connection.prepare(
+        "SELECT name, symbol, kind, quote_currency, statements, splits FROM ledgers
+         WHERE owner = ?1 AND account = ?2 AND file = ?3 ORDER BY position",
+    )?;
+    let rows = statement
+        .query_map(params![&**owner, id, name], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut ledgers = rows
+        .into_iter()
+        .map(
+            |(ledger, symbol, kind, quote_currency, statements, splits)| {
+                let kind = from_text(&kind)?;
+                Ok(Ledger {
+                    name: ledger,
+                    // Tickers like ALL are currency codes as well.
+                    symbol: match kind {
+                        LedgerKind::Stock => Symbol::Stock(symbol),
+                        LedgerKind::Bank => Symbol::from(symbol),
+                    },
+                    records: vec![],
+                    kind,
+                    statements: serde_json::from_str(&statements)?,
+                    quote_currency: quote_currency
+                        .map(|currency| from_text(&currency))
+                        .transpose()?,
+                    splits: serde_json::from_str(&splits)?,
+                })
+            },
+        )
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut statement = connection.prepare(
+        "SELECT ledger, id, date, amount, description, category, action, price FROM records
+         WHERE owner = ?1 AND account = ?2 AND file = ?3 ORDER BY ledger, position",
+    )?;
+    let rows = statement
+        .query_map(params![&**owner, id, name], |row| {
+            Ok((
+                row.get::<_, usize>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, NaiveDate>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, String>(6)?,
+                row.get::<_, Option<f64>>(7)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (ledger, id, date, amount, description, category, action, price) in rows {
+        let ledger = ledgers
+            .get_mut(ledger)
+            .with_context(|| format!("A record of {name} has no ledger"))?;
+        ledger.records.push(LedgerRecord {
+            id,
+            date,
+            amount: amount
+                .parse()
+                .with_context(|| format!("{amount} is not an amount"))?,
+            description,
+            category,
+            action: from_text(&action)?,
+            price,
+        });
+    }
+
+    Ok(ParsedAccount {
+        ledgers,
+        diagnostics: serde_json::from_str(&diagnostics)?,
+    })
+}
+
+fn load_overlay(connection: &Connection, owner: &Owner, id: &str) -> Result<SerdeOverlay> {
+    let mut statement = connection.prepare(
+        "SELECT record, checked, comments, category, description FROM overlays
+         WHERE owner = ?1 AND account = ?2",
+    )?;
+    let records = statement
+        .query_map(params![&**owner, id], |row| {
+            Ok((
+                row.get(0)?,
+                RecordOverlay {
+                    checked: row.get(1)?,
+                    comments: row.get(2)?,
+                    category: row.get(3)?,
+                    description: row.get(4)?,
+                },
+            ))
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok(SerdeOverlay { records })
+}
+
+/// Merges an edit into the stored one, keeping the fields the edit does not set.
+fn put_overlay(
+    connection: &Connection,
+    owner: &Owner,
+    id: &str,
+    record: &str,
+    edit: &RecordOverlay,
+) -> Result<()> {
+    connection.execute(
+        "INSERT INTO overlays (owner, account, record, checked, comments, category, description)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT (owner, account, record) DO UPDATE SET
+            checked = coalesce(excluded.checked, checked),
+            comments = coalesce(excluded.comments, comments),
+            category = coalesce(excluded.category, category),
+            description = coalesce(excluded.description, description)",
+        params![
+            &**owner,
+            id,
+            record,
+            edit.checked,
+            edit.comments,
+            edit.category,
+            edit.description,
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+    };
+
+    use crate::{
+        cli::BankFormat,
+        error::BadRequest,
+        handler::ledger::{
+            create::CreateLedgerRequest, records::patch::UpdateRecordRequest,
+            update::UpdateLedgerRequest,
+        },
+        money::Money,
+        realms::portfolio::{
+            adapter::{Adapter, Production},
+            state::{Owner, Portfolio},
+        },
+    };
+
+    use super::Sqlite;
+
+    const TRANSACTIONS: &str = r#""HEADER","TRNT","TransactionID","Symbol","Description","CurrencyPrimary","Quantity","TradePrice","IBCommission","TradeDate"
+"DATA","TRNT","T-1","VT","VANGUARD TOT WORLD STK ETF","USD","10","113","-1.05","2024-06-24"
+"HEADER","CTRN","TransactionID","Symbol","Description","CurrencyPrimary","Amount","Type","SettleDate"
+"DATA","CTRN","C-1","","CASH RECEIPTS / ELECTRONIC FUND TRANSFERS","USD","2000","Deposits/Withdrawals","2024-06-20"
+"#;
+
+    /// The accounts with their ledgers and records in a fixed order, as some parsers collect them
+    /// in hash maps.
+    fn accounts(portfolio: &Portfolio) -> serde_json::Value {
+        let mut accounts = portfolio.accounts.clone();
+        for account in accounts.values_mut() {
+            account.ledgers.sort_by_key(|l| l.symbol.to_string());
+            for ledger in &mut account.ledgers {
+                ledger
+                    .records
+                    .sort_by(|a, b| (a.date, &a.id).cmp(&(b.date, &b.id)));
+            }
+        }
+        serde_json::to_value(accounts).unwrap()
+    }
+
+    #[test]
+    fn import_matches_files() {
+        let production = Production::new(PathBuf::from("portfolio-test"));
+        let sqlite = Sqlite::in_memory().unwrap();
+
+        let owners = sqlite.import(Path::new("portfolio-test")).unwrap();
+        assert_eq!(owners, vec![Owner::new("123456789".to_string())]);
+
+        let owner = owners[0].clone();
+        let expected = production.load(owner.clone()).unwrap();
+        let imported = sqlite.load(owner.clone()).unwrap();
+        assert_eq!(imported.base_currency, expected.base_currency);
+        assert_eq!(accounts(&imported), accounts(&expected));
+        assert_eq!(
+            sqlite.list_files(&owner).unwrap(),
+            production.list_files(&owner).unwrap()
+        );
+
+        // Importing again replaces the portfolio.
+        sqlite.import(Path::new("portfolio-test")).unwrap();
+        let reimported = sqlite.load(owner).unwrap();
+        assert_eq!(accounts(&reimported), accounts(&expected));
+    }
+
+    #[tokio::test]
+    async fn store_files_and_edits() {
+        let sqlite = Sqlite::in_memory().unwrap();
+        let owner = Owner::new("owner".to_string());
+        let portfolio = || sqlite.load(owner.clone()).unwrap();
+
+        let id = sqlite
+            .create_ledger(
+                portfolio(),
+                CreateLedgerRequest {
+                    format: BankFormat::Ibkr,
+                    custom_format: None,
+                    initial_balance: None,
+                    initial_date: None,
+                    name: "Broker".to_string(),
+                    spending: false,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(id, "broker");
+        sqlite
+            .add_file(&owner, &id, "2024.csv", TRANSACTIONS.into())
+            .unwrap();
+        sqlite
+            .add_file(&owner, &id, "broken.csv", b"no flex query".to_vec())
+            .unwrap();
+
+        // A broken file is kept and fails to load, like on disk.
+        assert_eq!(
+            sqlite.read_file(&owner, &id, "broken.csv").unwrap(),
+            b"no flex query"
+        );
+        assert!(sqlite.load_file(&owner, &id, "broken.csv").is_err());
+        assert!(sqlite.load(owner.clone()).is_err());
+        sqlite.delete_file(&owner, &id, "broken.csv").unwrap();
+
+        let account = &portfolio().accounts["broker"];
+        assert_eq!(account.ledgers.len(), 2);
+        let record = account
+            .ledgers
+            .iter()
+            .flat_map(|l| &l.records)
+            .find(|r| r.id == "USD:C-1")
+            .unwrap()
+            .clone();
+        assert_eq!(record.amount, Money::from(2000));
+
+        sqlite
+            .update_records(
+                &owner,
+                &id,
+                vec![UpdateRecordRequest {
+                    id: record.id.clone(),
+                    checked: Some(true),
+                    comments: None,
+                    category: Some("deposit".to_string()),
+                    description: None,
+                }],
+            )
+            .unwrap();
+        sqlite
+            .update_records(
+                &owner,
+                &id,
+                vec![UpdateRecordRequest {
+                    id: record.id.clone(),
+                    checked: None,
+                    comments: Some("salary".to_string()),
+                    category: None,
+                    description: None,
+                }],
+            )
+            .unwrap();
+
+        // Renaming the account moves its files and edits.
+        let id = sqlite
+            .update_ledger(
+                portfolio(),
+                id,
+                UpdateLedgerRequest {
+                    format: BankFormat::Ibkr,
+                    custom_format: None,
+                    initial_balance: None,
+                    initial_date: None,
+                    name: "IBKR".to_string(),
+                    spending: true,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(id, "ibkr");
+        let portfolio = portfolio();
+        let account = &portfolio.accounts["ibkr"];
+        assert!(account.spending);
+        assert!(account.ledgers.iter().all(|l| l.name == "ibkr"));
+        let edited = account
+            .ledgers
+            .iter()
+            .flat_map(|l| &l.records)
+            .find(|r| r.id == "USD:C-1")
+            .unwrap();
+        assert!(edited.checked);
+        assert_eq!(edited.category, "deposit");
+        assert_eq!(edited.comments, "salary");
+
+        // Both legs of a trade share the transaction id, but are edited on their own.
+        sqlite
+            .update_records(
+                &owner,
+                &id,
+                vec![UpdateRecordRequest {
+                    id: "USD:T-1".to_string(),
+                    checked: Some(true),
+                    comments: None,
+                    category: None,
+                    description: None,
+                }],
+            )
+            .unwrap();
+        let records = sqlite.load(owner.clone()).unwrap().accounts["ibkr"]
+            .ledgers
+            .iter()
+            .flat_map(|l| l.records.clone())
+            .map(|r| (r.id, r.checked))
+            .collect::<HashMap<_, _>>();
+        assert!(records["USD:T-1"]);
+        assert!(!records["VT:T-1"]);
+        let overlay = sqlite.add_file(&owner, &id, ".overlay.yaml", vec![]);
+        assert!(overlay.unwrap_err().is::<BadRequest>());
+        let overlay = sqlite.delete_file(&owner, &id, ".overlay.yaml");
+        assert!(overlay.unwrap_err().is::<BadRequest>());
+        assert_eq!(
+            sqlite.list_files(&owner).unwrap()["ibkr"],
+            vec![PathBuf::from("2024.csv")]
+        );
+
+        sqlite.delete_ledger(portfolio, &id).await.unwrap();
+        assert!(sqlite.list_files(&owner).unwrap().is_empty());
+        assert!(sqlite.read_file(&owner, &id, "2024.csv").is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Datelike, Months, NaiveDate};
+
+/// A flow counts as recurring if it occurs in at least this share of the observed months.
+const RECURRING_SHARE: f64 = 0.5;
+
+/// At most this many months before the forecast are used to detect the flows.
+const OBSERVED_MONTHS: u32 = 12;
+
+/// What the assets of a portfolio are held in, which determines their expected return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AssetClass {
+    Cash,
+    Stocks,
+}
+
+impl AssetClass {
+    /// The expected annual return and its standard deviation.
+    ///
+    /// These are long-run averages of savings accounts and of a global stock index, as the
+    /// history of a single portfolio is too short to estimate them from.
+    pub fn returns(self) -> (f64, f64) {
+        match self {
+            AssetClass::Cash => (0.005, 0.0),
+            AssetClass::Stocks => (0.06, 0.16),
+        }
+    }
+}
+
+/// A flow which occurs in most months, like a salary or the rent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecurringFlow {
+    pub name: String,
+    /// The average amount per month, negative for expenses.
+    pub monthly: f64,
+}
+
+/// The income and expenses of the months before the forecast.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flows {
+    pub recurring: Vec<RecurringFlow>,
+    /// The net amount of the other flows in each observed month, e.g. bonuses or holidays.
+    pub irregular: Vec<f64>,
+}
+
+impl Flows {
+    /// Splits the flows of the complete months before `end` into recurring and irregular ones.
+    ///
+    /// Flows are told apart by their name, usually the category. The first month with a flow
+    /// starts the observation, so a young portfolio is not diluted by months without data.
+    pub fn detect(flows: &[(NaiveDate, String, f64)], end: NaiveDate) -> Self {
+        let end = end.with_day(1).unwrap();
+        let months = flows
+            .iter()
+            .map(|(date, _, _)| date.with_day(1).unwrap())
+            .filter(|month| *month < end)
+            .min()
+            .map_or(0, |first| {
+                let months = (end.year() - first.year()) * 12 + end.month() as i32;
+                (months - first.month() as i32) as u32
+            })
+            .min(OBSERVED_MONTHS);
+        if months == 0 {
+            return Self {
+                recurring: vec![],
+                irregular: vec![0.0],
+            };
+        }
+        let start = end - Months::new(months);
+
+        // The amount by name and month.
+        let mut names = BTreeMap::<&str, HashMap<NaiveDate, f64>>::new();
+        for (date, name, amount) in flows {
+            let month = date.with_day(1).unwrap();
+            if (start..end).contains(&month) {
+                *names.entry(name).or_default().entry(month).or_default() += amount;
+            }
+        }
+
+        let mut recurring = vec![];
+        let mut irregular = HashMap::<NaiveDate, f64>::new();
+        for (name, by_month) in names {
+            if by_month.len() as f64 >= months as f64 * RECURRING_SHARE {
+                recurring.push(RecurringFlow {
+                    name: name.to_string(),
+                    monthly: by_month.values().sum::<f64>() / months as f64,
+                });
+            } else {
+                for (month, amount) in by_month {
+                    *irregular.entry(month).or_default() += amount;
+                }
+            }
+        }
+        recurring.sort_by(|a, b| b.monthly.abs().total_cmp(&a.monthly.abs()));
+
+        Self {
+            recurring,
+            irregular: (0..months)
+                .map(|i| start + Months::new(i))
+                .map(|month| irregular.get(&month).copied().unwrap_or_default())
+                .collect(),
+        }
+    }
+
+    /// The expected net flow per month.
+    pub fn monthly(&self) -> f64 {
+        let irregular = self.irregular.iter().sum::<f64>() / self.irregular.len().max(1) as f64;
+        self.recurring.iter().map(|f| f.monthly).sum::<f64>() + irregular
+    }
+}
+
+/// The percentiles of the simulated values at the end of each month.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Band {
+    pub median: Vec<f64>,
+    pub low: Vec<f64>,
+    pub high: Vec<f64>,
+}
+
+/// Simulates the value of the assets over `horizon` months on many random paths.
+///
+/// Each month the assets grow by a random return of their class, and the recurring flows plus
+/// the irregular flows of a random observed month are added to the cash. `percentile` is the
+/// lower end of the band, e.g. 0.1 for the 10th to the 90th percentile.
+pub fn simulate(
+    assets: &BTreeMap<AssetClass, f64>,
+    flows: &Flows,
+    horizon: u32,
+    paths: usize,
+    percentile: f64,
+) -> Band {
+    let recurring = flows.recurring.iter().map(|f| f.monthly).sum::<f64>();
+    let mut random = Random::default();
+    let mut months = vec![Vec::with_capacity(paths); horizon as usize];
+    for _ in 0..paths {
+        let mut values = assets.clone();
+        values.entry(AssetClass::Cash).or_default();
+        for month in months.iter_mut() {
+            for (class, value) in values.iter_mut() {
+                let (mean, deviation) = class.returns();
+                // Log-normal monthly returns whose annual mean is the expected return.
+                let drift = (1.0 + mean).ln() / 12.0 - deviation.powi(2) / 24.0;
+                *value *= (drift + deviation / 12f64.sqrt() * random.normal()).exp();
+            }
+            let irregular = flows.irregular[random.below(flows.irregular.len())];
+            *values.get_mut(&AssetClass::Cash).unwrap() += recurring + irregular;
+            month.push(values.values().sum::<f64>());
+        }
+    }
+
+    let at = |values: &[f64], p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+    let mut band = Band {
+        median: vec![],
+        low: vec![],
+        high: vec![],
+    };
+    for mut values in months {
+        values.sort_by(f64::total_cmp);
+        band.median.push(at(&values, 0.5));
+        band.low.push(at(&values, percentile));
+        band.high.push(at(&values, 1.0 - percentile));
+    }
+    band
+}
+
+/// A SplitMix64 generator with a fixed seed, so the same portfolio always gets the same
+/// forecast.
+struct Random(u64);
+
+impl Default for Random {
+    fn default() -> Self {
+        Self(0x5EED)
+    }
+}
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A uniform number in `(0, 1]`.
+    fn uniform(&mut self) -> f64 {
+        ((self.next() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// A standard normal number by the Box-Muller transform.
+    fn normal(&mut self) -> f64 {
+        let (u, v) = (self.uniform(), self.uniform());
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashSet};
+
+    use chrono::NaiveDate;
+
+    use super::{simulate, AssetClass, Flows};
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn detect_recurring_flows() {
+        let mut flows = vec![];
+        for month in 1..=12 {
+            let day = |day| NaiveDate::from_ymd_opt(2023, month, day).unwrap();
+            flows.push((day(25), "Salary".to_string(), 5000.0));
+            flows.push((day(1), "Rent".to_string(), -2000.0));
+            if month % 2 == 0 {
+                flows.push((day(15), "Insurance".to_string(), -120.0));
+            }
+        }
+        flows.push((date("2023-12-20"), "Bonus".to_string(), 10000.0));
+        flows.push((date("2023-07-10"), "Holidays".to_string(), -3000.0));
+        // The current month is not complete yet.
+        flows.push((date("2024-01-05"), "Car".to_string(), -20000.0));
+
+        let detected = Flows::detect(&flows, date("2024-01-10"));
+        let names = detected.recurring.iter().map(|f| f.name.as_str());
+        assert_eq!(
+            names.collect::<HashSet<_>>(),
+            HashSet::from(["Salary", "Rent", "Insurance"])
+        );
+        assert_eq!(detected.recurring[0].monthly, 5000.0);
+        assert_eq!(detected.recurring[2].monthly, -60.0);
+        assert_eq!(detected.irregular.len(), 12);
+        assert_eq!(detected.irregular[6], -3000.0);
+        assert_eq!(detected.irregular[11], 10000.0);
+        assert_eq!(detected.monthly(), 5000.0 - 2000.0 - 60.0 + 7000.0 / 12.0);
+
+        // Only the months since the first flow are observed.
+        let since_june = flows
+            .into_iter()
+            .filter(|(date, _, _)| *date >= self::date("2023-06-01"))
+            .collect::<Vec<_>>();
+        let detected = Flows::detect(&since_june, date("2024-01-10"));
+        assert_eq!(detected.irregular.len(), 7);
+        assert_eq!(detected.recurring[0].monthly, 5000.0);
+    }
+
+    #[test]
+    fn simulate_band() {
+        let flows = Flows::detect(
+            &[(date("2024-05-25"), "Salary".to_string(), 1000.0)],
+            date("2024-06-10"),
+        );
+
+        // Cash only grows by its fixed return and the flows.
+        let cash = BTreeMap::from([(AssetClass::Cash, 10000.0)]);
+        let band = simulate(&cash, &flows, 12, 100, 0.1);
+        assert_eq!(band.low, band.high);
+        assert!((band.median[0] - (10000.0 * 1.005f64.powf(1.0 / 12.0) + 1000.0)).abs() < 1e-6);
+        assert!(band.median[11] > 22000.0);
+
+        // Stocks spread out over time.
+        let stocks = BTreeMap::from([(AssetClass::Stocks, 100000.0)]);
+        let band = simulate(&stocks, &flows, 24, 1000, 0.1);
+        let width = |i: usize| band.high[i] - band.low[i];
+        assert!(width(23) > width(0) * 2.0);
+        assert!(band.low[23] < band.median[23] && band.median[23] < band.high[23]);
+        assert!(band.median[23] > 100000.0 + 24.0 * 1000.0);
+
+        // The same inputs give the same forecast.
+        assert_eq!(simulate(&stocks, &flows, 24, 1000, 0.1), band);
+    }
+}
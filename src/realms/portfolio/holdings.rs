@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+
+use chrono::NaiveDate;
+
+use crate::banks::{ExtendedLedgerRecord, LedgerKind};
+
+use super::state::{Account, Stock};
+
+/// Quantities below this are rounding errors of earlier trades.
+const EPSILON: f64 = 1e-9;
+
+/// The shares of a stock held in one account and what was paid for them.
+///
+/// All amounts are in the quote currency of the stock.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Position {
+    pub quantity: f64,
+    /// The cost of the held shares, where every sell takes the average price of the held shares.
+    pub average_cost_basis: f64,
+    /// The cost of the held shares, where every sell takes the oldest shares first.
+    pub fifo_cost_basis: f64,
+    /// The gain of each sell against the FIFO cost basis.
+    pub realized: Vec<(NaiveDate, f64)>,
+}
+
+impl Position {
+    /// Replays the records of a stock ledger in date order.
+    ///
+    /// Shares without a price, e.g. from a spin-off, are held at no cost. Sells without a price
+    /// reduce the position without realizing a gain.
+    pub fn new(records: &[ExtendedLedgerRecord]) -> Self {
+        let mut records = records.iter().collect::<Vec<_>>();
+        records.sort_by_key(|r| r.date);
+
+        let mut position = Self::default();
+        // The remaining quantity and price of each purchase.
+        let mut lots = VecDeque::<(f64, f64)>::new();
+        for record in records {
+            let amount = record.amount.to_f64();
+            position.quantity += amount;
+            if amount > 0.0 {
+                let price = record.price.unwrap_or_default();
+                lots.push_back((amount, price));
+                position.average_cost_basis += amount * price;
+                continue;
+            }
+
+            let held = lots.iter().map(|(quantity, _)| quantity).sum::<f64>();
+            let mut remaining = -amount;
+            let mut cost = 0.0;
+            while remaining > EPSILON {
+                let Some(lot) = lots.front_mut() else {
+                    break;
+                };
+                let sold = lot.0.min(remaining);
+                cost += sold * lot.1;
+                lot.0 -= sold;
+                remaining -= sold;
+                if lot.0 <= EPSILON {
+                    lots.pop_front();
+                }
+            }
+
+            // Shares which were never bought can not be sold at a gain or a loss.
+            let sold = -amount - remaining;
+            if held > EPSILON {
+                position.average_cost_basis -= position.average_cost_basis * sold / held;
+            }
+            if let Some(price) = record.price {
+                position.realized.push((record.date, sold * price - cost));
+            }
+        }
+        position.fifo_cost_basis = lots.iter().map(|(quantity, price)| quantity * price).sum();
+
+        position
+    }
+}
+
+/// Sums the positions of all accounts by symbol.
+pub fn stocks<'a>(accounts: impl IntoIterator<Item = &'a Account>) -> Vec<Stock> {
+    let mut stocks = Vec::<Stock>::new();
+    for ledger in accounts
+        .into_iter()
+        .flat_map(|a| &a.ledgers)
+        .filter(|l| l.kind == LedgerKind::Stock)
+    {
+        let position = Position::new(&ledger.records);
+        let symbol = ledger.symbol.to_string();
+        if let Some(stock) = stocks.iter_mut().find(|s| s.symbol == symbol) {
+            stock.shares += position.quantity;
+            stock.cost_basis += position.fifo_cost_basis;
+        } else {
+            stocks.push(Stock {
+                symbol,
+                shares: position.quantity,
+                cost_basis: position.fifo_cost_basis,
+            });
+        }
+    }
+    stocks.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    stocks
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::{
+        banks::{ExtendedLedgerRecord, LedgerRecord},
+        money::Money,
+        processing::process,
+        realms::portfolio::{rules::Rules, state::Action},
+    };
+
+    use super::Position;
+
+    fn trade(date: &str, amount: f64, price: Option<f64>) -> LedgerRecord {
+        LedgerRecord {
+            id: None,
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            amount: Money::from_f64(amount).unwrap(),
+            description: format!("{amount} VT"),
+            category: "Broker".to_string(),
+            action: Action::Update,
+            price,
+        }
+    }
+
+    fn records(trades: Vec<LedgerRecord>) -> Vec<ExtendedLedgerRecord> {
+        process("ibkr", trades, None, None, &Rules::default()).unwrap()
+    }
+
+    #[test]
+    fn fifo_and_average_cost() {
+        let position = Position::new(&records(vec![
+            trade("2024-01-10", 10.0, Some(100.0)),
+            trade("2024-02-10", 10.0, Some(120.0)),
+            trade("2024-03-10", -15.0, Some(130.0)),
+            trade("2025-01-10", -2.0, Some(90.0)),
+        ]));
+
+        assert_eq!(position.quantity, 3.0);
+        // 1000 + 1200 at an average of 110, minus 15 and 2 shares at that average.
+        assert!((position.average_cost_basis - 330.0).abs() < 1e-9);
+        // The oldest lot is gone and 3 shares of the second one remain.
+        assert_eq!(position.fifo_cost_basis, 360.0);
+        assert_eq!(
+            position.realized,
+            vec![
+                // 15 * 130 - (10 * 100 + 5 * 120)
+                (NaiveDate::from_ymd_opt(2024, 3, 10).unwrap(), 350.0),
+                // 2 * 90 - 2 * 120
+                (NaiveDate::from_ymd_opt(2025, 1, 10).unwrap(), -60.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn shares_without_price() {
+        let position = Position::new(&records(vec![
+            trade("2024-01-10", 10.0, Some(100.0)),
+            trade("2024-02-10", 3.0, None),
+            trade("2024-03-10", -11.0, None),
+        ]));
+
+        assert_eq!(position.quantity, 2.0);
+        assert_eq!(position.fifo_cost_basis, 0.0);
+        assert!(position.realized.is_empty());
+    }
+}
@@ -0,0 +1,25 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// User edits to the records of one account, stored next to the account's files.
+///
+/// The overlay is keyed by the stable record id, so it survives re-uploading or replacing the
+/// files of an account.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SerdeOverlay {
+    #[serde(default)]
+    pub records: HashMap<String, RecordOverlay>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordOverlay {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comments: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
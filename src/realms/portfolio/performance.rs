@@ -0,0 +1,264 @@
+use std::collections::HashSet;
+
+use chrono::NaiveDate;
+
+use crate::{
+    banks::{ExtendedLedger, ExtendedLedgerRecord, LedgerKind},
+    processing::unscoped_id,
+};
+
+use super::state::{Account, Action};
+
+/// Gets the cash records of an account which move money in or out of it.
+///
+/// Dividends, interest, taxes and fees are part of the return. So are the cash legs of trades,
+/// which are the cash records with the transaction id of a record of a stock ledger.
+pub fn external_flows(account: &Account) -> Vec<(&ExtendedLedger, &ExtendedLedgerRecord)> {
+    let trades = account
+        .ledgers
+        .iter()
+        .filter(|l| l.kind == LedgerKind::Stock)
+        .flat_map(|l| l.records.iter().map(|r| unscoped_id(&l.symbol, &r.id)))
+        .collect::<HashSet<_>>();
+
+    account
+        .ledgers
+        .iter()
+        .filter(|l| l.kind == LedgerKind::Bank)
+        .flat_map(|l| l.records.iter().map(move |r| (l, r)))
+        .filter(|(_, r)| r.action == Action::Update)
+        .filter(|(l, r)| !trades.contains(unscoped_id(&l.symbol, &r.id)))
+        .collect()
+}
+
+/// Chains the daily returns of a value series, which removes the effect of the cash flows.
+///
+/// `values[i]` is the value at the end of day `i` and `flows[i]` the net external flow of that
+/// day, which is assumed to happen at its start. `start` is the value before the first day. Days
+/// without invested capital do not contribute.
+pub fn time_weighted_return(start: f64, values: &[f64], flows: &[f64]) -> f64 {
+    let mut growth = 1.0;
+    let mut previous = start;
+    for (value, flow) in values.iter().zip(flows) {
+        let invested = previous + flow;
+        if invested > f64::EPSILON {
+            growth *= value / invested;
+        }
+        previous = *value;
+    }
+    growth - 1.0
+}
+
+/// Finds the annual rate at which the net present value of the dated cash flows is zero.
+///
+/// Money paid in is negative, money paid out (including the final value) positive. Returns
+/// `None` if there is no such rate.
+pub fn xirr(flows: &[(NaiveDate, f64)]) -> Option<f64> {
+    let first = flows.iter().map(|(date, _)| *date).min()?;
+    let flows = flows
+        .iter()
+        .map(|(date, amount)| ((*date - first).num_days() as f64 / 365.0, *amount))
+        .collect::<Vec<_>>();
+    let npv = |rate: f64| {
+        flows
+            .iter()
+            .map(|(years, amount)| amount / (1.0 + rate).powf(*years))
+            .sum::<f64>()
+    };
+
+    // The net present value falls with the rate if the money is paid in before it is paid out,
+    // so the root is bracketed and found by bisection.
+    let (mut low, mut high) = (-0.9999, 1.0);
+    while npv(high) > 0.0 {
+        high *= 2.0;
+        if high > 1e6 {
+            return None;
+        }
+    }
+    if npv(low).signum() == npv(high).signum() {
+        return None;
+    }
+    for _ in 0..200 {
+        let mid = (low + high) / 2.0;
+        if npv(mid).signum() == npv(low).signum() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Some((low + high) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::{
+        banks::{parse, ExtendedLedger, ExtendedLedgerRecord, Ledger, LedgerKind, LedgerRecord},
+        cli::BankFormat,
+        fx::{Currency, Symbol},
+        money::Money,
+        processing::{process, scoped_id},
+        realms::portfolio::{
+            rules::Rules,
+            state::{Account, Action, Owner},
+        },
+    };
+
+    use super::{external_flows, time_weighted_return, xirr};
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn ledger(
+        symbol: Symbol,
+        kind: LedgerKind,
+        records: &[(&str, f64, &str, Action)],
+    ) -> ExtendedLedger {
+        let records: Vec<_> = records
+            .iter()
+            .map(|(id, amount, description, action)| LedgerRecord {
+                id: Some(id.to_string()),
+                date: date("2024-06-24"),
+                amount: Money::from_f64(*amount).unwrap(),
+                description: description.to_string(),
+                category: "Broker".to_string(),
+                action: *action,
+                price: None,
+            })
+            .collect();
+        extended(Ledger {
+            name: "IBKR".to_string(),
+            symbol,
+            records,
+            kind,
+            statements: vec![],
+            quote_currency: None,
+            splits: vec![],
+        })
+    }
+
+    /// Processes a parsed ledger the way an account is built.
+    fn extended(ledger: Ledger) -> ExtendedLedger {
+        let records = process(&ledger.name, ledger.records, None, None, &Rules::default())
+            .unwrap()
+            .into_iter()
+            .map(|record| ExtendedLedgerRecord {
+                id: scoped_id(&ledger.symbol, &record.id),
+                ..record
+            })
+            .collect();
+        ExtendedLedger {
+            name: ledger.name,
+            symbol: ledger.symbol,
+            records,
+            kind: ledger.kind,
+            statements: ledger.statements,
+            quote_currency: ledger.quote_currency,
+            splits: ledger.splits,
+        }
+    }
+
+    fn account(ledgers: Vec<ExtendedLedger>) -> Account {
+        Account {
+            id: "broker".to_string(),
+            owner: Owner::new("owner".to_string()),
+            name: "Broker".to_string(),
+            format: BankFormat::Ibkr,
+            custom_format: None,
+            ledgers,
+            initial_balance: None,
+            initial_date: None,
+            spending: false,
+        }
+    }
+
+    #[test]
+    fn trades_and_income_are_not_flows() {
+        let account = account(vec![
+            ledger(
+                Symbol::from("VT"),
+                LedgerKind::Stock,
+                &[("T-1", 10.0, "VANGUARD TOT WORLD STK ETF", Action::Update)],
+            ),
+            ledger(
+                Symbol::Currency(Currency::USD),
+                LedgerKind::Bank,
+                &[
+                    ("C-1", 2000.0, "CASH RECEIPTS", Action::Update),
+                    ("T-1", -1130.0, "VANGUARD TOT WORLD STK ETF", Action::Update),
+                    ("T-1:commission", -1.0, "Commission", Action::Fee),
+                    ("C-2", 5.78, "VT CASH DIVIDEND", Action::Dividend),
+                    // Trades are told apart by their id, not their description.
+                    ("C-3", -1130.0, "VANGUARD TOT WORLD STK ETF", Action::Update),
+                ],
+            ),
+        ]);
+
+        let flows = external_flows(&account);
+        assert_eq!(flows.len(), 2);
+        assert_eq!(flows[0].1.amount, Money::from(2000));
+        assert_eq!(flows[1].1.id, "USD:C-3");
+    }
+
+    #[test]
+    fn reinvested_income_is_not_a_flow() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<OFX>
+  <INVSTMTMSGSRSV1>
+    <INVSTMTTRNRS>
+      <INVSTMTRS>
+        <CURDEF>USD</CURDEF>
+        <INVTRANLIST>
+          <REINVEST>
+            <INVTRAN><FITID>R-1</FITID><DTTRADE>20240626</DTTRADE><MEMO>VT DIVIDEND REINVESTMENT</MEMO></INVTRAN>
+            <SECID><UNIQUEID>VT</UNIQUEID><UNIQUEIDTYPE>TICKER</UNIQUEIDTYPE></SECID>
+            <INCOMETYPE>DIV</INCOMETYPE>
+            <TOTAL>-22.60</TOTAL>
+            <UNITS>0.2</UNITS>
+            <UNITPRICE>113</UNITPRICE>
+          </REINVEST>
+        </INVTRANLIST>
+      </INVSTMTRS>
+    </INVSTMTTRNRS>
+  </INVSTMTMSGSRSV1>
+</OFX>"#;
+        let parsed = parse("broker", "", content.to_string(), BankFormat::Ofx, None).unwrap();
+        assert_eq!(parsed.ledgers.len(), 2);
+        let account = account(parsed.ledgers.into_iter().map(extended).collect());
+
+        assert!(external_flows(&account).is_empty());
+    }
+
+    #[test]
+    fn deposits_are_not_returns() {
+        // 100 grow by 10%, then a deposit of 1000 which grows by 10% as well.
+        let values = [110.0, 1110.0, 1221.0];
+        let flows = [0.0, 1000.0, 0.0];
+        let twr = time_weighted_return(100.0, &values, &flows);
+        assert!((twr - 0.21).abs() < 1e-9, "{twr}");
+    }
+
+    #[test]
+    fn no_capital() {
+        assert_eq!(time_weighted_return(0.0, &[0.0, 0.0], &[0.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn money_weighted_return() {
+        let rate = xirr(&[(date("2023-01-01"), -1000.0), (date("2024-01-01"), 1100.0)]).unwrap();
+        assert!((rate - 0.1).abs() < 1e-6, "{rate}");
+
+        let rate = xirr(&[
+            (date("2023-01-01"), -1000.0),
+            (date("2023-07-02"), -1000.0),
+            (date("2024-01-01"), 2000.0),
+        ])
+        .unwrap();
+        assert!(rate.abs() < 1e-6, "{rate}");
+
+        assert_eq!(xirr(&[(date("2023-01-01"), 1000.0)]), None);
+        assert_eq!(xirr(&[]), None);
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::money::Money;
+
+/// The contents of the `rules.yaml` file next to `portfolio.yaml`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SerdeRules {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+/// A categorization rule.
+///
+/// All given matchers have to match for the rule to apply. Rules are evaluated in order and the
+/// first matching rule decides the category of a record.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Rule {
+    /// The category assigned to matching records.
+    pub category: String,
+    /// A regex which has to match the original description.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// The inclusive lower bound of the amount.
+    #[serde(default)]
+    pub min_amount: Option<Money>,
+    /// The inclusive upper bound of the amount.
+    #[serde(default)]
+    pub max_amount: Option<Money>,
+    /// The id of the account the record has to belong to.
+    #[serde(default)]
+    pub account: Option<String>,
+    /// The first day (inclusive) the rule applies to.
+    #[serde(default)]
+    #[ts(type = "number")]
+    pub from: Option<NaiveDate>,
+    /// The last day (inclusive) the rule applies to.
+    #[serde(default)]
+    #[ts(type = "number")]
+    pub to: Option<NaiveDate>,
+}
+
+/// A set of rules with their description regexes compiled.
+#[derive(Debug, Default)]
+pub struct Rules {
+    rules: Vec<(Rule, Option<Regex>)>,
+}
+
+impl Rules {
+    pub fn new(rules: &[Rule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let regex = rule
+                    .description
+                    .as_ref()
+                    .map(|d| {
+                        Regex::new(d)
+                            .with_context(|| format!("{d} is not a valid description regex"))
+                    })
+                    .transpose()?;
+                Ok((rule.clone(), regex))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Returns the category of the first rule matching the given record, if any.
+    pub fn categorize(
+        &self,
+        account: &str,
+        date: &NaiveDate,
+        amount: Money,
+        description: &str,
+    ) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|(rule, regex)| {
+                rule.account.as_ref().is_none_or(|a| a == account)
+                    && rule.from.is_none_or(|from| date >= &from)
+                    && rule.to.is_none_or(|to| date <= &to)
+                    && rule.min_amount.is_none_or(|min| amount >= min)
+                    && rule.max_amount.is_none_or(|max| amount <= max)
+                    && regex.as_ref().is_none_or(|r| r.is_match(description))
+            })
+            .map(|(rule, _)| rule.category.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::money::Money;
+
+    use super::{Rule, Rules};
+
+    fn rule(category: &str) -> Rule {
+        Rule {
+            category: category.to_string(),
+            description: None,
+            min_amount: None,
+            max_amount: None,
+            account: None,
+            from: None,
+            to: None,
+        }
+    }
+
+    #[test]
+    fn categorize() {
+        let rules = Rules::new(&[
+            Rule {
+                description: Some("(?i)^migros|coop".to_string()),
+                max_amount: Some(Money::ZERO),
+                ..rule("groceries")
+            },
+            Rule {
+                account: Some("ubs".to_string()),
+                min_amount: Some(Money::from(1000)),
+                from: NaiveDate::from_ymd_opt(2021, 1, 1),
+                ..rule("salary")
+            },
+        ])
+        .unwrap();
+        let date = NaiveDate::from_ymd_opt(2021, 1, 22).unwrap();
+
+        assert_eq!(
+            rules.categorize("revolut", &date, "-1.45".parse().unwrap(), "Coop"),
+            Some("groceries")
+        );
+        assert_eq!(
+            rules.categorize("revolut", &date, "1.45".parse().unwrap(), "Coop"),
+            None
+        );
+        assert_eq!(
+            rules.categorize("ubs", &date, Money::from(3688), "TECHNOKRAT GMBH"),
+            Some("salary")
+        );
+        assert_eq!(
+            rules.categorize("neon", &date, Money::from(3688), "TECHNOKRAT GMBH"),
+            None
+        );
+        assert_eq!(
+            rules.categorize(
+                "ubs",
+                &NaiveDate::from_ymd_opt(2020, 12, 22).unwrap(),
+                Money::from(3688),
+                "TECHNOKRAT GMBH"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_regex() {
+        assert!(Rules::new(&[Rule {
+            description: Some("(".to_string()),
+            ..rule("broken")
+        }])
+        .is_err());
+    }
+}
@@ -6,13 +6,23 @@ use serde::{Deserialize, Deserializer, Serialize};
 use time::macros::format_description;
 use ts_rs::TS;
 
-use crate::{banks::ExtendedLedger, cli::BankFormat, fx::Currency};
+use crate::{
+    banks::{CustomFormat, ExtendedLedger, Split},
+    cli::BankFormat,
+    fx::Currency,
+    money::Money,
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+use super::rules::Rule;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Portfolio {
     pub base_currency: Currency,
     pub stocks: Vec<Stock>,
     pub accounts: HashMap<String, Account>,
+    pub rules: Vec<Rule>,
+    /// The declared splits by stock symbol.
+    pub splits: HashMap<String, Vec<Split>>,
     pub owner: Owner,
 }
 
@@ -24,6 +34,9 @@ pub struct SerdePortfolio {
     pub stocks: Vec<Stock>,
     #[serde(default)]
     pub accounts: HashMap<String, SerdeAccount>,
+    /// Splits by stock symbol for brokers which do not report them.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub splits: HashMap<String, Vec<Split>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,7 +53,10 @@ pub struct SerdeAccount {
     pub owner: Owner,
     pub name: String,
     pub format: BankFormat,
-    pub initial_balance: Option<f64>,
+    /// The column mapping for [`BankFormat::Custom`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_format: Option<CustomFormat>,
+    pub initial_balance: Option<Money>,
     pub initial_date: Option<NaiveDate>,
     pub spending: bool,
 }
@@ -53,13 +69,29 @@ pub struct Account {
     pub owner: Owner,
     pub name: String,
     pub format: BankFormat,
+    pub custom_format: Option<CustomFormat>,
     pub ledgers: Vec<ExtendedLedger>,
-    pub initial_balance: Option<f64>,
+    pub initial_balance: Option<Money>,
     #[ts(type = "number")]
     pub initial_date: Option<NaiveDate>,
     pub spending: bool,
 }
 
+impl From<&Account> for SerdeAccount {
+    fn from(account: &Account) -> Self {
+        Self {
+            id: account.id.clone(),
+            owner: account.owner.clone(),
+            name: account.name.clone(),
+            format: account.format,
+            custom_format: account.custom_format.clone(),
+            initial_balance: account.initial_balance,
+            initial_date: account.initial_date,
+            spending: account.spending,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Transaction {
     #[serde(deserialize_with = "parse_date")]
@@ -81,12 +113,18 @@ where
         .map_err(serde::de::Error::custom)
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+/// What kind of transaction a record is.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export)]
 pub enum Action {
+    /// A regular movement of money like a purchase, a transfer or a trade.
     #[default]
     Update,
     Interest,
     Fee,
+    Dividend,
+    /// Taxes withheld or paid, e.g. on dividends.
+    Tax,
 }
 
 #[derive(
@@ -1,18 +1,32 @@
-use std::{env, sync::Arc};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    env,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
 use async_session::MemoryStore;
 use axum::extract::FromRef;
+use notify::RecommendedWatcher;
 use oauth2::{basic::BasicClient, AuthUrl, ClientId, ClientSecret, RedirectUrl, TokenUrl};
 use tokio::sync::Mutex;
 
-use crate::{fx::HistoryCache, realms::portfolio};
+use crate::{
+    fx::HistoryCache,
+    realms::portfolio::{self, adapter::cached::PortfolioCache, state::Owner},
+};
 
 // the application state
 #[derive(Clone)]
 pub struct AppState {
     pub cache: CacheState,
+    pub pending_uploads: PendingUploads,
     pub portfolio_adapter: PortfolioAdapter,
+    pub portfolio_cache: Arc<PortfolioCache>,
+    /// Invalidates the cached portfolios when their files change, as long as it lives.
+    pub portfolio_watcher: Option<Arc<RecommendedWatcher>>,
     pub oauth_client: BasicClient,
     pub session_store: MemoryStore,
     pub frontend_url: String,
@@ -20,11 +34,29 @@ pub struct AppState {
 
 impl AppState {
     pub fn new() -> Result<Self> {
-        let adapter = portfolio::adapter::Production::new("portfolio/".into());
+        // The files are used unless a database is configured.
+        let database = env::var("PORTFOLIO_DATABASE").ok();
+        let adapter: PortfolioAdapter = match &database {
+            Some(path) => Arc::new(portfolio::adapter::sqlite::Sqlite::open(Path::new(path))?),
+            None => Arc::new(portfolio::adapter::Production::new("portfolio/".into())),
+        };
+        let portfolio_cache = Arc::<PortfolioCache>::default();
+        // Files can also be edited by hand, the database only through the adapter.
+        let portfolio_watcher = match (&database, env::var("PORTFOLIO_WATCH")) {
+            (None, Ok(_)) => Some(Arc::new(portfolio_cache.watch(Path::new("portfolio/"))?)),
+            _ => None,
+        };
+        let adapter: PortfolioAdapter = Arc::new(portfolio::adapter::cached::Cached::new(
+            adapter,
+            portfolio_cache.clone(),
+        ));
         let frontend_url = env::var("FRONTEND_URL").context("Missing FRONTEND_URL!")?;
         Ok(Self {
             cache: Arc::new(Mutex::new(HistoryCache::load().unwrap())),
-            portfolio_adapter: Arc::new(adapter),
+            pending_uploads: PendingUploads::default(),
+            portfolio_adapter: adapter,
+            portfolio_cache,
+            portfolio_watcher,
             oauth_client: Self::oauth_client()?,
             session_store: MemoryStore::new(),
             frontend_url,
@@ -59,6 +91,75 @@ impl FromRef<AppState> for CacheState {
     }
 }
 
+pub type PendingUploads = Arc<Mutex<Pending>>;
+
+/// Uploads which were previewed but not yet confirmed, by owner, account id and file name.
+///
+/// Previews which are never confirmed expire, and every owner can only have a few at a time, so
+/// their content does not pile up in memory.
+#[derive(Default)]
+pub struct Pending {
+    uploads: HashMap<(Owner, String, String), PendingUpload>,
+}
+
+struct PendingUpload {
+    /// Identifies the previewed content, so a confirmation stores exactly what was previewed.
+    token: String,
+    content: Vec<u8>,
+    created: Instant,
+}
+
+impl Pending {
+    /// How long a preview can be confirmed.
+    const TTL: Duration = Duration::from_secs(30 * 60);
+    /// The most previews of an owner, beyond which the oldest ones are dropped.
+    const PER_OWNER: usize = 10;
+
+    pub fn insert(&mut self, key: (Owner, String, String), token: String, content: Vec<u8>) {
+        self.evict();
+        let owner = key.0.clone();
+        self.uploads.insert(
+            key,
+            PendingUpload {
+                token,
+                content,
+                created: Instant::now(),
+            },
+        );
+
+        let mut owned = self
+            .uploads
+            .iter()
+            .filter(|((o, _, _), _)| *o == owner)
+            .map(|(key, upload)| (upload.created, key.clone()))
+            .collect::<Vec<_>>();
+        owned.sort_by_key(|(created, _)| *created);
+        for (_, key) in owned.iter().rev().skip(Self::PER_OWNER) {
+            self.uploads.remove(key);
+        }
+    }
+
+    /// Takes the content of a preview if it was made with `token`.
+    pub fn take(&mut self, key: &(Owner, String, String), token: &str) -> Option<Vec<u8>> {
+        self.evict();
+        match self.uploads.entry(key.clone()) {
+            Entry::Occupied(entry) if entry.get().token == token => Some(entry.remove().content),
+            _ => None,
+        }
+    }
+
+    fn evict(&mut self) {
+        self.uploads
+            .retain(|_, upload| upload.created.elapsed() < Self::TTL);
+    }
+}
+
+impl FromRef<AppState> for PendingUploads {
+    fn from_ref(app_state: &AppState) -> PendingUploads {
+        app_state.pending_uploads.clone()
+    }
+}
+
 pub type PortfolioAdapter = Arc<dyn portfolio::adapter::Adapter>;
 
 impl FromRef<AppState> for PortfolioAdapter {
@@ -85,3 +186,36 @@ impl FromRef<AppState> for FrontendUrl {
         Self(state.frontend_url.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::realms::portfolio::state::Owner;
+
+    use super::Pending;
+
+    #[test]
+    fn evict_pending_uploads() {
+        let key = |owner: &str, name: usize| {
+            let owner = Owner::new(owner.to_string());
+            (owner, "ubs".to_string(), format!("{name}.csv"))
+        };
+        let mut pending = Pending::default();
+        for name in 0..=Pending::PER_OWNER {
+            pending.insert(key("a", name), "token".to_string(), vec![]);
+        }
+        pending.insert(key("b", 0), "token".to_string(), vec![]);
+
+        // The oldest upload of the owner was dropped, the other owner keeps theirs.
+        assert_eq!(pending.uploads.len(), Pending::PER_OWNER + 1);
+        assert_eq!(pending.take(&key("a", 0), "token"), None);
+        assert_eq!(pending.take(&key("a", 1), "other"), None);
+        assert_eq!(pending.take(&key("a", 1), "token"), Some(vec![]));
+        assert_eq!(pending.take(&key("a", 1), "token"), None);
+
+        let expired = Instant::now() - Pending::TTL - Duration::from_secs(1);
+        pending.uploads.get_mut(&key("b", 0)).unwrap().created = expired;
+        assert_eq!(pending.take(&key("b", 0), "token"), None);
+    }
+}